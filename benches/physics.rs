@@ -0,0 +1,75 @@
+//! Inputs are generated from fixed seeds, so numbers are comparable between commits:
+//! `cargo bench -- --save-baseline before`, then `cargo bench -- --baseline before`.
+use circles::circles_app::broad_phase;
+use circles::circles_app::circle::Circle;
+use circles::circles_app::generators;
+use circles::circles_app::scalar::{Scalar, Vector};
+use circles::circles_app::simulation::Simulation;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use slog::{o, Discard, Logger};
+use std::time::Duration;
+
+const SEED: u64 = 45;
+const COUNTS: [usize; 3] = [1_000, 10_000, 100_000];
+/// Field area per circle. With radii in 1..2 it gives packing fraction about 0.07.
+const AREA_PER_CIRCLE: Scalar = 100.0;
+
+fn field(count: usize) -> (Vector, Vec<Circle>) {
+    let side = (count as Scalar * AREA_PER_CIRCLE).sqrt();
+    let field_size = Vector::new(side, side);
+    let circles = generators::poisson_disk(field_size, count, (1.0, 2.0), 10.0, SEED);
+    assert_eq!(circles.len(), count);
+    (field_size, circles)
+}
+
+fn collide(c: &mut Criterion) {
+    let a = Circle::new(Vector::new(0.0, 0.0), 1.0, Vector::new(1.0, 0.5));
+    let b = Circle::new(Vector::new(1.5, 0.5), 0.7, Vector::new(-2.0, 0.0));
+    c.bench_function("circle_collide", |bencher| {
+        bencher.iter_batched_ref(|| (a, b), |(a, b)| a.collide(b), BatchSize::SmallInput)
+    });
+}
+
+fn step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("simulation_step");
+    group.sample_size(10);
+    for &count in COUNTS.iter() {
+        let (field_size, circles) = field(count);
+        let mut simulation = Simulation::new(field_size, Logger::root(Discard, o!()));
+        for circle in circles {
+            simulation.add_circle(circle);
+        }
+        let state = simulation.state().clone();
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &state,
+            |bencher, state| {
+                bencher.iter_batched_ref(
+                    || Simulation::from_state(state.clone(), Logger::root(Discard, o!())),
+                    |simulation| simulation.step(Duration::from_millis(10)),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn broad_phase(c: &mut Criterion) {
+    let mut group = c.benchmark_group("broad_phase");
+    group.sample_size(10);
+    for &count in COUNTS.iter() {
+        let (_, circles) = field(count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &circles,
+            |bencher, circles| bencher.iter(|| broad_phase::candidate_pairs(circles)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, collide, step, broad_phase);
+criterion_main!(benches);
@@ -0,0 +1,109 @@
+//! Benchmarks that need a Vulkan device are skipped when no driver is installed.
+use ash::vk;
+use circles::circles_app::camera::Camera;
+use circles::circles_app::circle::Circle;
+use circles::circles_app::generators;
+use circles::circles_app::scalar::{Scalar, Vector};
+use circles::vulkan::base::VulkanBase;
+use circles::vulkan::render::circle_geometry::{self, INDICES_PER_CIRCLE, VERTICES_PER_CIRCLE};
+use circles::vulkan::render::geometry_buffers::GeometryBuffers;
+use circles::vulkan::render::offscreen::OffscreenRenderer;
+use circles::vulkan::render::vertex::Vertex;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use glam::Vec2;
+use slog::{o, Discard, Logger};
+
+const SEED: u64 = 45;
+const COUNTS: [usize; 2] = [1_000, 10_000];
+const FIELD_SIZE: (Scalar, Scalar) = (800.0, 600.0);
+const RESOLUTION: vk::Extent2D = vk::Extent2D {
+    width: 800,
+    height: 600,
+};
+
+fn logger() -> Logger {
+    Logger::root(Discard, o!())
+}
+
+fn field(count: usize) -> (Camera, Vec<Circle>) {
+    let field_size: Vector = FIELD_SIZE.into();
+    let circles = generators::poisson_disk(field_size, count, (1.0, 2.0), 10.0, SEED);
+    assert_eq!(circles.len(), count);
+    (Camera::fit(field_size, viewport()), circles)
+}
+
+fn viewport() -> Vec2 {
+    Vec2::new(RESOLUTION.width as f32, RESOLUTION.height as f32)
+}
+
+fn geometry(count: usize) -> (Vec<Vertex>, Vec<u32>) {
+    let (camera, circles) = field(count);
+    circle_geometry::circle_quads(circles.iter(), &camera, viewport())
+}
+
+fn geometry_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("geometry_build");
+    for &count in COUNTS.iter() {
+        let (camera, circles) = field(count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &circles,
+            |bencher, circles| {
+                bencher.iter(|| circle_geometry::circle_quads(circles.iter(), &camera, viewport()))
+            },
+        );
+    }
+    group.finish();
+}
+
+fn geometry_upload(c: &mut Criterion) {
+    if !VulkanBase::is_available() {
+        eprintln!("Vulkan is not available, skipping geometry_upload");
+        return;
+    }
+    let mut base = VulkanBase::new("geometry_upload", logger());
+    let mut group = c.benchmark_group("geometry_upload");
+    for &count in COUNTS.iter() {
+        let (vertices, indices) = geometry(count);
+        let mut buffers = GeometryBuffers::with_capacity(
+            &base,
+            count * VERTICES_PER_CIRCLE,
+            count * INDICES_PER_CIRCLE,
+            logger(),
+        );
+        let vk_device = base.get_device().get_vk_device();
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_function(BenchmarkId::from_parameter(count), |bencher| {
+            bencher.iter(|| buffers.write(&vertices, &indices, vk_device))
+        });
+        buffers.destroy(vk_device);
+    }
+    group.finish();
+    base.destroy();
+}
+
+fn offscreen_render(c: &mut Criterion) {
+    if !VulkanBase::is_available() {
+        eprintln!("Vulkan is not available, skipping offscreen_render");
+        return;
+    }
+    let mut base = VulkanBase::new("offscreen_render", logger());
+    let mut group = c.benchmark_group("offscreen_render");
+    for &count in COUNTS.iter() {
+        let (vertices, indices) = geometry(count);
+        let mut renderer =
+            OffscreenRenderer::new(&base, RESOLUTION, vertices.len(), indices.len(), logger());
+        renderer.write_geometry(&base, &vertices, &indices);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_function(BenchmarkId::from_parameter(count), |bencher| {
+            bencher.iter(|| renderer.render(&base))
+        });
+        renderer.destroy(&base);
+    }
+    group.finish();
+    base.destroy();
+}
+
+criterion_group!(benches, geometry_build, geometry_upload, offscreen_render);
+criterion_main!(benches);
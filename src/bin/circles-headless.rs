@@ -0,0 +1,28 @@
+#[macro_use]
+extern crate slog;
+use circles::circles_app::headless::{self, RunOptions, USAGE};
+use slog::{Drain, Logger};
+use slog_term::{CompactFormat, TermDecorator};
+use std::process;
+
+fn main() {
+    let options = match RunOptions::from_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    let logger = init_logger();
+    if let Err(e) = headless::run(&options, logger.clone()) {
+        error!(logger, "Run failed: {}", e);
+        process::exit(1);
+    }
+}
+
+fn init_logger() -> Logger {
+    let term = TermDecorator::new().stderr().build();
+    let format = CompactFormat::new(term).build().fuse();
+    let sync = std::sync::Mutex::new(format).fuse();
+    Logger::root(sync, o!())
+}
@@ -0,0 +1,63 @@
+#[macro_use]
+extern crate slog;
+use circles::circles_app::sweep::{self, SweepSpec};
+use slog::{Drain, Logger};
+use slog_term::{CompactFormat, TermDecorator};
+use std::path::PathBuf;
+use std::process;
+
+const USAGE: &str = "\
+Usage: circles-sweep <spec.ron> [--output DIR] [--threads N]
+
+Runs every parameter point of the spec with every seed and writes runs.csv and results.csv
+into DIR, default `sweep`. Runs already in DIR/runs.csv are skipped, so an interrupted sweep
+is resumed by running the same command again.";
+
+fn main() {
+    let (spec_path, output_dir, threads) = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    let logger = init_logger();
+    let result = SweepSpec::load(&spec_path)
+        .and_then(|spec| sweep::run(&spec, &output_dir, threads, logger.clone()));
+    if let Err(e) = result {
+        error!(logger, "Sweep failed: {}", e);
+        process::exit(1);
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(
+    mut args: I,
+) -> Result<(PathBuf, PathBuf, Option<usize>), String> {
+    let mut spec = None;
+    let mut output_dir = PathBuf::from("sweep");
+    let mut threads = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => output_dir = args.next().ok_or("Missing value of --output")?.into(),
+            "--threads" => {
+                let value = args.next().ok_or("Missing value of --threads")?;
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("Invalid number: {}", value))?;
+                threads = Some(value);
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ if spec.is_none() => spec = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+    let spec = spec.ok_or("Sweep spec is not specified")?;
+    Ok((spec, output_dir, threads))
+}
+
+fn init_logger() -> Logger {
+    let term = TermDecorator::new().stderr().build();
+    let format = CompactFormat::new(term).build().fuse();
+    let sync = std::sync::Mutex::new(format).fuse();
+    Logger::root(sync, o!())
+}
@@ -0,0 +1,336 @@
+use crate::circles_app::events::CollisionEvent;
+use crate::circles_app::handle::CircleHandle;
+use crate::circles_app::scalar::{widen, Scalar, Vector};
+use crate::circles_app::simulation::Simulation;
+use std::collections::{BTreeMap, VecDeque};
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AnalysisSettings {
+    pub sample_interval: Duration,
+    pub rdf_bins: usize,
+    pub rdf_max_distance: Scalar,
+    /// Use minimum image distances for g(r), as if field was periodic.
+    pub periodic: bool,
+    /// Number of past samples mean squared displacement is computed against.
+    pub msd_lags: usize,
+}
+
+impl Default for AnalysisSettings {
+    fn default() -> Self {
+        Self {
+            sample_interval: Duration::from_millis(100),
+            rdf_bins: 100,
+            rdf_max_distance: 50.0,
+            periodic: false,
+            msd_lags: 50,
+        }
+    }
+}
+
+/// Accumulates g(r), mean squared displacement and collision statistics while simulation runs.
+pub struct Analysis {
+    settings: AnalysisSettings,
+    last_sample: Option<Duration>,
+    first_sample: Option<Duration>,
+    samples: usize,
+    rdf_counts: Vec<u64>,
+    rdf_density: f64,
+    history: BTreeMap<CircleHandle, VecDeque<(Duration, Vector)>>,
+    msd_sums: Vec<f64>,
+    msd_lag_times: Vec<f64>,
+    msd_counts: Vec<u64>,
+    collisions: u64,
+    speed_sum: f64,
+    population_sum: f64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MsdPoint {
+    pub lag_time: Scalar,
+    pub msd: Scalar,
+}
+
+impl Analysis {
+    pub fn new(settings: AnalysisSettings) -> Self {
+        Self {
+            settings,
+            last_sample: None,
+            first_sample: None,
+            samples: 0,
+            rdf_counts: vec![0; settings.rdf_bins],
+            rdf_density: 0f64,
+            history: BTreeMap::new(),
+            msd_sums: vec![0f64; settings.msd_lags],
+            msd_lag_times: vec![0f64; settings.msd_lags],
+            msd_counts: vec![0; settings.msd_lags],
+            collisions: 0,
+            speed_sum: 0f64,
+            population_sum: 0f64,
+        }
+    }
+
+    /// Forgets everything sampled so far, for when the simulation state is replaced.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.settings);
+    }
+
+    pub fn observe_event(&mut self, event: &CollisionEvent) {
+        if let CollisionEvent::ContactBegin(contact) = event {
+            if contact.impulse > 0.0 {
+                self.collisions += 1;
+            }
+        }
+    }
+
+    /// Takes a sample if `sample_interval` has passed since the previous one.
+    pub fn update(&mut self, simulation: &Simulation) {
+        let time = simulation.time();
+        if let Some(last) = self.last_sample {
+            if time < last + self.settings.sample_interval {
+                return;
+            }
+        }
+        self.sample(simulation);
+    }
+
+    pub fn sample(&mut self, simulation: &Simulation) {
+        let time = simulation.time();
+        self.last_sample = Some(time);
+        self.first_sample.get_or_insert(time);
+        self.samples += 1;
+
+        let positions: Vec<(CircleHandle, Vector)> = simulation
+            .circles()
+            .map(|(handle, circle)| (handle, circle.center()))
+            .collect();
+        let field_size = simulation.field_size();
+        self.sample_rdf(&positions, field_size);
+        self.sample_msd(&positions, time);
+
+        let population = positions.len() as f64;
+        self.population_sum += population;
+        if population > 0f64 {
+            let speeds: f64 = simulation
+                .circles()
+                .map(|(_, c)| widen(c.speed().length()))
+                .sum();
+            self.speed_sum += speeds / population;
+        }
+    }
+
+    fn sample_rdf(&mut self, positions: &[(CircleHandle, Vector)], field_size: Vector) {
+        let n = positions.len();
+        if n < 2 {
+            return;
+        }
+        let bin_width = self.settings.rdf_max_distance / self.settings.rdf_bins as Scalar;
+        for (i, (_, a)) in positions.iter().enumerate() {
+            for (_, b) in &positions[i + 1..] {
+                let mut d = *b - *a;
+                if self.settings.periodic {
+                    d -= field_size * (d / field_size).round();
+                }
+                let bin = (d.length() / bin_width) as usize;
+                if bin < self.settings.rdf_bins {
+                    self.rdf_counts[bin] += 2;
+                }
+            }
+        }
+        let area = widen(field_size.x() * field_size.y());
+        self.rdf_density += n as f64 * (n - 1) as f64 / area;
+    }
+
+    fn sample_msd(&mut self, positions: &[(CircleHandle, Vector)], time: Duration) {
+        let lags = self.settings.msd_lags;
+        let mut history = BTreeMap::new();
+        for &(handle, position) in positions {
+            let mut past = self.history.remove(&handle).unwrap_or_default();
+            for (lag, (past_time, past_position)) in past.iter().rev().enumerate() {
+                self.msd_sums[lag] += widen((position - *past_position).length_squared());
+                self.msd_lag_times[lag] += (time - *past_time).as_secs_f64();
+                self.msd_counts[lag] += 1;
+            }
+            past.push_back((time, position));
+            if past.len() > lags {
+                past.pop_front();
+            }
+            history.insert(handle, past);
+        }
+        self.history = history;
+    }
+
+    /// Pairs of `(r, g(r))` at bin centers.
+    pub fn radial_distribution(&self) -> Vec<(Scalar, Scalar)> {
+        let bin_width = widen(self.settings.rdf_max_distance / self.settings.rdf_bins as Scalar);
+        self.rdf_counts
+            .iter()
+            .enumerate()
+            .map(|(bin, &count)| {
+                let r = (bin as f64 + 0.5f64) * bin_width;
+                let shell = 2f64 * PI * r * bin_width;
+                let g = if self.rdf_density > 0f64 {
+                    count as f64 / (self.rdf_density * shell)
+                } else {
+                    0f64
+                };
+                (r as Scalar, g as Scalar)
+            })
+            .collect()
+    }
+
+    pub fn mean_squared_displacement(&self) -> Vec<MsdPoint> {
+        self.msd_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(lag, &count)| MsdPoint {
+                lag_time: (self.msd_lag_times[lag] / count as f64) as Scalar,
+                msd: (self.msd_sums[lag] / count as f64) as Scalar,
+            })
+            .collect()
+    }
+
+    /// Least squares slope of MSD over lag time divided by 4, as in 2D `MSD = 4 D t`.
+    pub fn diffusion_coefficient(&self) -> Scalar {
+        let points = self.mean_squared_displacement();
+        if points.len() < 2 {
+            return 0.0;
+        }
+        let n = points.len() as Scalar;
+        let mean_t = points.iter().map(|p| p.lag_time).sum::<Scalar>() / n;
+        let mean_msd = points.iter().map(|p| p.msd).sum::<Scalar>() / n;
+        let covariance: Scalar = points
+            .iter()
+            .map(|p| (p.lag_time - mean_t) * (p.msd - mean_msd))
+            .sum();
+        let variance: Scalar = points.iter().map(|p| (p.lag_time - mean_t).powi(2)).sum();
+        if variance > 0.0 {
+            covariance / variance / 4.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Collisions per circle per second.
+    pub fn collision_frequency(&self) -> Scalar {
+        let (first, last) = match (self.first_sample, self.last_sample) {
+            (Some(first), Some(last)) if last > first => (first, last),
+            _ => return 0.0,
+        };
+        let mean_population = self.population_sum / self.samples as f64;
+        if mean_population == 0f64 {
+            return 0.0;
+        }
+        (2f64 * self.collisions as f64 / (mean_population * (last - first).as_secs_f64())) as Scalar
+    }
+
+    /// Mean speed over collision frequency.
+    pub fn mean_free_path(&self) -> Scalar {
+        let frequency = self.collision_frequency();
+        if frequency == 0.0 || self.samples == 0 {
+            return Scalar::INFINITY;
+        }
+        (self.speed_sum / self.samples as f64) as Scalar / frequency
+    }
+
+    pub fn write_rdf_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "r,g")?;
+        for (r, g) in self.radial_distribution() {
+            writeln!(writer, "{},{}", r, g)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_msd_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "lag_time,msd")?;
+        for point in self.mean_squared_displacement() {
+            writeln!(writer, "{},{}", point.lag_time, point.msd)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_summary_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "samples,collisions,collision_frequency,mean_free_path,diffusion_coefficient"
+        )?;
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            self.samples,
+            self.collisions,
+            self.collision_frequency(),
+            self.mean_free_path(),
+            self.diffusion_coefficient()
+        )
+    }
+
+    /// Writes `rdf.csv`, `msd.csv` and `summary.csv` into `dir`.
+    pub fn write_csv_files(&self, dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        self.write_rdf_csv(BufWriter::new(File::create(dir.join("rdf.csv"))?))?;
+        self.write_msd_csv(BufWriter::new(File::create(dir.join("msd.csv"))?))?;
+        self.write_summary_csv(BufWriter::new(File::create(dir.join("summary.csv"))?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Analysis, AnalysisSettings};
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::simulation::Simulation;
+    use rand::{Rng, SeedableRng};
+    use rand_pcg::Pcg64Mcg;
+    use slog::{Discard, Logger};
+    use std::time::Duration;
+
+    #[test]
+    fn ballistic_msd() {
+        let mut sim = Simulation::new((1000.0, 1000.0).into(), Logger::root(Discard, o!()));
+        sim.add_circle(Circle::new((500.0, 500.0).into(), 1.0, (3.0, 4.0).into()));
+        let mut analysis = Analysis::new(AnalysisSettings {
+            sample_interval: Duration::from_millis(100),
+            msd_lags: 5,
+            ..AnalysisSettings::default()
+        });
+        for _ in 0..50 {
+            analysis.update(&sim);
+            sim.step(Duration::from_millis(50));
+        }
+
+        let msd = analysis.mean_squared_displacement();
+        assert_eq!(msd.len(), 5);
+        for point in msd {
+            let expected = (5.0 * point.lag_time).powi(2);
+            assert!((point.msd - expected).abs() < 1e-2 * expected);
+        }
+        assert_eq!(analysis.collision_frequency(), 0.0);
+    }
+
+    #[test]
+    fn uniform_gas_rdf_is_flat() {
+        let size = 100.0;
+        let mut sim = Simulation::new((size, size).into(), Logger::root(Discard, o!()));
+        let mut rng = Pcg64Mcg::seed_from_u64(11);
+        for _ in 0..2000 {
+            let center = (rng.gen_range(0.0, size), rng.gen_range(0.0, size));
+            sim.add_circle(Circle::new(center.into(), 0.1, (0.0, 0.0).into()));
+        }
+        let mut analysis = Analysis::new(AnalysisSettings {
+            rdf_bins: 10,
+            rdf_max_distance: 40.0,
+            periodic: true,
+            ..AnalysisSettings::default()
+        });
+        analysis.sample(&sim);
+
+        for (r, g) in analysis.radial_distribution() {
+            assert!((g - 1.0).abs() < 0.05, "g({}) = {}", r, g);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::circles_app::circle::Circle;
+use crate::circles_app::scalar::Scalar;
+use crate::circles_app::sphere::Sphere;
+use std::cmp::Ordering;
+
+/// Sweep and prune along x axis. Returns pairs of indices `(i, j)`, `i < j`, whose bounding boxes
+/// overlap. Pairs are sorted, so iteration order doesn't depend on circles positions.
+pub fn candidate_pairs(circles: &[Circle]) -> Vec<(usize, usize)> {
+    sweep_and_prune(circles, Circle::left, Circle::right, |a, b| {
+        a.top() <= b.bot() && b.top() <= a.bot()
+    })
+}
+
+/// `candidate_pairs` of the 3D mode.
+pub fn sphere_pairs(spheres: &[Sphere]) -> Vec<(usize, usize)> {
+    sweep_and_prune(
+        spheres,
+        |sphere| sphere.lower().x(),
+        |sphere| sphere.upper().x(),
+        |a, b| {
+            let (a_lower, a_upper) = (a.lower(), a.upper());
+            let (b_lower, b_upper) = (b.lower(), b.upper());
+            a_lower.y() <= b_upper.y()
+                && b_lower.y() <= a_upper.y()
+                && a_lower.z() <= b_upper.z()
+                && b_lower.z() <= a_upper.z()
+        },
+    )
+}
+
+/// `overlap` checks the remaining axes of a pair already overlapping along x.
+fn sweep_and_prune<T>(
+    items: &[T],
+    left: impl Fn(&T) -> Scalar,
+    right: impl Fn(&T) -> Scalar,
+    overlap: impl Fn(&T, &T) -> bool,
+) -> Vec<(usize, usize)> {
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    order.sort_by(|&a, &b| {
+        left(&items[a])
+            .partial_cmp(&left(&items[b]))
+            .unwrap_or(Ordering::Equal)
+            .then(a.cmp(&b))
+    });
+
+    let mut pairs = Vec::new();
+    for (k, &i) in order.iter().enumerate() {
+        let right = right(&items[i]);
+        for &j in &order[k + 1..] {
+            if left(&items[j]) > right {
+                break;
+            }
+            if overlap(&items[i], &items[j]) {
+                pairs.push((i.min(j), i.max(j)));
+            }
+        }
+    }
+    pairs.sort();
+    pairs
+}
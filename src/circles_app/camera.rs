@@ -0,0 +1,160 @@
+use crate::circles_app::scalar::{self, Scalar, Vector, Vector3};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+
+const PERSPECTIVE_FOV_Y: f32 = std::f32::consts::FRAC_PI_4;
+
+/// Maps world metres onto a viewport in pixels. Both have y pointing down.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Camera {
+    pub pixels_per_metre: f32,
+    /// World point shown in the middle of the viewport.
+    pub center: Vector,
+}
+
+impl Camera {
+    pub fn new(pixels_per_metre: f32, center: Vector) -> Self {
+        Self {
+            pixels_per_metre,
+            center,
+        }
+    }
+
+    /// Largest scale at which the whole field is visible, centered.
+    pub fn fit(field_size: Vector, viewport: Vec2) -> Self {
+        let scale = viewport / scalar::to_render(field_size);
+        Self::new(scale.min_element(), field_size / 2.0)
+    }
+
+    pub fn to_pixels(&self, length: Scalar) -> f32 {
+        scalar::narrow(length) * self.pixels_per_metre
+    }
+
+    /// Pixel coordinates with origin at the top left corner of the viewport.
+    pub fn world_to_screen(&self, point: Vector, viewport: Vec2) -> Vec2 {
+        // Subtracting in simulation precision keeps far away worlds exact near the camera.
+        scalar::to_render(point - self.center) * self.pixels_per_metre + viewport / 2f32
+    }
+
+    pub fn screen_to_world(&self, pixel: Vec2, viewport: Vec2) -> Vector {
+        self.center + scalar::from_render((pixel - viewport / 2f32) / self.pixels_per_metre)
+    }
+
+    /// Vulkan clip space, `(-1, -1)` is the top left corner of the viewport.
+    pub fn world_to_clip(&self, point: Vector, viewport: Vec2) -> Vec2 {
+        self.world_to_screen(point, viewport) / viewport * 2f32 - Vec2::one()
+    }
+}
+
+/// Camera of the 3D mode. World y points down as in 2D, so the world up is `-y`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PerspectiveCamera {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl PerspectiveCamera {
+    /// Looks at the center of the box slightly from above, with the whole box in view.
+    pub fn fit(field_size: Vector3) -> Self {
+        let size = scalar::to_render3(field_size);
+        let target = size / 2f32;
+        let radius = size.length() / 2f32;
+        let distance = radius / (PERSPECTIVE_FOV_Y / 2f32).sin();
+        let direction = Vec3::new(0f32, -0.35f32, -1f32).normalize();
+        Self {
+            eye: target + direction * distance,
+            target,
+            fov_y: PERSPECTIVE_FOV_Y,
+            near: (distance - radius) / 2f32,
+            far: (distance + radius) * 2f32,
+        }
+    }
+
+    /// Rotates the eye around the vertical axis through the target.
+    pub fn orbit(&mut self, angle: f32) {
+        self.eye = self.target + Quat::from_rotation_y(angle) * (self.eye - self.target);
+    }
+
+    /// Moves the eye towards the target, `factor` < 1 zooms in.
+    pub fn dolly(&mut self, factor: f32) {
+        self.eye = self.target + (self.eye - self.target) * factor;
+        self.near *= factor;
+        self.far *= factor;
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.eye, self.target, -Vec3::unit_y())
+    }
+
+    /// Right handed projection into Vulkan clip space: depth in `[0, 1]` and y pointing down.
+    pub fn projection(&self, aspect_ratio: f32) -> Mat4 {
+        let f = 1f32 / (self.fov_y / 2f32).tan();
+        let depth = self.far / (self.near - self.far);
+        Mat4::from_cols(
+            Vec4::new(f / aspect_ratio, 0f32, 0f32, 0f32),
+            Vec4::new(0f32, -f, 0f32, 0f32),
+            Vec4::new(0f32, 0f32, depth, -1f32),
+            Vec4::new(0f32, 0f32, depth * self.near, 0f32),
+        )
+    }
+
+    pub fn world_to_clip(&self, point: Vector3, aspect_ratio: f32) -> Vec4 {
+        let point = scalar::to_render3(point).extend(1f32);
+        self.projection(aspect_ratio) * self.view() * point
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Camera, PerspectiveCamera};
+    use crate::circles_app::scalar::{Vector, Vector3};
+    use glam::Vec2;
+
+    #[test]
+    fn fit_and_transform() {
+        let viewport = Vec2::new(800f32, 600f32);
+        let camera = Camera::fit(Vector::new(8.0, 4.0), viewport);
+        assert_eq!(camera.pixels_per_metre, 100f32);
+        assert_eq!(camera.to_pixels(0.5), 50f32);
+        assert_eq!(
+            camera.world_to_screen(Vector::new(0.0, 0.0), viewport),
+            Vec2::new(0f32, 100f32)
+        );
+        assert_eq!(
+            camera.world_to_clip(Vector::new(8.0, 2.0), viewport),
+            Vec2::new(1f32, 0f32)
+        );
+        assert_eq!(
+            camera.screen_to_world(Vec2::new(200f32, 300f32), viewport),
+            Vector::new(2.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn perspective_keeps_box_in_view() {
+        let field_size = Vector3::new(4.0, 3.0, 2.0);
+        let camera = PerspectiveCamera::fit(field_size);
+        let clip = camera.world_to_clip(field_size / 2.0, 4f32 / 3f32);
+        let ndc = clip.truncate() / clip.w();
+        assert!(ndc.x().abs() < 1e-5 && ndc.y().abs() < 1e-5);
+        assert!(ndc.z() > 0f32 && ndc.z() < 1f32);
+        for &corner in &[
+            (0.0, 0.0, 0.0),
+            (4.0, 0.0, 2.0),
+            (0.0, 3.0, 2.0),
+            (4.0, 3.0, 0.0),
+        ] {
+            let clip = camera.world_to_clip(corner.into(), 4f32 / 3f32);
+            let ndc = clip.truncate() / clip.w();
+            assert!(ndc.x().abs() <= 1f32 && ndc.y().abs() <= 1f32, "{:?}", ndc);
+            assert!(ndc.z() > 0f32 && ndc.z() < 1f32, "{:?}", ndc);
+        }
+        // Top of the box is drawn in the upper half and its right side on the right.
+        let top = camera.world_to_clip(Vector3::new(2.0, 0.0, 1.0), 4f32 / 3f32);
+        assert!(top.y() < 0f32);
+        let right = camera.world_to_clip(Vector3::new(4.0, 1.5, 1.0), 4f32 / 3f32);
+        assert!(right.x() > 0f32);
+    }
+}
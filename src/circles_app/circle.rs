@@ -1,70 +1,147 @@
-use glam::Vec2;
-use std::f32::consts::PI;
+use crate::circles_app::filter::CollisionFilter;
+use crate::circles_app::material::Material;
+use crate::circles_app::scalar::consts::PI;
+use crate::circles_app::scalar::{seconds, Scalar, Vector};
+use glam::Vec4;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-const DENSITY: f32 = 1f32;
-
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Circle {
-    center: Vec2,
-    radius: f32,
-    speed: Vec2,
+    center: Vector,
+    radius: Scalar,
+    speed: Vector,
+    filter: CollisionFilter,
+    material: Material,
+    species: u32,
+    color: Vec4,
+    lifetime: Option<Duration>,
 }
 
 impl Circle {
-    pub fn new(center: Vec2, radius: f32, speed: Vec2) -> Self {
+    pub fn new(center: Vector, radius: Scalar, speed: Vector) -> Self {
         Self {
             center,
             radius,
             speed,
+            filter: CollisionFilter::default(),
+            material: Material::default(),
+            species: 0,
+            color: Vec4::one(),
+            lifetime: None,
         }
     }
 
-    pub fn mass(&self) -> f32 {
-        DENSITY * PI * self.radius.powi(2)
+    pub fn mass(&self) -> Scalar {
+        self.material.density * PI * self.radius.powi(2)
+    }
+
+    pub fn kinetic_energy(&self) -> Scalar {
+        0.5 * self.mass() * self.speed.length_squared()
     }
 
-    pub fn center(&self) -> Vec2 {
+    pub fn center(&self) -> Vector {
         self.center
     }
 
-    pub fn left(&self) -> f32 {
+    pub fn set_center(&mut self, center: Vector) {
+        self.center = center;
+    }
+
+    pub fn left(&self) -> Scalar {
         self.center.x() - self.radius
     }
 
-    pub fn top(&self) -> f32 {
+    pub fn top(&self) -> Scalar {
         self.center.y() - self.radius
     }
 
-    pub fn right(&self) -> f32 {
+    pub fn right(&self) -> Scalar {
         self.center.x() + self.radius
     }
 
-    pub fn bot(&self) -> f32 {
+    pub fn bot(&self) -> Scalar {
         self.center.y() + self.radius
     }
 
-    pub fn radius(&self) -> f32 {
+    pub fn radius(&self) -> Scalar {
         self.radius
     }
 
-    pub fn speed(&self) -> Vec2 {
+    pub fn set_radius(&mut self, radius: Scalar) {
+        self.radius = radius;
+    }
+
+    pub fn speed(&self) -> Vector {
         self.speed
     }
 
+    pub fn set_speed(&mut self, speed: Vector) {
+        self.speed = speed;
+    }
+
+    pub fn filter(&self) -> CollisionFilter {
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: CollisionFilter) {
+        self.filter = filter;
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    pub fn species(&self) -> u32 {
+        self.species
+    }
+
+    pub fn set_species(&mut self, species: u32) {
+        self.species = species;
+    }
+
+    pub fn color(&self) -> Vec4 {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: Vec4) {
+        self.color = color;
+    }
+
+    /// Time left before the circle expires. `None` means it lives forever.
+    pub fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+
+    pub fn set_lifetime(&mut self, lifetime: Option<Duration>) {
+        self.lifetime = lifetime;
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.lifetime == Some(Duration::from_secs(0))
+    }
+
     pub fn is_intersect(&self, other: &Self) -> bool {
         (self.center - other.center).length() < (self.radius + other.radius)
     }
 
     pub fn update(&mut self, elapsed_time: Duration) {
-        self.center += elapsed_time.as_secs_f32() * self.speed;
+        self.center += seconds(elapsed_time) * self.speed;
+        if let Some(lifetime) = self.lifetime {
+            self.lifetime = Some(lifetime.checked_sub(elapsed_time).unwrap_or_default());
+        }
     }
 
     pub fn reflect_x(&mut self) {
-        self.speed *= Vec2::new(-1f32, 1f32);
+        self.speed *= Vector::new(-self.material.restitution, 1.0);
     }
 
     pub fn reflect_y(&mut self) {
-        self.speed *= Vec2::new(1f32, -1f32);
+        self.speed *= Vector::new(1.0, -self.material.restitution);
     }
 
     pub fn collide(&mut self, other: &mut Self) {
@@ -78,8 +155,10 @@ impl Circle {
         let m2 = other.mass();
         let v1_len = v1.length();
         let v2_len = v2.length();
-        // From energy conservation law:
-        let dv1_len = (2f32 * (v1_len * cos_a1 + v2_len * cos_a2) * m2 / (m1 + m2)).abs();
+        let restitution = self.material.combined_restitution(other.material);
+        // From energy conservation law (restitution of 1):
+        let dv1_len =
+            ((1.0 + restitution) * (v1_len * cos_a1 + v2_len * cos_a2) * m2 / (m1 + m2)).abs();
         let dv2_len = dv1_len * m1 / m2;
         let dv1 = to_self.normalize() * dv1_len;
         let dv2 = to_other.normalize() * dv2_len;
@@ -87,9 +166,9 @@ impl Circle {
         other.speed += dv2;
     }
 
-    fn v_dv_cos(v: Vec2, dv: Vec2) -> f32 {
-        if v == Vec2::new(0f32, 0f32) || dv == Vec2::new(0f32, 0f32) {
-            return 0f32;
+    fn v_dv_cos(v: Vector, dv: Vector) -> Scalar {
+        if v == Vector::new(0.0, 0.0) || dv == Vector::new(0.0, 0.0) {
+            return 0.0;
         }
         v.dot(dv) * v.length_reciprocal() * dv.length_reciprocal()
     }
@@ -98,34 +177,34 @@ impl Circle {
 #[cfg(test)]
 mod tests {
     use super::Circle;
+    use crate::circles_app::scalar::{Scalar, Vector};
     use assert_approx_eq;
-    use glam::Vec2;
 
     fn intersect_circles() -> Vec<Circle> {
         vec![
-            Circle::new((0f32, 0f32).into(), 2f32, (0f32, 0f32).into()),
-            Circle::new((3f32, 4f32).into(), 4f32, (0f32, 0f32).into()),
+            Circle::new((0.0, 0.0).into(), 2.0, (0.0, 0.0).into()),
+            Circle::new((3.0, 4.0).into(), 4.0, (0.0, 0.0).into()),
         ]
     }
 
     fn no_intersect_circles() -> Vec<Circle> {
         vec![
-            Circle::new((0f32, 0f32).into(), 2f32, (0f32, 0f32).into()),
-            Circle::new((3f32, 4f32).into(), 2.9f32, (0f32, 0f32).into()),
+            Circle::new((0.0, 0.0).into(), 2.0, (0.0, 0.0).into()),
+            Circle::new((3.0, 4.0).into(), 2.9, (0.0, 0.0).into()),
         ]
     }
 
     fn line_collided_circles() -> (Circle, Circle) {
-        let a = Circle::new(Vec2::zero(), 2f32, (10f32, 0f32).into());
-        let b = Circle::new((4f32, 0f32).into(), 2f32, (-10f32, 0f32).into());
+        let a = Circle::new(Vector::zero(), 2.0, (10.0, 0.0).into());
+        let b = Circle::new((4.0, 0.0).into(), 2.0, (-10.0, 0.0).into());
         (a, b)
     }
 
     fn angle_collided_circles() -> (Circle, Circle) {
-        let r = 1f32;
-        let a = Circle::new(Vec2::zero(), r, (10f32, 0f32).into());
-        let b_center = r * 2f32.sqrt();
-        let b = Circle::new((b_center, b_center).into(), r, (-10f32, 0f32).into());
+        let r = 1.0;
+        let a = Circle::new(Vector::zero(), r, (10.0, 0.0).into());
+        let b_center = r * (2.0 as Scalar).sqrt();
+        let b = Circle::new((b_center, b_center).into(), r, (-10.0, 0.0).into());
         (a, b)
     }
 
@@ -144,13 +223,13 @@ mod tests {
         a.collide(&mut b);
         assert_approx_eq::assert_approx_eq!(
             a.speed(),
-            (-10f32, 0f32).into(),
-            (std::f32::EPSILON, std::f32::EPSILON).into()
+            (-10.0, 0.0).into(),
+            (Scalar::EPSILON, Scalar::EPSILON).into()
         );
         assert_approx_eq::assert_approx_eq!(
             b.speed(),
-            (10f32, 0f32).into(),
-            (std::f32::EPSILON, std::f32::EPSILON).into()
+            (10.0, 0.0).into(),
+            (Scalar::EPSILON, Scalar::EPSILON).into()
         );
     }
 
@@ -158,15 +237,7 @@ mod tests {
     fn angle_collide() {
         let (mut a, mut b) = angle_collided_circles();
         a.collide(&mut b);
-        assert_approx_eq::assert_approx_eq!(
-            a.speed(),
-            (0f32, -10f32).into(),
-            (1e-5f32, 1e-5f32).into()
-        );
-        assert_approx_eq::assert_approx_eq!(
-            b.speed(),
-            (0f32, 10f32).into(),
-            (1e-5f32, 1e-5f32).into()
-        );
+        assert_approx_eq::assert_approx_eq!(a.speed(), (0.0, -10.0).into(), (1e-5, 1e-5).into());
+        assert_approx_eq::assert_approx_eq!(b.speed(), (0.0, 10.0).into(), (1e-5, 1e-5).into());
     }
 }
@@ -0,0 +1,39 @@
+use crate::circles_app::circle::Circle;
+use crate::circles_app::compound::Compound;
+use crate::circles_app::emitter::Emitter;
+use crate::circles_app::growth::Growth;
+use crate::circles_app::handle::{
+    CircleHandle, CompoundHandle, EmitterHandle, ObstacleHandle, SensorHandle, SinkHandle,
+};
+use crate::circles_app::obstacle::Obstacle;
+use crate::circles_app::scalar::{Scalar, Vector};
+use crate::circles_app::sensor::Sensor;
+use crate::circles_app::simulation::SimulationState;
+use crate::circles_app::sink::Sink;
+use crate::circles_app::thermostat::Thermostat;
+use serde::{Deserialize, Serialize};
+
+/// Change of simulation made from outside, between steps. Commands are what replays record.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Command {
+    AddCircle(Circle),
+    RemoveCircle(CircleHandle),
+    SetRadius(CircleHandle, Scalar),
+    SetGrowth(CircleHandle, Option<Growth>),
+    AddSensor(Sensor),
+    RemoveSensor(SensorHandle),
+    AddEmitter(Emitter),
+    RemoveEmitter(EmitterHandle),
+    AddSink(Sink),
+    RemoveSink(SinkHandle),
+    SetMaxPopulation(Option<usize>),
+    SetGravity(Vector),
+    SetThermostat(Option<Thermostat>),
+    SetSeed(u64),
+    /// Loading a scene or a snapshot.
+    ReplaceState(Box<SimulationState>),
+    AddCompound(Compound),
+    RemoveCompound(CompoundHandle),
+    AddObstacle(Obstacle),
+    RemoveObstacle(ObstacleHandle),
+}
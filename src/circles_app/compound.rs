@@ -0,0 +1,358 @@
+use crate::circles_app::circle::Circle;
+use crate::circles_app::obstacle::Obstacle;
+use crate::circles_app::scalar::consts::PI;
+use crate::circles_app::scalar::{seconds, Scalar, Vector};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Rigid body made of circles welded together: dumbbells, peanuts, gears.
+/// Members take part in narrow phase as ordinary circles, responses move the whole body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Compound {
+    /// Member centers are relative to the center of mass, at zero angle.
+    members: Vec<Circle>,
+    center: Vector,
+    angle: Scalar,
+    speed: Vector,
+    angular_speed: Scalar,
+    mass: Scalar,
+    inertia: Scalar,
+}
+
+impl Compound {
+    /// Welds circles at their current positions. The body starts at rest, speeds of circles
+    /// are ignored. Overlapping members count their shared area twice.
+    pub fn new(circles: Vec<Circle>) -> Self {
+        assert!(!circles.is_empty(), "Compound needs at least one circle");
+        let mass: Scalar = circles.iter().map(Circle::mass).sum();
+        let center = circles
+            .iter()
+            .fold(Vector::zero(), |sum, c| sum + c.center() * c.mass())
+            / mass;
+        let inertia = circles
+            .iter()
+            .map(|c| c.mass() * (0.5 * c.radius().powi(2) + (c.center() - center).length_squared()))
+            .sum();
+        let members = circles
+            .into_iter()
+            .map(|mut c| {
+                c.set_center(c.center() - center);
+                c.set_speed(Vector::zero());
+                c
+            })
+            .collect();
+        Self {
+            members,
+            center,
+            angle: 0.0,
+            speed: Vector::zero(),
+            angular_speed: 0.0,
+            mass,
+            inertia,
+        }
+    }
+
+    /// Two balls of `radius` with centers `length` apart along x, joined by a bar of circles
+    /// half as thick.
+    pub fn dumbbell(center: Vector, radius: Scalar, length: Scalar) -> Self {
+        let half = 0.5 * length;
+        let bar_radius = 0.5 * radius;
+        let mut circles = vec![
+            Circle::new(center - Vector::new(half, 0.0), radius, Vector::zero()),
+            Circle::new(center + Vector::new(half, 0.0), radius, Vector::zero()),
+        ];
+        let mut x = bar_radius - half + radius;
+        while x < half - radius {
+            circles.push(Circle::new(
+                center + Vector::new(x, 0.0),
+                bar_radius,
+                Vector::zero(),
+            ));
+            x += 2.0 * bar_radius;
+        }
+        Self::new(circles)
+    }
+
+    /// Hub of `radius` with `teeth` circles of `tooth_radius` centered on its rim.
+    pub fn gear(center: Vector, radius: Scalar, teeth: usize, tooth_radius: Scalar) -> Self {
+        let mut circles = vec![Circle::new(center, radius, Vector::zero())];
+        for tooth in 0..teeth {
+            let angle = 2.0 * PI * tooth as Scalar / teeth as Scalar;
+            let offset = rotate(Vector::new(radius, 0.0), angle);
+            circles.push(Circle::new(center + offset, tooth_radius, Vector::zero()));
+        }
+        Self::new(circles)
+    }
+
+    pub fn mass(&self) -> Scalar {
+        self.mass
+    }
+
+    /// Moment of inertia about the center of mass.
+    pub fn inertia(&self) -> Scalar {
+        self.inertia
+    }
+
+    /// Center of mass.
+    pub fn center(&self) -> Vector {
+        self.center
+    }
+
+    pub fn angle(&self) -> Scalar {
+        self.angle
+    }
+
+    pub fn speed(&self) -> Vector {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: Vector) {
+        self.speed = speed;
+    }
+
+    /// Radians per second, positive turns the x axis towards the y axis.
+    pub fn angular_speed(&self) -> Scalar {
+        self.angular_speed
+    }
+
+    pub fn set_angular_speed(&mut self, angular_speed: Scalar) {
+        self.angular_speed = angular_speed;
+    }
+
+    pub fn kinetic_energy(&self) -> Scalar {
+        0.5 * self.mass * self.speed.length_squared()
+            + 0.5 * self.inertia * self.angular_speed.powi(2)
+    }
+
+    pub fn momentum(&self) -> Vector {
+        self.speed * self.mass
+    }
+
+    /// Relative to `origin`.
+    pub fn angular_momentum(&self, origin: Vector) -> Scalar {
+        self.mass * cross(self.center - origin, self.speed) + self.inertia * self.angular_speed
+    }
+
+    /// Member placed in the field, moving with the speed of the body at its center.
+    pub fn member(&self, index: usize) -> Circle {
+        let mut circle = self.members[index];
+        let center = self.center + rotate(circle.center(), self.angle);
+        circle.set_center(center);
+        circle.set_speed(self.speed_at(center));
+        circle
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = Circle> + '_ {
+        (0..self.members.len()).map(move |index| self.member(index))
+    }
+
+    /// Speed of the body point currently at `point`.
+    pub fn speed_at(&self, point: Vector) -> Vector {
+        let r = point - self.center;
+        self.speed + Vector::new(-r.y(), r.x()) * self.angular_speed
+    }
+
+    pub fn apply_impulse(&mut self, point: Vector, impulse: Vector) {
+        self.speed += impulse / self.mass;
+        self.angular_speed += cross(point - self.center, impulse) / self.inertia;
+    }
+
+    /// Inverse of the mass resisting an impulse along `normal` applied at `point`.
+    pub fn inverse_mass_at(&self, point: Vector, normal: Vector) -> Scalar {
+        1.0 / self.mass + cross(point - self.center, normal).powi(2) / self.inertia
+    }
+
+    pub fn update(&mut self, elapsed_time: Duration) {
+        let dt = seconds(elapsed_time);
+        self.center += self.speed * dt;
+        self.angle += self.angular_speed * dt;
+    }
+
+    /// Bounces members approaching walls of the field spanning from the origin to `field_size`.
+    /// Returns the momentum given to the walls.
+    pub fn reflect_from_walls(&mut self, field_size: Vector) -> Scalar {
+        let mut total = 0.0;
+        for index in 0..self.members.len() {
+            let member = self.member(index);
+            let walls = [
+                (member.left() < 0.0, Vector::new(1.0, 0.0)),
+                (member.right() > field_size.x(), Vector::new(-1.0, 0.0)),
+                (member.top() < 0.0, Vector::new(0.0, 1.0)),
+                (member.bot() > field_size.y(), Vector::new(0.0, -1.0)),
+            ];
+            for &(touches, normal) in &walls {
+                if !touches {
+                    continue;
+                }
+                let point = member.center() - normal * member.radius();
+                let approach = -self.speed_at(point).dot(normal);
+                if approach > 0.0 {
+                    let restitution = member.material().restitution;
+                    let impulse =
+                        (1.0 + restitution) * approach / self.inverse_mass_at(point, normal);
+                    self.apply_impulse(point, normal * impulse);
+                    total += impulse;
+                }
+            }
+        }
+        total
+    }
+
+    /// Bounces members approaching `obstacle`. Returns the normal impulse.
+    pub fn collide_obstacle(&mut self, obstacle: &Obstacle) -> Scalar {
+        let mut total = 0.0;
+        for index in 0..self.members.len() {
+            let member = self.member(index);
+            let (_, normal) = match obstacle.contact(&member) {
+                Some(contact) => contact,
+                None => continue,
+            };
+            let point = member.center() - normal * member.radius();
+            let approach = -self.speed_at(point).dot(normal);
+            if approach > 0.0 {
+                let restitution = member.material().restitution;
+                let impulse = (1.0 + restitution) * approach / self.inverse_mass_at(point, normal);
+                self.apply_impulse(point, normal * impulse);
+                total += impulse;
+            }
+        }
+        total
+    }
+
+    /// Collides own `member` with a plain circle touching it. Returns the normal impulse.
+    pub fn collide_circle(&mut self, member: &Circle, circle: &mut Circle) -> Scalar {
+        let to_circle = circle.center() - member.center();
+        if to_circle == Vector::zero() {
+            return 0.0;
+        }
+        let normal = to_circle.normalize();
+        let point = member.center() + normal * member.radius();
+        let approach = (self.speed_at(point) - circle.speed()).dot(normal);
+        if approach <= 0.0 {
+            return 0.0;
+        }
+        let restitution = member.material().combined_restitution(circle.material());
+        let impulse = (1.0 + restitution) * approach
+            / (self.inverse_mass_at(point, normal) + 1.0 / circle.mass());
+        self.apply_impulse(point, -normal * impulse);
+        circle.set_speed(circle.speed() + normal * (impulse / circle.mass()));
+        impulse
+    }
+
+    /// Collides own `member` with `other_member` of another compound. Returns the normal impulse.
+    pub fn collide_compound(
+        &mut self,
+        member: &Circle,
+        other: &mut Compound,
+        other_member: &Circle,
+    ) -> Scalar {
+        let to_other = other_member.center() - member.center();
+        if to_other == Vector::zero() {
+            return 0.0;
+        }
+        let normal = to_other.normalize();
+        let point = member.center() + normal * member.radius();
+        let approach = (self.speed_at(point) - other.speed_at(point)).dot(normal);
+        if approach <= 0.0 {
+            return 0.0;
+        }
+        let restitution = member
+            .material()
+            .combined_restitution(other_member.material());
+        let impulse = (1.0 + restitution) * approach
+            / (self.inverse_mass_at(point, normal) + other.inverse_mass_at(point, normal));
+        self.apply_impulse(point, -normal * impulse);
+        other.apply_impulse(point, normal * impulse);
+        impulse
+    }
+}
+
+fn rotate(v: Vector, angle: Scalar) -> Vector {
+    let (sin, cos) = angle.sin_cos();
+    Vector::new(v.x() * cos - v.y() * sin, v.x() * sin + v.y() * cos)
+}
+
+fn cross(a: Vector, b: Vector) -> Scalar {
+    a.x() * b.y() - a.y() * b.x()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compound;
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::scalar::consts::PI;
+    use crate::circles_app::scalar::{Scalar, Vector};
+    use assert_approx_eq::assert_approx_eq;
+    use std::time::Duration;
+
+    fn dumbbell() -> Compound {
+        Compound::new(vec![
+            Circle::new((0.0, 0.0).into(), 1.0, Vector::zero()),
+            Circle::new((6.0, 0.0).into(), 2.0, Vector::zero()),
+        ])
+    }
+
+    #[test]
+    fn mass_center_and_inertia() {
+        let compound = dumbbell();
+        assert_approx_eq!(compound.mass(), 5.0 * PI);
+        assert_approx_eq!(compound.center().x(), 4.8);
+        assert_approx_eq!(compound.center().y(), 0.0);
+        assert_approx_eq!(compound.inertia(), 37.3 * PI, 1e-3);
+        assert_approx_eq!(compound.member(0).center().x(), 0.0);
+        assert_approx_eq!(compound.member(1).center().x(), 6.0);
+    }
+
+    #[test]
+    fn members_turn_with_body() {
+        let mut compound = dumbbell();
+        compound.set_angular_speed(PI / 2.0);
+        compound.update(Duration::from_secs(1));
+        let small = compound.member(0);
+        assert_approx_eq!(small.center().x(), 4.8, 1e-4);
+        assert_approx_eq!(small.center().y(), -4.8, 1e-4);
+        assert_approx_eq!(small.speed().x(), 4.8 * PI / 2.0, 1e-4);
+    }
+
+    #[test]
+    fn off_center_hit_spins_body_and_conserves_momentum() {
+        let mut compound = dumbbell();
+        let mut circle = Circle::new((0.0, 1.9).into(), 1.0, (0.0, -3.0).into());
+        let member = compound.member(0);
+        assert!(member.is_intersect(&circle));
+
+        let origin = Vector::zero();
+        let momentum = |c: &Compound, b: &Circle| c.momentum() + b.speed() * b.mass();
+        let angular = |c: &Compound, b: &Circle| {
+            c.angular_momentum(origin) + b.mass() * super::cross(b.center() - origin, b.speed())
+        };
+        let energy = |c: &Compound, b: &Circle| c.kinetic_energy() + b.kinetic_energy();
+        let before = (
+            momentum(&compound, &circle),
+            angular(&compound, &circle),
+            energy(&compound, &circle),
+        );
+        assert!(compound.collide_circle(&member, &mut circle) > 0.0);
+
+        let tolerance: Scalar = 1e-3;
+        assert!((momentum(&compound, &circle) - before.0).length() < tolerance);
+        assert_approx_eq!(angular(&compound, &circle), before.1, tolerance);
+        assert_approx_eq!(energy(&compound, &circle), before.2, tolerance);
+        assert!(compound.angular_speed() > 0.0);
+        assert!(circle.speed().y() > 0.0);
+        assert_eq!(compound.collide_circle(&member, &mut circle), 0.0);
+    }
+
+    #[test]
+    fn wall_hit_turns_body_back() {
+        let mut compound = Compound::dumbbell((3.0, 5.0).into(), 1.0, 4.0);
+        compound.set_speed((0.0, 2.0).into());
+        compound.set_angular_speed(0.5);
+        let energy = compound.kinetic_energy();
+        let field_size = (20.0, 5.5).into();
+        let impulse = compound.reflect_from_walls(field_size);
+        assert!(impulse > 0.0);
+        assert_approx_eq!(compound.kinetic_energy(), energy, 1e-4);
+        assert!(compound.speed().y() < 0.0);
+    }
+}
@@ -0,0 +1,288 @@
+use crate::circles_app::scalar::{widen, Vector};
+use crate::circles_app::simulation::Simulation;
+use slog::Logger;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Drifts kept by `ConservationMonitor`, older ones are dropped.
+pub const HISTORY_LENGTH: usize = 10_000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Conserved {
+    pub kinetic_energy: f64,
+    pub momentum: Vector,
+    /// Relative to the field center.
+    pub angular_momentum: f64,
+    momentum_scale: f64,
+    angular_momentum_scale: f64,
+}
+
+impl Conserved {
+    pub fn measure(simulation: &Simulation) -> Self {
+        let origin = simulation.field_size() * 0.5;
+        let mut conserved = Self {
+            kinetic_energy: 0f64,
+            momentum: Vector::zero(),
+            angular_momentum: 0f64,
+            momentum_scale: 0f64,
+            angular_momentum_scale: 0f64,
+        };
+        for (_, circle) in simulation.circles() {
+            let mass = widen(circle.mass());
+            let r = circle.center() - origin;
+            let v = circle.speed();
+            conserved.kinetic_energy += widen(circle.kinetic_energy());
+            conserved.momentum += v * circle.mass();
+            conserved.angular_momentum += mass * widen(r.x() * v.y() - r.y() * v.x());
+            conserved.momentum_scale += mass * widen(v.length());
+            conserved.angular_momentum_scale += mass * widen(r.length() * v.length());
+        }
+        for (_, compound) in simulation.compounds() {
+            let mass = widen(compound.mass());
+            let r = compound.center() - origin;
+            let v = compound.speed();
+            let spin = widen(compound.inertia() * compound.angular_speed());
+            conserved.kinetic_energy += widen(compound.kinetic_energy());
+            conserved.momentum += compound.momentum();
+            conserved.angular_momentum += widen(compound.angular_momentum(origin));
+            conserved.momentum_scale += mass * widen(v.length());
+            conserved.angular_momentum_scale += mass * widen(r.length() * v.length()) + spin.abs();
+        }
+        conserved
+    }
+}
+
+/// Drifts relative to initial values. Momentum drifts are normalized by sums of
+/// per-circle magnitudes, because total momentum is often close to zero.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Drift {
+    pub kinetic_energy: f64,
+    pub momentum: f64,
+    pub angular_momentum: f64,
+}
+
+/// `None` disables the alarm for a quantity. Walls exchange momentum with circles,
+/// so momentum alarms make sense for wall-free setups only.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tolerance {
+    pub kinetic_energy: Option<f64>,
+    pub momentum: Option<f64>,
+    pub angular_momentum: Option<f64>,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            kinetic_energy: Some(1e-3),
+            momentum: None,
+            angular_momentum: None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Quantity {
+    KineticEnergy,
+    Momentum,
+    AngularMomentum,
+}
+
+pub struct ConservationMonitor {
+    initial: Conserved,
+    tolerance: Tolerance,
+    history: VecDeque<(Duration, Drift)>,
+    max_drift: Drift,
+    alarmed: Vec<Quantity>,
+    logger: Logger,
+}
+
+impl ConservationMonitor {
+    pub fn new(simulation: &Simulation, tolerance: Tolerance, logger: Logger) -> Self {
+        Self {
+            initial: Conserved::measure(simulation),
+            tolerance,
+            history: VecDeque::new(),
+            max_drift: Drift::default(),
+            alarmed: Vec::new(),
+            logger,
+        }
+    }
+
+    pub fn tolerance(&self) -> Tolerance {
+        self.tolerance
+    }
+
+    pub fn initial(&self) -> Conserved {
+        self.initial
+    }
+
+    pub fn drift(&self, simulation: &Simulation) -> Drift {
+        let current = Conserved::measure(simulation);
+        let initial = self.initial;
+        Drift {
+            kinetic_energy: relative(
+                current.kinetic_energy - initial.kinetic_energy,
+                initial.kinetic_energy.abs(),
+            ),
+            momentum: relative(
+                widen((current.momentum - initial.momentum).length()),
+                initial.momentum_scale,
+            ),
+            angular_momentum: relative(
+                current.angular_momentum - initial.angular_momentum,
+                initial.angular_momentum_scale,
+            ),
+        }
+    }
+
+    /// Records drift and logs a warning for each quantity that newly exceeds its tolerance.
+    /// Returns quantities currently out of tolerance.
+    pub fn check(&mut self, simulation: &Simulation) -> Vec<Quantity> {
+        let drift = self.drift(simulation);
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back((simulation.time(), drift));
+        self.max_drift = Drift {
+            kinetic_energy: self
+                .max_drift
+                .kinetic_energy
+                .max(drift.kinetic_energy.abs()),
+            momentum: self.max_drift.momentum.max(drift.momentum.abs()),
+            angular_momentum: self
+                .max_drift
+                .angular_momentum
+                .max(drift.angular_momentum.abs()),
+        };
+
+        let checks = [
+            (
+                Quantity::KineticEnergy,
+                drift.kinetic_energy,
+                self.tolerance.kinetic_energy,
+            ),
+            (Quantity::Momentum, drift.momentum, self.tolerance.momentum),
+            (
+                Quantity::AngularMomentum,
+                drift.angular_momentum,
+                self.tolerance.angular_momentum,
+            ),
+        ];
+        let mut exceeded = Vec::new();
+        for &(quantity, value, tolerance) in &checks {
+            let tolerance = match tolerance {
+                Some(tolerance) => tolerance,
+                None => continue,
+            };
+            if value.abs() > tolerance {
+                if !self.alarmed.contains(&quantity) {
+                    warn!(
+                        self.logger,
+                        "{:?} drift {:e} exceeds tolerance {:e} at {:?}",
+                        quantity,
+                        value,
+                        tolerance,
+                        simulation.time()
+                    );
+                    self.alarmed.push(quantity);
+                }
+                exceeded.push(quantity);
+            } else {
+                self.alarmed.retain(|q| *q != quantity);
+            }
+        }
+        exceeded
+    }
+
+    /// Last `HISTORY_LENGTH` drifts, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &(Duration, Drift)> {
+        self.history.iter()
+    }
+
+    /// Largest absolute drifts seen by `check`, including those dropped from history.
+    pub fn max_drift(&self) -> Drift {
+        self.max_drift
+    }
+}
+
+fn relative(difference: f64, scale: f64) -> f64 {
+    if scale > 0f64 {
+        difference / scale
+    } else {
+        difference
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConservationMonitor, Quantity, Tolerance, HISTORY_LENGTH};
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::scalar::Scalar;
+    use crate::circles_app::simulation::Simulation;
+    use crate::circles_app::thermostat::Thermostat;
+    use slog::{Discard, Logger};
+    use std::time::Duration;
+
+    fn logger() -> Logger {
+        Logger::root(Discard, o!())
+    }
+
+    fn gas() -> Simulation {
+        let mut sim = Simulation::new((100.0, 100.0).into(), logger());
+        for i in 0..10 {
+            for j in 0..10 {
+                let center = (5.0 + 10.0 * i as Scalar, 5.0 + 10.0 * j as Scalar).into();
+                let speed = ((i * 7 % 5) as Scalar - 2.0, (j * 3 % 5) as Scalar - 2.0).into();
+                sim.add_circle(Circle::new(center, 2.0 + (i % 3) as Scalar, speed));
+            }
+        }
+        sim
+    }
+
+    #[test]
+    fn elastic_gas_conserves_energy() {
+        let mut sim = gas();
+        let mut monitor = ConservationMonitor::new(&sim, Tolerance::default(), logger());
+        for _ in 0..500 {
+            sim.step(Duration::from_millis(20));
+            assert!(monitor.check(&sim).is_empty());
+        }
+        assert!(monitor.max_drift().kinetic_energy < 1e-3);
+    }
+
+    #[test]
+    fn thermostat_raises_alarm() {
+        let mut sim = gas();
+        let mut monitor = ConservationMonitor::new(&sim, Tolerance::default(), logger());
+        sim.set_thermostat(Some(Thermostat::Berendsen {
+            target: 100.0 * sim.temperature(),
+            tau: Duration::from_millis(100),
+        }));
+        sim.step(Duration::from_millis(20));
+        assert_eq!(monitor.check(&sim), vec![Quantity::KineticEnergy]);
+    }
+
+    #[test]
+    fn history_is_bounded_and_keeps_max_drift() {
+        let sim = gas();
+        let mut monitor = ConservationMonitor::new(&sim, Tolerance::default(), logger());
+        let mut heated = gas();
+        heated.set_thermostat(Some(Thermostat::Berendsen {
+            target: 2.0 * sim.temperature(),
+            tau: Duration::from_millis(100),
+        }));
+        heated.step(Duration::from_millis(20));
+        monitor.check(&heated);
+        let heating = monitor.max_drift().kinetic_energy;
+        assert!(heating > 0.0);
+
+        for _ in 0..HISTORY_LENGTH {
+            monitor.check(&sim);
+        }
+        assert_eq!(monitor.history().count(), HISTORY_LENGTH);
+        assert!(monitor
+            .history()
+            .all(|(_, drift)| drift.kinetic_energy == 0.0));
+        assert_eq!(monitor.max_drift().kinetic_energy, heating);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::circles_app::circle::Circle;
+use crate::circles_app::filter::CollisionFilter;
+use crate::circles_app::material::Material;
+use crate::circles_app::scalar::{seconds, Scalar, Vector};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Spawns circles at `position` with `rate` circles per second.
+/// Radius and speed are uniform in their ranges, direction is uniform in the cone
+/// `direction ± spread` (radians), material is picked by weight.
+/// The simulation holds a spawn back while it would intersect a circle at `position`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Emitter {
+    position: Vector,
+    rate: Scalar,
+    radius: (Scalar, Scalar),
+    speed: (Scalar, Scalar),
+    direction: Scalar,
+    spread: Scalar,
+    materials: Vec<(Material, Scalar)>,
+    filter: CollisionFilter,
+    species: u32,
+    lifetime: Option<Duration>,
+    accumulated: Scalar,
+}
+
+impl Emitter {
+    pub fn new(position: Vector, rate: Scalar) -> Self {
+        Self {
+            position,
+            rate,
+            radius: (1.0, 1.0),
+            speed: (0.0, 0.0),
+            direction: 0.0,
+            spread: 0.0,
+            materials: vec![(Material::default(), 1.0)],
+            filter: CollisionFilter::default(),
+            species: 0,
+            lifetime: None,
+            accumulated: 0.0,
+        }
+    }
+
+    pub fn radius(mut self, min: Scalar, max: Scalar) -> Self {
+        self.radius = (min, max);
+        self
+    }
+
+    pub fn speed(mut self, min: Scalar, max: Scalar) -> Self {
+        self.speed = (min, max);
+        self
+    }
+
+    pub fn cone(mut self, direction: Scalar, spread: Scalar) -> Self {
+        self.direction = direction;
+        self.spread = spread;
+        self
+    }
+
+    pub fn materials(mut self, materials: Vec<(Material, Scalar)>) -> Self {
+        self.materials = materials;
+        self
+    }
+
+    pub fn filter(mut self, filter: CollisionFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn species(mut self, species: u32) -> Self {
+        self.species = species;
+        self
+    }
+
+    pub fn lifetime(mut self, lifetime: Option<Duration>) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    pub fn position(&self) -> Vector {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vector) {
+        self.position = position;
+    }
+
+    pub fn rate(&self) -> Scalar {
+        self.rate
+    }
+
+    pub fn set_rate(&mut self, rate: Scalar) {
+        self.rate = rate;
+    }
+
+    /// Number of circles due after `elapsed_time` has passed.
+    pub fn advance(&mut self, elapsed_time: Duration) -> usize {
+        self.accumulated += self.rate * seconds(elapsed_time);
+        let due = self.accumulated.floor();
+        self.accumulated -= due;
+        due as usize
+    }
+
+    /// Makes one circle due again on the next `advance`, after a spawn was held back.
+    pub fn postpone(&mut self) {
+        self.accumulated += 1.0;
+    }
+
+    pub fn spawn<R: Rng>(&self, rng: &mut R) -> Circle {
+        let radius = sample(rng, self.radius);
+        let speed = sample(rng, self.speed);
+        let angle = sample(
+            rng,
+            (self.direction - self.spread, self.direction + self.spread),
+        );
+        let velocity = Vector::new(angle.cos(), angle.sin()) * speed;
+
+        let mut circle = Circle::new(self.position, radius, velocity);
+        if let Ok((material, _)) = self.materials.choose_weighted(rng, |(_, weight)| *weight) {
+            circle.set_material(*material);
+        }
+        circle.set_filter(self.filter);
+        circle.set_species(self.species);
+        circle.set_lifetime(self.lifetime);
+        circle
+    }
+}
+
+fn sample<R: Rng>(rng: &mut R, (min, max): (Scalar, Scalar)) -> Scalar {
+    if min < max {
+        rng.gen_range(min, max)
+    } else {
+        min
+    }
+}
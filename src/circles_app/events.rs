@@ -0,0 +1,132 @@
+use crate::circles_app::handle::{
+    CircleHandle, EmitterHandle, ObstacleHandle, SensorHandle, SinkHandle,
+};
+use crate::circles_app::scalar::{Scalar, Vector};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WallSide {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl WallSide {
+    /// Normal directed from wall into the field.
+    pub fn normal(self) -> Vector {
+        match self {
+            WallSide::Left => Vector::new(1.0, 0.0),
+            WallSide::Right => Vector::new(-1.0, 0.0),
+            WallSide::Top => Vector::new(0.0, 1.0),
+            WallSide::Bottom => Vector::new(0.0, -1.0),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Contact {
+    /// Circle with the lesser handle.
+    pub a: CircleHandle,
+    pub b: CircleHandle,
+    pub point: Vector,
+    /// Unit vector from `a` to `b`.
+    pub normal: Vector,
+    /// Momentum transferred from one circle to another. Zero for persisting contacts.
+    pub impulse: Scalar,
+    /// Approach speed along the normal, measured before response.
+    pub relative_speed: Scalar,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WallHit {
+    pub circle: CircleHandle,
+    pub side: WallSide,
+    pub point: Vector,
+    pub normal: Vector,
+    pub impulse: Scalar,
+    pub relative_speed: Scalar,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ObstacleHit {
+    pub circle: CircleHandle,
+    pub obstacle: ObstacleHandle,
+    pub point: Vector,
+    /// Directed out of the obstacle.
+    pub normal: Vector,
+    pub impulse: Scalar,
+    pub relative_speed: Scalar,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RemoveCause {
+    Sink(SinkHandle),
+    Expired,
+    /// `Simulation::remove_circle` or `Command::RemoveCircle`.
+    Manual,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CollisionEvent {
+    ContactBegin(Contact),
+    ContactPersist(Contact),
+    ContactEnd {
+        a: CircleHandle,
+        b: CircleHandle,
+    },
+    WallHit(WallHit),
+    ObstacleHit(ObstacleHit),
+    SensorEnter {
+        sensor: SensorHandle,
+        circle: CircleHandle,
+    },
+    SensorExit {
+        sensor: SensorHandle,
+        circle: CircleHandle,
+    },
+    Spawned {
+        emitter: EmitterHandle,
+        circle: CircleHandle,
+    },
+    Removed {
+        circle: CircleHandle,
+        cause: RemoveCause,
+    },
+}
+
+pub type EventCallback = Box<dyn FnMut(&CollisionEvent) + Send>;
+
+/// Events accumulate until drained. Subscribed callbacks are called immediately on emit.
+#[derive(Default)]
+pub struct CollisionEvents {
+    queue: Vec<CollisionEvent>,
+    callbacks: Vec<EventCallback>,
+}
+
+impl CollisionEvents {
+    pub fn subscribe<F>(&mut self, callback: F)
+    where
+        F: FnMut(&CollisionEvent) + Send + 'static,
+    {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    pub fn emit(&mut self, event: CollisionEvent) {
+        for callback in &mut self.callbacks {
+            callback(&event);
+        }
+        self.queue.push(event);
+    }
+
+    pub fn drain(&mut self) -> std::vec::Drain<'_, CollisionEvent> {
+        self.queue.drain(..)
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
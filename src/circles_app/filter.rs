@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CollisionFilter {
+    pub layer: u32,
+    pub mask: u32,
+}
+
+impl CollisionFilter {
+    pub const ALL: u32 = u32::MAX;
+
+    pub fn new(layer: u32, mask: u32) -> Self {
+        Self { layer, mask }
+    }
+
+    pub fn interacts(self, other: Self) -> bool {
+        self.layer & other.mask != 0 && other.layer & self.mask != 0
+    }
+}
+
+impl Default for CollisionFilter {
+    fn default() -> Self {
+        Self::new(1, Self::ALL)
+    }
+}
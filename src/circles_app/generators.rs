@@ -0,0 +1,369 @@
+use crate::circles_app::circle::Circle;
+use crate::circles_app::filter::CollisionFilter;
+use crate::circles_app::obstacle::Obstacle;
+use crate::circles_app::scalar::consts::PI;
+use crate::circles_app::scalar::{Scalar, Vector, Vector3};
+use crate::circles_app::sensor::SensorShape;
+use crate::circles_app::sphere::Sphere;
+use glam::Vec4;
+use rand::{Rng, SeedableRng};
+use rand_distr::UnitSphere;
+use rand_pcg::Pcg64Mcg;
+use std::collections::HashMap;
+
+const MAX_ATTEMPTS_PER_CIRCLE: usize = 100;
+/// Relative gap between touching lattice neighbours, so rounding never makes them intersect.
+const LATTICE_GAP: Scalar = 1e-4;
+/// Width of the `two_gases` partition, as part of the field width.
+const PARTITION_WIDTH: Scalar = 0.01;
+
+/// Dart throwing with rejection of overlapping candidates. Radii are uniform in `radius`.
+/// Returns fewer than `count` circles if the field is too crowded.
+pub fn poisson_disk(
+    field_size: Vector,
+    count: usize,
+    radius: (Scalar, Scalar),
+    speed: Scalar,
+    seed: u64,
+) -> Vec<Circle> {
+    let mut rng = Pcg64Mcg::seed_from_u64(seed);
+    let mut placer = Placer::new(radius.1);
+    let mut circles = Vec::with_capacity(count);
+    let mut attempts = 0;
+    while circles.len() < count && attempts < count * MAX_ATTEMPTS_PER_CIRCLE {
+        attempts += 1;
+        let r = if radius.0 < radius.1 {
+            rng.gen_range(radius.0, radius.1)
+        } else {
+            radius.0
+        };
+        if 2.0 * r >= field_size.min_element() {
+            continue;
+        }
+        let center = Vector::new(
+            rng.gen_range(r, field_size.x() - r),
+            rng.gen_range(r, field_size.y() - r),
+        );
+        let circle = Circle::new(center, r, random_velocity(&mut rng, speed));
+        if placer.try_place(&circle) {
+            circles.push(circle);
+        }
+    }
+    circles
+}
+
+/// Circles on a square lattice with `spacing` between neighbour centers.
+pub fn square_lattice(
+    field_size: Vector,
+    spacing: Scalar,
+    radius: Scalar,
+    speed: Scalar,
+    seed: u64,
+) -> Vec<Circle> {
+    let mut rng = Pcg64Mcg::seed_from_u64(seed);
+    let spacing = spacing.max(2.0 * radius * (1.0 + LATTICE_GAP));
+    let columns = ((field_size.x() - 2.0 * radius) / spacing).floor() as i32 + 1;
+    let rows = ((field_size.y() - 2.0 * radius) / spacing).floor() as i32 + 1;
+    let mut placer = Placer::new(radius);
+    let mut circles = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let center = Vector::new(
+                radius + column as Scalar * spacing,
+                radius + row as Scalar * spacing,
+            );
+            let circle = Circle::new(center, radius, random_velocity(&mut rng, speed));
+            if fits(&circle, field_size) && placer.try_place(&circle) {
+                circles.push(circle);
+            }
+        }
+    }
+    circles
+}
+
+/// Circles on a hexagonal (triangular) lattice with `spacing` between neighbour centers.
+pub fn hex_lattice(
+    field_size: Vector,
+    spacing: Scalar,
+    radius: Scalar,
+    speed: Scalar,
+    seed: u64,
+) -> Vec<Circle> {
+    let mut rng = Pcg64Mcg::seed_from_u64(seed);
+    let spacing = spacing.max(2.0 * radius * (1.0 + LATTICE_GAP));
+    let row_height = spacing * (3.0 as Scalar).sqrt() / 2.0;
+    let rows = ((field_size.y() - 2.0 * radius) / row_height).floor() as i32 + 1;
+    let mut placer = Placer::new(radius);
+    let mut circles = Vec::new();
+    for row in 0..rows {
+        let offset = if row % 2 == 1 { spacing / 2.0 } else { 0.0 };
+        let columns = ((field_size.x() - 2.0 * radius - offset) / spacing).floor() as i32 + 1;
+        for column in 0..columns {
+            let center = Vector::new(
+                radius + offset + column as Scalar * spacing,
+                radius + row as Scalar * row_height,
+            );
+            let circle = Circle::new(center, radius, random_velocity(&mut rng, speed));
+            if fits(&circle, field_size) && placer.try_place(&circle) {
+                circles.push(circle);
+            }
+        }
+    }
+    circles
+}
+
+/// Triangle of 15 balls with apex at `apex` pointing to the left, and a cue ball
+/// shot toward the apex with `cue_speed`. The seed slightly perturbs the shot direction.
+/// Balls that do not fit into the field are left out.
+pub fn billiards_rack(
+    field_size: Vector,
+    apex: Vector,
+    ball_radius: Scalar,
+    cue_speed: Scalar,
+    seed: u64,
+) -> Vec<Circle> {
+    let mut rng = Pcg64Mcg::seed_from_u64(seed);
+    let gap = ball_radius * 0.01;
+    let spacing = 2.0 * ball_radius + gap;
+    let row_step = spacing * (3.0 as Scalar).sqrt() / 2.0;
+
+    let mut placer = Placer::new(ball_radius);
+    let mut circles = Vec::new();
+    for row in 0..5 {
+        for i in 0..=row {
+            let x = apex.x() + row as Scalar * row_step;
+            let y = apex.y() + (i as Scalar - row as Scalar / 2.0) * spacing;
+            let mut ball = Circle::new(Vector::new(x, y), ball_radius, Vector::zero());
+            ball.set_color(rack_color(row * (row + 1) / 2 + i));
+            if fits(&ball, field_size) && placer.try_place(&ball) {
+                circles.push(ball);
+            }
+        }
+    }
+
+    let cue_center = apex - Vector::new(10.0 * ball_radius, 0.0);
+    let angle: Scalar = rng.gen_range(-0.01, 0.01);
+    let cue_velocity = Vector::new(angle.cos(), angle.sin()) * cue_speed;
+    let cue = Circle::new(cue_center, ball_radius, cue_velocity);
+    if fits(&cue, field_size) && placer.try_place(&cue) {
+        circles.push(cue);
+    }
+    circles
+}
+
+/// Two gases of different species on the left and the right of the `partition` of the field.
+/// Add the partition as an obstacle to keep them apart, and remove it to let them mix.
+pub fn two_gases(
+    field_size: Vector,
+    count_per_side: usize,
+    radius: (Scalar, Scalar),
+    speeds: (Scalar, Scalar),
+    seed: u64,
+) -> Vec<Circle> {
+    let width = field_size.x() * PARTITION_WIDTH;
+    let side = Vector::new((field_size.x() - width) / 2.0, field_size.y());
+    let mut left = poisson_disk(side, count_per_side, radius, speeds.0, seed);
+    for circle in &mut left {
+        circle.set_species(0);
+        circle.set_color(Vec4::new(1.0, 0.3, 0.3, 1.0));
+    }
+    let mut right = poisson_disk(side, count_per_side, radius, speeds.1, seed.wrapping_add(1));
+    for circle in &mut right {
+        circle.set_center(circle.center() + Vector::new(side.x() + width, 0.0));
+        circle.set_species(1);
+        circle.set_color(Vec4::new(0.3, 0.3, 1.0, 1.0));
+    }
+    left.extend(right);
+    left
+}
+
+/// Wall across the middle of the field between the two gases of `two_gases`.
+pub fn partition(field_size: Vector) -> Obstacle {
+    let width = field_size.x() * PARTITION_WIDTH;
+    let left = (field_size.x() - width) / 2.0;
+    Obstacle::new(
+        SensorShape::Rect {
+            min: Vector::new(left, 0.0),
+            max: Vector::new(left + width, field_size.y()),
+        },
+        CollisionFilter::default(),
+    )
+}
+
+/// `poisson_disk` of the 3D mode. Candidates are checked against every placed sphere, so it is
+/// meant for hundreds of spheres, not for huge fields.
+pub fn random_spheres(
+    field_size: Vector3,
+    count: usize,
+    radius: (Scalar, Scalar),
+    speed: Scalar,
+    seed: u64,
+) -> Vec<Sphere> {
+    let mut rng = Pcg64Mcg::seed_from_u64(seed);
+    let mut spheres: Vec<Sphere> = Vec::with_capacity(count);
+    let mut attempts = 0;
+    while spheres.len() < count && attempts < count * MAX_ATTEMPTS_PER_CIRCLE {
+        attempts += 1;
+        let r = if radius.0 < radius.1 {
+            rng.gen_range(radius.0, radius.1)
+        } else {
+            radius.0
+        };
+        if 2.0 * r >= field_size.min_element() {
+            continue;
+        }
+        let center = Vector3::new(
+            rng.gen_range(r, field_size.x() - r),
+            rng.gen_range(r, field_size.y() - r),
+            rng.gen_range(r, field_size.z() - r),
+        );
+        let [x, y, z]: [Scalar; 3] = rng.sample(UnitSphere);
+        let mut sphere = Sphere::new(center, r, Vector3::new(x, y, z) * speed);
+        if spheres.iter().all(|placed| !placed.is_intersect(&sphere)) {
+            sphere.set_color(rack_color(spheres.len()));
+            spheres.push(sphere);
+        }
+    }
+    spheres
+}
+
+fn fits(circle: &Circle, field_size: Vector) -> bool {
+    circle.left() >= 0.0
+        && circle.top() >= 0.0
+        && circle.right() <= field_size.x()
+        && circle.bot() <= field_size.y()
+}
+
+fn random_velocity<R: Rng>(rng: &mut R, speed: Scalar) -> Vector {
+    let angle = rng.gen_range(0.0, 2.0 * PI);
+    Vector::new(angle.cos(), angle.sin()) * speed
+}
+
+fn rack_color(index: usize) -> Vec4 {
+    use std::f32::consts::PI;
+    let hue = index as f32 / 15f32;
+    Vec4::new(
+        0.5f32 + 0.5f32 * (2f32 * PI * hue).cos(),
+        0.5f32 + 0.5f32 * (2f32 * PI * (hue + 1f32 / 3f32)).cos(),
+        0.5f32 + 0.5f32 * (2f32 * PI * (hue + 2f32 / 3f32)).cos(),
+        1f32,
+    )
+}
+
+/// Uniform grid of already placed circles with cell size of the largest diameter.
+struct Placer {
+    cell_size: Scalar,
+    cells: HashMap<(i32, i32), Vec<Circle>>,
+}
+
+impl Placer {
+    fn new(max_radius: Scalar) -> Self {
+        Self {
+            cell_size: 2.0 * max_radius.max(Scalar::EPSILON),
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, point: Vector) -> (i32, i32) {
+        (
+            (point.x() / self.cell_size).floor() as i32,
+            (point.y() / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn try_place(&mut self, circle: &Circle) -> bool {
+        let (cx, cy) = self.cell(circle.center());
+        for x in cx - 1..=cx + 1 {
+            for y in cy - 1..=cy + 1 {
+                if let Some(placed) = self.cells.get(&(x, y)) {
+                    if placed.iter().any(|other| other.is_intersect(circle)) {
+                        return false;
+                    }
+                }
+            }
+        }
+        self.cells.entry((cx, cy)).or_default().push(*circle);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        billiards_rack, hex_lattice, partition, poisson_disk, random_spheres, square_lattice,
+        two_gases,
+    };
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::scalar::{Vector, Vector3};
+
+    fn assert_valid(circles: &[Circle], field_size: Vector) {
+        for (i, a) in circles.iter().enumerate() {
+            assert!(a.left() >= 0.0 && a.top() >= 0.0, "{:?}", a);
+            assert!(
+                a.right() <= field_size.x() && a.bot() <= field_size.y(),
+                "{:?}",
+                a
+            );
+            for b in &circles[i + 1..] {
+                assert!(!a.is_intersect(b), "{:?} intersects {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn generators_produce_no_overlaps() {
+        let field_size = Vector::new(200.0, 100.0);
+
+        let gas = poisson_disk(field_size, 300, (1.0, 4.0), 10.0, 1);
+        assert_eq!(gas.len(), 300);
+        assert_valid(&gas, field_size);
+
+        let square = square_lattice(field_size, 10.0, 4.9, 1.0, 2);
+        assert_eq!(square.len(), 20 * 10);
+        assert_valid(&square, field_size);
+
+        let hex = hex_lattice(field_size, 10.0, 4.9, 1.0, 3);
+        assert_valid(&hex, field_size);
+        assert!(hex.len() > square.len());
+        assert!(hex_lattice((4.0, 4.0).into(), 1.0, 3.0, 1.0, 3).is_empty());
+
+        let rack = billiards_rack(field_size, (100.0, 50.0).into(), 3.0, 50.0, 4);
+        assert_eq!(rack.len(), 16);
+        assert_valid(&rack, field_size);
+        let clipped = billiards_rack(field_size, (180.0, 50.0).into(), 3.0, 50.0, 4);
+        assert!(clipped.len() < 16);
+        assert_valid(&clipped, field_size);
+
+        let gases = two_gases(field_size, 100, (1.0, 2.0), (5.0, 20.0), 5);
+        assert_eq!(gases.len(), 200);
+        assert_valid(&gases, field_size);
+        assert!(gases
+            .iter()
+            .all(|c| (c.species() == 0) == (c.center().x() < 100.0)));
+        let partition = partition(field_size);
+        assert!(gases.iter().all(|c| partition.contact(c).is_none()));
+    }
+
+    #[test]
+    fn spheres_fit_into_box() {
+        let field_size = Vector3::new(10.0, 8.0, 6.0);
+        let spheres = random_spheres(field_size, 100, (0.2, 0.5), 2.0, 6);
+        assert_eq!(spheres.len(), 100);
+        for (i, a) in spheres.iter().enumerate() {
+            assert!(a.lower().min_element() >= 0.0, "{:?}", a);
+            assert!((a.upper() - field_size).max_element() <= 0.0, "{:?}", a);
+            assert!((a.speed().length() - 2.0).abs() < 1e-4);
+            assert!(spheres[i + 1..].iter().all(|b| !a.is_intersect(b)));
+        }
+    }
+
+    #[test]
+    fn same_seed_same_scene() {
+        let field_size = Vector::new(100.0, 100.0);
+        let a = poisson_disk(field_size, 50, (1.0, 3.0), 5.0, 42);
+        let b = poisson_disk(field_size, 50, (1.0, 3.0), 5.0, 42);
+        let c = poisson_disk(field_size, 50, (1.0, 3.0), 5.0, 43);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+}
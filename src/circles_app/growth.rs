@@ -0,0 +1,179 @@
+use crate::circles_app::scalar::{seconds, Scalar};
+use crate::circles_app::simulation::Simulation;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+pub const MIN_RADIUS: Scalar = 1e-3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Growth {
+    /// Radius change per second. Negative values shrink the circle.
+    Rate(Scalar),
+    /// Piecewise-linear radius over time since growth was set, as `(seconds, radius)` keys
+    /// sorted by time. Radius is held at the first and the last keys outside the curve.
+    Curve(Vec<(Scalar, Scalar)>),
+}
+
+impl Growth {
+    pub fn radius(&self, initial_radius: Scalar, time: Duration) -> Scalar {
+        let t = seconds(time);
+        let radius = match self {
+            Growth::Rate(rate) => initial_radius + rate * t,
+            Growth::Curve(keys) => match keys.iter().position(|(key_t, _)| *key_t > t) {
+                None => keys.last().map_or(initial_radius, |(_, r)| *r),
+                Some(0) => keys[0].1,
+                Some(i) => {
+                    let (t0, r0) = keys[i - 1];
+                    let (t1, r1) = keys[i];
+                    r0 + (r1 - r0) * (t - t0) / (t1 - t0)
+                }
+            },
+        };
+        radius.max(MIN_RADIUS)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct JamSettings {
+    /// Relative radius growth per second.
+    pub growth_rate: Scalar,
+    pub time_step: Duration,
+    /// Overlap left after resolution, relative to mean radius, at which circles count as jammed.
+    pub tolerance: Scalar,
+    pub max_steps: usize,
+}
+
+impl Default for JamSettings {
+    fn default() -> Self {
+        Self {
+            growth_rate: 0.1,
+            time_step: Duration::from_millis(10),
+            tolerance: 0.01,
+            max_steps: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct JamReport {
+    pub jammed: bool,
+    pub steps: usize,
+    pub packing_fraction: Scalar,
+}
+
+/// Inflates all circles with dynamics running until overlaps can't be resolved anymore.
+/// Stops unjammed when no circles are left, e.g. all were taken by sinks.
+pub fn grow_until_jammed(simulation: &mut Simulation, settings: JamSettings) -> JamReport {
+    let handles: Vec<_> = simulation.circles().map(|(handle, _)| handle).collect();
+    let scale = 1.0 + settings.growth_rate * seconds(settings.time_step);
+    let mut steps = 0;
+    let mut jammed = false;
+
+    while steps < settings.max_steps && !handles.is_empty() {
+        for &handle in &handles {
+            if let Some(radius) = simulation.circle(handle).map(|c| c.radius()) {
+                simulation.set_radius(handle, radius * scale);
+            }
+        }
+        simulation.step(settings.time_step);
+        steps += 1;
+        if simulation.population() == 0 {
+            break;
+        }
+
+        let mean_radius = simulation
+            .circles()
+            .map(|(_, c)| c.radius())
+            .sum::<Scalar>()
+            / simulation.population() as Scalar;
+        if simulation.max_overlap() > settings.tolerance * mean_radius {
+            jammed = true;
+            break;
+        }
+    }
+
+    JamReport {
+        jammed,
+        steps,
+        packing_fraction: simulation.packing_fraction(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{grow_until_jammed, Growth, JamSettings};
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::scalar::{Scalar, Vector};
+    use crate::circles_app::sensor::SensorShape;
+    use crate::circles_app::simulation::Simulation;
+    use crate::circles_app::sink::Sink;
+    use slog::{Discard, Logger};
+    use std::time::Duration;
+
+    #[test]
+    fn curve_interpolation() {
+        let growth = Growth::Curve(vec![(1.0, 2.0), (3.0, 6.0)]);
+        assert_eq!(growth.radius(1.0, Duration::from_secs(0)), 2.0);
+        assert_eq!(growth.radius(1.0, Duration::from_secs(2)), 4.0);
+        assert_eq!(growth.radius(1.0, Duration::from_secs(5)), 6.0);
+        assert_eq!(
+            Growth::Rate(-1.0).radius(1.0, Duration::from_secs(5)),
+            super::MIN_RADIUS
+        );
+    }
+
+    #[test]
+    fn growing_circle_pushes_neighbour() {
+        let mut sim = Simulation::new((100.0, 100.0).into(), Logger::root(Discard, o!()));
+        let a = sim.add_circle(Circle::new((45.0, 50.0).into(), 4.0, (0.0, 0.0).into()));
+        let b = sim.add_circle(Circle::new((55.0, 50.0).into(), 4.0, (0.0, 0.0).into()));
+        sim.set_growth(a, Some(Growth::Rate(2.0)));
+
+        for _ in 0..100 {
+            sim.step(Duration::from_millis(20));
+        }
+        let a = sim.circle(a).unwrap();
+        let b = sim.circle(b).unwrap();
+        assert!((a.radius() - 8.0).abs() < 1e-3);
+        assert!(!a.is_intersect(b) || sim.max_overlap() < 1e-3);
+    }
+
+    #[test]
+    fn jams_in_small_box() {
+        let mut sim = Simulation::new((20.0, 20.0).into(), Logger::root(Discard, o!()));
+        for i in 0..4 {
+            for j in 0..4 {
+                let center = (2.5 + 5.0 * i as Scalar, 2.5 + 5.0 * j as Scalar).into();
+                let speed = ((i as Scalar - 1.5) * 0.1, (j as Scalar - 1.5) * 0.1).into();
+                sim.add_circle(Circle::new(center, 1.0, speed));
+            }
+        }
+        let settings = JamSettings {
+            growth_rate: 1.0,
+            ..JamSettings::default()
+        };
+        let report = grow_until_jammed(&mut sim, settings);
+        assert!(report.jammed);
+        assert!(report.packing_fraction > 0.5 && report.packing_fraction < 0.91);
+    }
+
+    #[test]
+    fn stops_when_circles_are_gone() {
+        let mut sim = Simulation::new((20.0, 20.0).into(), Logger::root(Discard, o!()));
+        sim.add_circle(Circle::new((10.0, 10.0).into(), 1.0, Vector::zero()));
+        sim.add_sink(Sink::new(
+            SensorShape::Rect {
+                min: Vector::zero(),
+                max: (20.0, 20.0).into(),
+            },
+            Default::default(),
+        ));
+        let report = grow_until_jammed(&mut sim, JamSettings::default());
+        assert!(!report.jammed);
+        assert_eq!(report.steps, 1);
+        assert_eq!(sim.population(), 0);
+
+        let report = grow_until_jammed(&mut sim, JamSettings::default());
+        assert_eq!(report.steps, 0);
+    }
+}
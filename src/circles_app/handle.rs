@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+macro_rules! handle {
+    ($name:ident) => {
+        #[derive(
+            Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+        )]
+        pub struct $name(u64);
+
+        impl $name {
+            pub fn new(id: u64) -> Self {
+                Self(id)
+            }
+
+            pub fn id(self) -> u64 {
+                self.0
+            }
+        }
+    };
+}
+
+handle!(CircleHandle);
+handle!(SensorHandle);
+handle!(EmitterHandle);
+handle!(SinkHandle);
+handle!(CompoundHandle);
+handle!(ObstacleHandle);
@@ -0,0 +1,441 @@
+use crate::circles_app::diagnostics::Conserved;
+use crate::circles_app::scalar::Scalar;
+use crate::circles_app::scene::{Scene, SceneError};
+use crate::circles_app::simulation::Simulation;
+use crate::circles_app::snapshot::{self, SnapshotError};
+use crate::circles_app::trajectory::{ExportFormat, ExportSettings, Sampling, TrajectoryExporter};
+use crate::circles_app::world::{World, WorldError};
+use slog::Logger;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: circles-headless <scene.ron> (--steps N | --time SECONDS) [options]
+
+Options:
+    --dt SECONDS            fixed timestep, default 0.01
+    --seed N                seed of the simulation RNG
+    --output DIR            directory for results, default current
+    --stats-every N         steps between rows of stats.csv, default 100
+    --trajectory FORMAT     also export trajectories as csv or ndjson
+    --sample-every N        steps between trajectory samples, default 1
+    --world CHUNK_SIZE      move the circles in an unbounded world of chunks instead,
+                            writes world.csv only";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RunLength {
+    Steps(u64),
+    Time(Duration),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOptions {
+    pub scene: PathBuf,
+    pub length: RunLength,
+    pub dt: Duration,
+    pub seed: Option<u64>,
+    pub output_dir: PathBuf,
+    pub stats_every: u64,
+    pub trajectory: Option<ExportSettings>,
+    /// Chunk size of the world to run the scene circles in, instead of the simulation.
+    pub world: Option<Scalar>,
+}
+
+impl RunOptions {
+    pub fn new(scene: PathBuf, length: RunLength) -> Self {
+        Self {
+            scene,
+            length,
+            dt: Duration::from_millis(10),
+            seed: None,
+            output_dir: PathBuf::from("."),
+            stats_every: 100,
+            trajectory: None,
+            world: None,
+        }
+    }
+
+    /// Parses command line arguments without the program name.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        let mut scene = None;
+        let mut length = None;
+        let mut options = Self::new(PathBuf::new(), RunLength::Steps(0));
+        let mut sample_every = 1;
+        let mut format = None;
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value of {}", arg))
+            };
+            match arg.as_str() {
+                "--steps" => length = Some(RunLength::Steps(parse(&value()?)?)),
+                "--time" => length = Some(RunLength::Time(seconds(&value()?)?)),
+                "--dt" => options.dt = seconds(&value()?)?,
+                "--seed" => options.seed = Some(parse(&value()?)?),
+                "--output" => options.output_dir = PathBuf::from(value()?),
+                "--stats-every" => options.stats_every = parse(&value()?)?,
+                "--sample-every" => sample_every = parse(&value()?)?,
+                "--world" => options.world = Some(parse(&value()?)?),
+                "--trajectory" => {
+                    format = Some(match value()?.as_str() {
+                        "csv" => ExportFormat::Csv,
+                        "ndjson" => ExportFormat::Ndjson,
+                        other => return Err(format!("Unknown trajectory format: {}", other)),
+                    })
+                }
+                flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+                _ if scene.is_none() => scene = Some(PathBuf::from(arg)),
+                _ => return Err(format!("Unexpected argument: {}", arg)),
+            }
+        }
+        options.scene = scene.ok_or("Scene file is not specified")?;
+        options.length = length.ok_or("Either --steps or --time is required")?;
+        if options.dt == Duration::from_secs(0) {
+            return Err("Timestep must be positive".into());
+        }
+        if let Some(chunk_size) = options.world {
+            if !(chunk_size > 0.0 && chunk_size.is_finite()) {
+                return Err("Chunk size must be positive".into());
+            }
+            if format.is_some() {
+                return Err("Trajectories are not exported from a world".into());
+            }
+        }
+        options.stats_every = options.stats_every.max(1);
+        options.trajectory = format.map(|format| ExportSettings {
+            format,
+            sampling: Sampling::Steps(sample_every),
+            ..ExportSettings::default()
+        });
+        Ok(options)
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number: {}", value))
+}
+
+fn seconds(value: &str) -> Result<Duration, String> {
+    let seconds: f64 = parse(value)?;
+    if !seconds.is_finite() || seconds < 0f64 {
+        return Err(format!("Invalid duration: {}", value));
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    Io(io::Error),
+    Scene(SceneError),
+    Snapshot(SnapshotError),
+    World(WorldError),
+}
+
+impl Error for RunError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RunError::Io(e) => Some(e),
+            RunError::Scene(e) => Some(e),
+            RunError::Snapshot(e) => Some(e),
+            RunError::World(e) => Some(e),
+        }
+    }
+}
+
+impl Display for RunError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            RunError::Io(e) => Display::fmt(e, f),
+            RunError::Scene(e) => write!(f, "Scene: {}", e),
+            RunError::Snapshot(e) => write!(f, "Snapshot: {}", e),
+            RunError::World(e) => write!(f, "World: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for RunError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<SceneError> for RunError {
+    fn from(e: SceneError) -> Self {
+        Self::Scene(e)
+    }
+}
+
+impl From<SnapshotError> for RunError {
+    fn from(e: SnapshotError) -> Self {
+        Self::Snapshot(e)
+    }
+}
+
+impl From<WorldError> for RunError {
+    fn from(e: WorldError) -> Self {
+        Self::World(e)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RunSummary {
+    pub steps: u64,
+    pub time: Duration,
+    pub population: usize,
+    pub state_hash: u64,
+}
+
+/// Runs the scene with fixed timestep and writes `final.ron`, `final.csnp`, `stats.csv` and,
+/// if requested, `circles.*` and `events.*` trajectories into the output directory.
+/// With `world` set, runs the scene circles in a world and writes `world.csv` only.
+pub fn run(options: &RunOptions, logger: Logger) -> Result<RunSummary, RunError> {
+    let scene = Scene::load(&options.scene)?;
+    if let Some(chunk_size) = options.world {
+        let world = scene.to_world(chunk_size, logger.clone())?;
+        return run_world(options, world, logger);
+    }
+    let mut simulation = scene.to_simulation(logger.clone());
+    if let Some(seed) = options.seed {
+        simulation.set_seed(seed);
+    }
+    std::fs::create_dir_all(&options.output_dir)?;
+    let output = |name: &str| options.output_dir.join(name);
+
+    let mut exporter = match &options.trajectory {
+        Some(settings) => {
+            let extension = match settings.format {
+                ExportFormat::Csv => "csv",
+                ExportFormat::Ndjson => "ndjson",
+            };
+            let circles = output(&format!("circles.{}", extension));
+            let events = output(&format!("events.{}", extension));
+            Some(TrajectoryExporter::create(
+                settings.clone(),
+                &circles,
+                Some(&events),
+            )?)
+        }
+        None => None,
+    };
+    let mut stats = BufWriter::new(File::create(output("stats.csv"))?);
+    writeln!(
+        stats,
+        "step,time,count,kinetic_energy,temperature,pressure,momentum_x,momentum_y,angular_momentum"
+    )?;
+    write_stats(&mut stats, &simulation)?;
+
+    info!(
+        logger,
+        "Running {:?} with {} circles for {:?}",
+        options.scene,
+        simulation.population(),
+        options.length
+    );
+    let start_steps = simulation.steps();
+    let start_time = simulation.time();
+    advance(&mut simulation, options.length, options.dt, |simulation| {
+        let (step, time) = (simulation.steps(), simulation.time());
+        if let Some(exporter) = &mut exporter {
+            for event in simulation.events().drain() {
+                exporter.write_event(step, time, &event)?;
+            }
+            exporter.record(simulation)?;
+        }
+        if (step - start_steps) % options.stats_every == 0 {
+            write_stats(&mut stats, simulation)?;
+        }
+        Ok::<(), io::Error>(())
+    })?;
+    stats.flush()?;
+    if let Some(exporter) = exporter {
+        exporter.finish()?;
+    }
+
+    Scene::from_simulation(&simulation).save(&output("final.ron"))?;
+    snapshot::save(&simulation, &output("final.csnp"))?;
+    let summary = RunSummary {
+        steps: simulation.steps() - start_steps,
+        time: simulation.time() - start_time,
+        population: simulation.population(),
+        state_hash: simulation.state_hash(),
+    };
+    info!(logger, "Finished: {:?}", summary);
+    Ok(summary)
+}
+
+fn run_world(
+    options: &RunOptions,
+    mut world: World,
+    logger: Logger,
+) -> Result<RunSummary, RunError> {
+    std::fs::create_dir_all(&options.output_dir)?;
+    let mut stats = BufWriter::new(File::create(options.output_dir.join("world.csv"))?);
+    writeln!(stats, "step,time,population,awake")?;
+    write_world_stats(&mut stats, &world)?;
+    info!(
+        logger,
+        "Running {:?} with {} circles in a world for {:?}",
+        options.scene,
+        world.population(),
+        options.length
+    );
+    let (start_steps, start_time) = (world.steps(), world.time());
+    loop {
+        let done = match options.length {
+            RunLength::Steps(steps) => world.steps() - start_steps >= steps,
+            RunLength::Time(time) => world.time() - start_time >= time,
+        };
+        if done {
+            break;
+        }
+        world.step(options.dt)?;
+        if (world.steps() - start_steps).is_multiple_of(options.stats_every) {
+            write_world_stats(&mut stats, &world)?;
+        }
+    }
+    stats.flush()?;
+    let summary = RunSummary {
+        steps: world.steps() - start_steps,
+        time: world.time() - start_time,
+        population: world.population(),
+        state_hash: world.state_hash(),
+    };
+    info!(logger, "Finished: {:?}", summary);
+    Ok(summary)
+}
+
+fn write_world_stats<W: Write>(writer: &mut W, world: &World) -> io::Result<()> {
+    writeln!(
+        writer,
+        "{},{},{},{}",
+        world.steps(),
+        world.time().as_secs_f64(),
+        world.population(),
+        world.awake_circles().count()
+    )
+}
+
+/// Steps with fixed `dt` until `length` has passed, calling `on_step` after every step.
+/// Events not drained by `on_step` are discarded.
+pub fn advance<F, E>(
+    simulation: &mut Simulation,
+    length: RunLength,
+    dt: Duration,
+    mut on_step: F,
+) -> Result<(), E>
+where
+    F: FnMut(&mut Simulation) -> Result<(), E>,
+{
+    let start_steps = simulation.steps();
+    let start_time = simulation.time();
+    loop {
+        let done = match length {
+            RunLength::Steps(steps) => simulation.steps() - start_steps >= steps,
+            RunLength::Time(time) => simulation.time() - start_time >= time,
+        };
+        if done {
+            return Ok(());
+        }
+        simulation.step(dt);
+        on_step(simulation)?;
+        simulation.events().drain();
+    }
+}
+
+fn write_stats<W: Write>(writer: &mut W, simulation: &Simulation) -> io::Result<()> {
+    let observables = simulation.observables();
+    let conserved = Conserved::measure(simulation);
+    writeln!(
+        writer,
+        "{},{},{},{},{},{},{},{},{}",
+        simulation.steps(),
+        simulation.time().as_secs_f64(),
+        observables.count,
+        observables.kinetic_energy,
+        observables.temperature,
+        observables.pressure,
+        conserved.momentum.x(),
+        conserved.momentum.y(),
+        conserved.angular_momentum
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, RunLength, RunOptions};
+    use crate::circles_app::generators;
+    use crate::circles_app::scene::Scene;
+    use crate::circles_app::simulation::Simulation;
+    use slog::{Discard, Logger};
+    use std::time::Duration;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let options =
+            RunOptions::from_args(args("gas.ron --time 2.5 --dt 0.005 --trajectory ndjson"))
+                .unwrap();
+        assert_eq!(options.length, RunLength::Time(Duration::from_millis(2500)));
+        assert_eq!(options.dt, Duration::from_millis(5));
+        assert!(options.trajectory.is_some());
+
+        assert!(RunOptions::from_args(args("gas.ron")).is_err());
+        assert!(RunOptions::from_args(args("--steps 10")).is_err());
+        assert!(RunOptions::from_args(args("gas.ron --steps ten")).is_err());
+        assert!(RunOptions::from_args(args("gas.ron --steps 1 --fast")).is_err());
+        assert!(RunOptions::from_args(args("gas.ron --steps 1 --world 0")).is_err());
+        assert!(
+            RunOptions::from_args(args("gas.ron --steps 1 --world 10 --trajectory csv")).is_err()
+        );
+    }
+
+    #[test]
+    fn run_writes_results() {
+        let dir = std::env::temp_dir().join(format!("circles-headless-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let field_size = (100.0, 100.0).into();
+        let mut sim = Simulation::new(field_size, Logger::root(Discard, o!()));
+        for circle in generators::poisson_disk(field_size, 30, (2.0, 4.0), 20.0, 2) {
+            sim.add_circle(circle);
+        }
+        let scene = dir.join("scene.ron");
+        Scene::from_simulation(&sim).save(&scene).unwrap();
+
+        let mut options =
+            RunOptions::from_args(args("x --steps 50 --stats-every 10 --trajectory csv")).unwrap();
+        options.scene = scene;
+        options.output_dir = dir.join("out");
+        let summary = run(&options, Logger::root(Discard, o!())).unwrap();
+        assert_eq!(summary.steps, 50);
+        assert_eq!(summary.population, 30);
+
+        let stats = std::fs::read_to_string(options.output_dir.join("stats.csv")).unwrap();
+        assert_eq!(stats.lines().count(), 1 + 1 + 5);
+        let circles = std::fs::read_to_string(options.output_dir.join("circles.csv")).unwrap();
+        assert_eq!(circles.lines().count(), 1 + 50 * 30);
+        for name in &["final.ron", "final.csnp", "events.csv"] {
+            assert!(options.output_dir.join(name).exists(), "{}", name);
+        }
+
+        options.world = Some(10.0);
+        options.trajectory = None;
+        options.output_dir = dir.join("world");
+        let summary = run(&options, Logger::root(Discard, o!())).unwrap();
+        assert_eq!(summary.steps, 50);
+        assert_eq!(summary.population, 30);
+        assert_eq!(run(&options, Logger::root(Discard, o!())).unwrap(), summary);
+        let stats = std::fs::read_to_string(options.output_dir.join("world.csv")).unwrap();
+        assert_eq!(stats.lines().count(), 1 + 1 + 5);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,28 @@
+use crate::circles_app::scalar::Scalar;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Material {
+    pub density: Scalar,
+    /// Fraction of normal speed kept after a collision. 1 is perfectly elastic.
+    pub restitution: Scalar,
+}
+
+impl Material {
+    pub fn new(density: Scalar, restitution: Scalar) -> Self {
+        Self {
+            density,
+            restitution,
+        }
+    }
+
+    pub fn combined_restitution(self, other: Self) -> Scalar {
+        self.restitution.min(other.restitution)
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new(1.0, 1.0)
+    }
+}
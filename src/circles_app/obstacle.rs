@@ -0,0 +1,110 @@
+use crate::circles_app::circle::Circle;
+use crate::circles_app::filter::CollisionFilter;
+use crate::circles_app::scalar::Vector;
+use crate::circles_app::sensor::SensorShape;
+use serde::{Deserialize, Serialize};
+
+/// Static solid region. Circles bounce off it as off walls, the obstacle itself never moves.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Obstacle {
+    shape: SensorShape,
+    filter: CollisionFilter,
+}
+
+impl Obstacle {
+    pub fn new(shape: SensorShape, filter: CollisionFilter) -> Self {
+        Self { shape, filter }
+    }
+
+    pub fn shape(&self) -> SensorShape {
+        self.shape
+    }
+
+    pub fn filter(&self) -> CollisionFilter {
+        self.filter
+    }
+
+    /// Surface point closest to a touching `circle` and unit normal out of the obstacle there.
+    /// Circles with centers inside a rectangle are sent out through its nearest side.
+    pub fn contact(&self, circle: &Circle) -> Option<(Vector, Vector)> {
+        if !self.filter.interacts(circle.filter()) {
+            return None;
+        }
+        let center = circle.center();
+        match self.shape {
+            SensorShape::Circle {
+                center: obstacle_center,
+                radius,
+            } => {
+                let outwards = center - obstacle_center;
+                let distance = outwards.length();
+                if distance >= radius + circle.radius() || distance == 0.0 {
+                    return None;
+                }
+                let normal = outwards / distance;
+                Some((obstacle_center + normal * radius, normal))
+            }
+            SensorShape::Rect { min, max } => {
+                let closest = center.max(min).min(max);
+                let outwards = center - closest;
+                if outwards != Vector::zero() {
+                    let distance = outwards.length();
+                    if distance >= circle.radius() {
+                        return None;
+                    }
+                    return Some((closest, outwards / distance));
+                }
+                let sides = [
+                    (center.x() - min.x(), Vector::new(-1.0, 0.0)),
+                    (max.x() - center.x(), Vector::new(1.0, 0.0)),
+                    (center.y() - min.y(), Vector::new(0.0, -1.0)),
+                    (max.y() - center.y(), Vector::new(0.0, 1.0)),
+                ];
+                let (depth, normal) = sides[1..].iter().fold(sides[0], |nearest, &side| {
+                    if side.0 < nearest.0 {
+                        side
+                    } else {
+                        nearest
+                    }
+                });
+                Some((center + normal * depth, normal))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Obstacle;
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::filter::CollisionFilter;
+    use crate::circles_app::scalar::Vector;
+    use crate::circles_app::sensor::SensorShape;
+
+    #[test]
+    fn rect_contacts() {
+        let obstacle = Obstacle::new(
+            SensorShape::Rect {
+                min: (0.0, 0.0).into(),
+                max: (10.0, 2.0).into(),
+            },
+            CollisionFilter::default(),
+        );
+        let touching = Circle::new((5.0, 2.5).into(), 1.0, Vector::zero());
+        assert_eq!(
+            obstacle.contact(&touching),
+            Some(((5.0, 2.0).into(), (0.0, 1.0).into()))
+        );
+        let inside = Circle::new((9.5, 1.0).into(), 1.0, Vector::zero());
+        assert_eq!(
+            obstacle.contact(&inside),
+            Some(((10.0, 1.0).into(), (1.0, 0.0).into()))
+        );
+        let apart = Circle::new((5.0, 3.5).into(), 1.0, Vector::zero());
+        assert_eq!(obstacle.contact(&apart), None);
+
+        let mut masked = touching;
+        masked.set_filter(CollisionFilter::new(0b10, 0b10));
+        assert_eq!(obstacle.contact(&masked), None);
+    }
+}
@@ -0,0 +1,404 @@
+use crate::circles_app::command::Command;
+use crate::circles_app::scalar::SCALAR_SIZE;
+use crate::circles_app::simulation::{Simulation, SimulationState};
+use serde::{Deserialize, Serialize};
+use slog::Logger;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+// Layout: magic, format version (u16 LE), `SCALAR_SIZE` of the writer (u8), bincode encoded
+// `Replay`. States are embedded in frames, so replays of older versions can't be upgraded and are
+// rejected. Version 2 added compounds, version 3 the scalar size, version 4 obstacles.
+pub const MAGIC: [u8; 4] = *b"CRPL";
+pub const FORMAT_VERSION: u16 = 4;
+pub const REPLAY_EXTENSION: &str = "crpl";
+pub const DEFAULT_KEYFRAME_INTERVAL: u64 = 300;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Encoding(bincode::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// Scalar size in bytes of the build that wrote the replay.
+    PrecisionMismatch(u8),
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplayError::Io(e) => Some(e),
+            ReplayError::Encoding(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(e) => Display::fmt(e, f),
+            ReplayError::Encoding(e) => Display::fmt(e, f),
+            ReplayError::BadMagic => write!(f, "Not a circles replay"),
+            ReplayError::UnsupportedVersion(v) => write!(
+                f,
+                "Replay format version {} is not supported, expected {}",
+                v, FORMAT_VERSION
+            ),
+            ReplayError::PrecisionMismatch(size) => write!(
+                f,
+                "Replay has {}-byte scalars, this build uses {}-byte ones",
+                size, SCALAR_SIZE
+            ),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<bincode::Error> for ReplayError {
+    fn from(e: bincode::Error) -> Self {
+        Self::Encoding(e)
+    }
+}
+
+/// Input of one simulation step: commands applied before the step and its duration.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Frame {
+    pub commands: Vec<Command>,
+    pub dt: Duration,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    initial: SimulationState,
+    frames: Vec<Frame>,
+    /// State before the frame with given index. Used for seeking.
+    keyframes: Vec<(usize, SimulationState)>,
+}
+
+impl Replay {
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.dt).sum()
+    }
+
+    pub fn initial_simulation(&self, logger: Logger) -> Simulation {
+        Simulation::from_state(self.initial.clone(), logger)
+    }
+
+    fn keyframe_before(&self, frame: usize) -> (usize, &SimulationState) {
+        self.keyframes
+            .iter()
+            .rev()
+            .find(|(index, _)| *index <= frame)
+            .map(|(index, state)| (*index, state))
+            .unwrap_or((0, &self.initial))
+    }
+}
+
+pub struct ReplayRecorder {
+    replay: Replay,
+    pending: Vec<Command>,
+    keyframe_interval: u64,
+}
+
+impl ReplayRecorder {
+    pub fn new(simulation: &Simulation, keyframe_interval: u64) -> Self {
+        Self {
+            replay: Replay {
+                initial: simulation.state().clone(),
+                frames: Vec::new(),
+                keyframes: Vec::new(),
+            },
+            pending: Vec::new(),
+            keyframe_interval: keyframe_interval.max(1),
+        }
+    }
+
+    /// Must be called for every command applied to the recorded simulation.
+    pub fn record_command(&mut self, command: Command) {
+        self.pending.push(command);
+    }
+
+    /// Must be called after every step of the recorded simulation.
+    pub fn record_step(&mut self, dt: Duration, simulation: &Simulation) {
+        let commands = std::mem::take(&mut self.pending);
+        self.replay.frames.push(Frame { commands, dt });
+        let last_keyframe = self.replay.keyframes.last().map_or(0, |(index, _)| *index);
+        let recorded = self.replay.frames.len();
+        if (recorded - last_keyframe) as u64 >= self.keyframe_interval {
+            self.replay
+                .keyframes
+                .push((recorded, simulation.state().clone()));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.replay.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replay.frames.is_empty()
+    }
+
+    /// Commands recorded after the last step are dropped.
+    pub fn finish(self) -> Replay {
+        self.replay
+    }
+}
+
+pub struct ReplayPlayer {
+    replay: Replay,
+    position: usize,
+    speed: f32,
+    lag: Duration,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            position: 0,
+            speed: 1f32,
+            lag: Duration::from_secs(0),
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Index of the next frame to play.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.replay.len()
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0f32);
+    }
+
+    /// Frames to play to keep up with wall clock scaled by speed.
+    pub fn due_frames(&mut self, wall_elapsed: Duration) -> Vec<Frame> {
+        self.lag += wall_elapsed.mul_f32(self.speed);
+        let mut due = Vec::new();
+        while let Some(frame) = self.replay.frames.get(self.position) {
+            if frame.dt > self.lag {
+                break;
+            }
+            self.lag -= frame.dt;
+            self.position += 1;
+            due.push(frame.clone());
+        }
+        if self.is_finished() {
+            self.lag = Duration::from_secs(0);
+        }
+        due
+    }
+
+    pub fn next_frame(&mut self) -> Option<Frame> {
+        let frame = self.replay.frames.get(self.position).cloned()?;
+        self.position += 1;
+        Some(frame)
+    }
+
+    /// Restores nearest keyframe and replays frames up to `frame`.
+    /// Events emitted while catching up stay in the simulation queue.
+    pub fn seek(&mut self, frame: usize, simulation: &mut Simulation) {
+        let frame = frame.min(self.replay.len());
+        let (start, state) = self.replay.keyframe_before(frame);
+        simulation.set_state(state.clone());
+        for played in &self.replay.frames[start..frame] {
+            for command in played.commands.iter().cloned() {
+                simulation.apply(command);
+            }
+            simulation.step(played.dt);
+        }
+        self.position = frame;
+        self.lag = Duration::from_secs(0);
+    }
+}
+
+pub fn write<W: Write>(replay: &Replay, mut writer: W) -> Result<(), ReplayError> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    writer.write_all(&[SCALAR_SIZE])?;
+    bincode::serialize_into(&mut writer, replay)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read<R: Read>(mut reader: R) -> Result<Replay, ReplayError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(ReplayError::BadMagic);
+    }
+    let mut version = [0u8; 2];
+    reader.read_exact(&mut version)?;
+    let version = u16::from_le_bytes(version);
+    if version != FORMAT_VERSION {
+        return Err(ReplayError::UnsupportedVersion(version));
+    }
+    let mut precision = [0u8; 1];
+    reader.read_exact(&mut precision)?;
+    if precision[0] != SCALAR_SIZE {
+        return Err(ReplayError::PrecisionMismatch(precision[0]));
+    }
+    Ok(bincode::deserialize_from(reader)?)
+}
+
+pub fn save(replay: &Replay, path: &Path) -> Result<(), ReplayError> {
+    write(replay, BufWriter::new(File::create(path)?))
+}
+
+pub fn load(path: &Path) -> Result<Replay, ReplayError> {
+    read(BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read, write, Replay, ReplayError, ReplayPlayer, ReplayRecorder};
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::command::Command;
+    use crate::circles_app::generators;
+    use crate::circles_app::scalar::SCALAR_SIZE;
+    use crate::circles_app::simulation::Simulation;
+    use crate::circles_app::snapshot::encode_state;
+    use crate::circles_app::thermostat::Thermostat;
+    use slog::{Discard, Logger};
+    use std::time::Duration;
+
+    fn logger() -> Logger {
+        Logger::root(Discard, o!())
+    }
+
+    fn command_at(step: usize) -> Option<Command> {
+        match step {
+            10 => Some(Command::SetGravity((0.0, 20.0).into())),
+            25 => Some(Command::AddCircle(Circle::new(
+                (100.0, 100.0).into(),
+                3.0,
+                (10.0, -5.0).into(),
+            ))),
+            40 => Some(Command::SetThermostat(Some(Thermostat::Andersen {
+                target: 300.0,
+                frequency: 2.0,
+            }))),
+            _ => None,
+        }
+    }
+
+    fn record(steps: usize) -> (Replay, Simulation) {
+        let field_size = (200.0, 200.0).into();
+        let mut sim = Simulation::new(field_size, logger());
+        for circle in generators::poisson_disk(field_size, 60, (2.0, 5.0), 40.0, 1) {
+            sim.add_circle(circle);
+        }
+        let mut recorder = ReplayRecorder::new(&sim, 16);
+        for step in 0..steps {
+            if let Some(command) = command_at(step) {
+                recorder.record_command(command.clone());
+                sim.apply(command);
+            }
+            let dt = Duration::from_millis(5 + (step % 7) as u64);
+            sim.step(dt);
+            recorder.record_step(dt, &sim);
+        }
+        (recorder.finish(), sim)
+    }
+
+    #[test]
+    fn playback_reproduces_recorded_run() {
+        let (replay, recorded) = record(60);
+        let mut bytes = Vec::new();
+        write(&replay, &mut bytes).unwrap();
+        let replay = read(bytes.as_slice()).unwrap();
+
+        let mut sim = replay.initial_simulation(logger());
+        let mut player = ReplayPlayer::new(replay);
+        while let Some(frame) = player.next_frame() {
+            for command in frame.commands {
+                sim.apply(command);
+            }
+            sim.step(frame.dt);
+        }
+        assert_eq!(
+            encode_state(sim.state()).unwrap(),
+            encode_state(recorded.state()).unwrap()
+        );
+    }
+
+    #[test]
+    fn other_precision_is_rejected() {
+        let (replay, _) = record(5);
+        let mut bytes = Vec::new();
+        write(&replay, &mut bytes).unwrap();
+        assert_eq!(bytes[6], SCALAR_SIZE);
+        let other = if SCALAR_SIZE == 4 { 8 } else { 4 };
+        bytes[6] = other;
+        let result = read(bytes.as_slice());
+        assert!(matches!(result, Err(ReplayError::PrecisionMismatch(size)) if size == other));
+    }
+
+    #[test]
+    fn seek_matches_playback_from_start() {
+        let (replay, _) = record(60);
+        let mut direct = replay.initial_simulation(logger());
+        let mut player = ReplayPlayer::new(replay.clone());
+        for _ in 0..37 {
+            let frame = player.next_frame().unwrap();
+            for command in frame.commands {
+                direct.apply(command);
+            }
+            direct.step(frame.dt);
+        }
+
+        let mut sought = replay.initial_simulation(logger());
+        let mut player = ReplayPlayer::new(replay);
+        player.seek(50, &mut sought);
+        player.seek(37, &mut sought);
+        assert_eq!(player.position(), 37);
+        assert_eq!(
+            encode_state(sought.state()).unwrap(),
+            encode_state(direct.state()).unwrap()
+        );
+    }
+
+    #[test]
+    fn speed_scales_due_frames() {
+        let (replay, _) = record(20);
+        let mut player = ReplayPlayer::new(replay);
+        let normal = player.due_frames(Duration::from_millis(50)).len();
+        player.set_speed(4f32);
+        let fast = player.due_frames(Duration::from_millis(50)).len();
+        assert!(fast > 2 * normal, "{} vs {}", fast, normal);
+    }
+}
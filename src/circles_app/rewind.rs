@@ -0,0 +1,302 @@
+use crate::circles_app::simulation::SimulationState;
+use crate::circles_app::snapshot::{decode_state, encode_state};
+use std::collections::VecDeque;
+
+pub const DEFAULT_BUDGET: usize = 64 * 1024 * 1024;
+pub const DEFAULT_KEYFRAME_INTERVAL: usize = 60;
+// Equal runs shorter than this are cheaper to store as part of changed bytes.
+const MIN_KEEP_RUN: usize = 8;
+
+enum Entry {
+    Full(Vec<u8>),
+    /// Spans of (kept count u32 LE, changed count u32 LE, changed bytes) against previous state.
+    Delta(Vec<u8>),
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        match self {
+            Entry::Full(bytes) | Entry::Delta(bytes) => bytes.len(),
+        }
+    }
+}
+
+/// Ring buffer of recent encoded states. Most states are stored as deltas to the previous one,
+/// with a full state every `keyframe_interval` entries to bound reconstruction cost.
+pub struct Rewind {
+    entries: VecDeque<Entry>,
+    latest: Vec<u8>,
+    since_keyframe: usize,
+    memory: usize,
+    budget: usize,
+    keyframe_interval: usize,
+    cursor: Option<usize>,
+}
+
+impl Rewind {
+    /// `budget` is the maximum number of bytes kept. The oldest states are dropped first.
+    pub fn new(budget: usize, keyframe_interval: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            latest: Vec::new(),
+            since_keyframe: 0,
+            memory: 0,
+            budget,
+            keyframe_interval: keyframe_interval.max(1),
+            cursor: None,
+        }
+    }
+
+    /// Adds the newest state. If scrubbing, states after the cursor are discarded first.
+    pub fn push(&mut self, state: &SimulationState) {
+        if self.cursor.is_some() {
+            self.branch();
+        }
+        let bytes = encode_state(state).expect("Can't encode simulation state");
+        let entry = if self.entries.is_empty() || self.since_keyframe + 1 >= self.keyframe_interval
+        {
+            self.since_keyframe = 0;
+            Entry::Full(bytes.clone())
+        } else {
+            self.since_keyframe += 1;
+            Entry::Delta(diff(&self.latest, &bytes))
+        };
+        self.memory += entry.size();
+        self.entries.push_back(entry);
+        self.latest = bytes;
+        self.evict();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes used by stored states.
+    pub fn memory(&self) -> usize {
+        self.memory
+    }
+
+    /// Index of the viewed state, `None` when following the newest one.
+    pub fn cursor(&self) -> Option<usize> {
+        self.cursor
+    }
+
+    pub fn is_scrubbing(&self) -> bool {
+        self.cursor.is_some()
+    }
+
+    /// Moves cursor by `offset` states and returns the state under it.
+    pub fn scrub(&mut self, offset: isize) -> Option<SimulationState> {
+        let newest = self.entries.len().checked_sub(1)?;
+        let from = self.cursor.unwrap_or(newest) as isize;
+        let cursor = (from + offset).max(0).min(newest as isize) as usize;
+        self.cursor = Some(cursor);
+        let bytes = self.bytes_at(cursor);
+        Some(decode_state(&bytes).expect("Can't decode rewound state"))
+    }
+
+    /// Makes the state under the cursor the newest one, dropping the ones after it.
+    pub fn branch(&mut self) {
+        let cursor = match self.cursor.take() {
+            Some(cursor) => cursor,
+            None => return,
+        };
+        self.latest = self.bytes_at(cursor);
+        for entry in self.entries.drain(cursor + 1..) {
+            self.memory -= entry.size();
+        }
+        self.since_keyframe = self
+            .entries
+            .iter()
+            .rev()
+            .take_while(|entry| matches!(entry, Entry::Delta(_)))
+            .count();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.latest.clear();
+        self.memory = 0;
+        self.since_keyframe = 0;
+        self.cursor = None;
+    }
+
+    fn bytes_at(&self, index: usize) -> Vec<u8> {
+        let keyframe = (0..=index)
+            .rev()
+            .find(|i| matches!(self.entries[*i], Entry::Full(_)))
+            .expect("Rewind buffer must start with a full state");
+        let mut bytes = Vec::new();
+        for entry in self.entries.range(keyframe..=index) {
+            bytes = match entry {
+                Entry::Full(full) => full.clone(),
+                Entry::Delta(delta) => patch(&bytes, delta),
+            };
+        }
+        bytes
+    }
+
+    fn evict(&mut self) {
+        while self.memory > self.budget && self.entries.len() > 1 {
+            if let Entry::Delta(_) = self.entries[1] {
+                let bytes = self.bytes_at(1);
+                self.memory -= self.entries[1].size();
+                self.memory += bytes.len();
+                self.entries[1] = Entry::Full(bytes);
+            }
+            let oldest = self
+                .entries
+                .pop_front()
+                .expect("Rewind buffer is not empty");
+            self.memory -= oldest.size();
+            self.cursor = match self.cursor {
+                Some(0) | None => self.cursor,
+                Some(cursor) => Some(cursor - 1),
+            };
+        }
+    }
+}
+
+fn diff(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let same = |i: usize| base.get(i) == Some(&target[i]);
+    let mut delta = Vec::new();
+    let mut i = 0;
+    while i < target.len() {
+        let keep_start = i;
+        while i < target.len() && same(i) {
+            i += 1;
+        }
+        let keep = i - keep_start;
+        let changed_start = i;
+        while i < target.len() {
+            if !same(i) {
+                i += 1;
+                continue;
+            }
+            let run = (i..target.len()).take_while(|j| same(*j)).count();
+            if run >= MIN_KEEP_RUN || i + run == target.len() {
+                break;
+            }
+            i += run;
+        }
+        delta.extend_from_slice(&(keep as u32).to_le_bytes());
+        delta.extend_from_slice(&((i - changed_start) as u32).to_le_bytes());
+        delta.extend_from_slice(&target[changed_start..i]);
+    }
+    delta
+}
+
+fn patch(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let read_u32 = |at: usize| {
+        let mut word = [0u8; 4];
+        word.copy_from_slice(&delta[at..at + 4]);
+        u32::from_le_bytes(word) as usize
+    };
+    let mut bytes = Vec::with_capacity(base.len());
+    let mut at = 0;
+    while at < delta.len() {
+        let keep = read_u32(at);
+        let changed = read_u32(at + 4);
+        at += 8;
+        let offset = bytes.len();
+        bytes.extend_from_slice(&base[offset..offset + keep]);
+        bytes.extend_from_slice(&delta[at..at + changed]);
+        at += changed;
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, patch, Rewind};
+    use crate::circles_app::generators;
+    use crate::circles_app::simulation::{Simulation, SimulationState};
+    use crate::circles_app::snapshot::encode_state;
+    use slog::{Discard, Logger};
+    use std::time::Duration;
+
+    fn states(count: usize) -> Vec<SimulationState> {
+        let field_size = (200.0, 200.0).into();
+        let mut sim = Simulation::new(field_size, Logger::root(Discard, o!()));
+        for circle in generators::poisson_disk(field_size, 80, (2.0, 5.0), 30.0, 5) {
+            sim.add_circle(circle);
+        }
+        (0..count)
+            .map(|_| {
+                sim.step(Duration::from_millis(10));
+                sim.state().clone()
+            })
+            .collect()
+    }
+
+    fn bytes(state: &SimulationState) -> Vec<u8> {
+        encode_state(state).unwrap()
+    }
+
+    #[test]
+    fn patch_restores_diffed_bytes() {
+        let base = vec![1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+        for target in &[
+            vec![1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+            vec![1u8, 9, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14],
+            vec![0u8, 2, 3],
+            vec![],
+        ] {
+            assert_eq!(&patch(&base, &diff(&base, target)), target);
+        }
+    }
+
+    #[test]
+    fn scrub_restores_exact_states() {
+        let states = states(50);
+        let mut rewind = Rewind::new(usize::MAX, 16);
+        for state in &states {
+            rewind.push(state);
+        }
+        let full: usize = states.iter().map(|state| bytes(state).len()).sum();
+        assert!(rewind.memory() < full, "{} of {}", rewind.memory(), full);
+
+        let state = rewind.scrub(-20).unwrap();
+        assert_eq!(bytes(&state), bytes(&states[29]));
+        let state = rewind.scrub(7).unwrap();
+        assert_eq!(bytes(&state), bytes(&states[36]));
+        let state = rewind.scrub(-100).unwrap();
+        assert_eq!(bytes(&state), bytes(&states[0]));
+    }
+
+    #[test]
+    fn budget_drops_oldest_states() {
+        let states = states(40);
+        let budget = 4 * bytes(&states[0]).len();
+        let mut rewind = Rewind::new(budget, 8);
+        for state in &states {
+            rewind.push(state);
+        }
+        assert!(rewind.memory() <= budget);
+        let kept = rewind.len();
+        assert!(kept > 1 && kept < states.len());
+        let oldest = rewind.scrub(-(states.len() as isize)).unwrap();
+        assert_eq!(bytes(&oldest), bytes(&states[states.len() - kept]));
+    }
+
+    #[test]
+    fn push_after_scrub_branches() {
+        let states = states(30);
+        let mut rewind = Rewind::new(usize::MAX, 8);
+        for state in &states[..20] {
+            rewind.push(state);
+        }
+        rewind.scrub(-5);
+        rewind.push(&states[25]);
+        assert_eq!(rewind.len(), 16);
+        assert!(!rewind.is_scrubbing());
+        let state = rewind.scrub(-1).unwrap();
+        assert_eq!(bytes(&state), bytes(&states[14]));
+        let state = rewind.scrub(1).unwrap();
+        assert_eq!(bytes(&state), bytes(&states[25]));
+    }
+}
@@ -0,0 +1,467 @@
+//! Precision of the simulation core: `f32` with glam `Vec2` by default, `f64` with the `f64`
+//! feature. Rendering always works in `f32`, use `to_render` at that boundary.
+use glam::{Vec2, Vec3};
+use std::time::Duration;
+
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
+#[cfg(not(feature = "f64"))]
+pub type Scalar = f32;
+#[cfg(feature = "f64")]
+pub type Scalar = f64;
+
+/// Bytes per scalar. Snapshots and replays store it, as only builds of the same precision can
+/// decode them.
+pub const SCALAR_SIZE: u8 = std::mem::size_of::<Scalar>() as u8;
+
+#[cfg(not(feature = "f64"))]
+pub type Vector = Vec2;
+#[cfg(feature = "f64")]
+pub type Vector = dvec2::DVec2;
+
+/// Vector of the 3D sphere mode.
+#[cfg(not(feature = "f64"))]
+pub type Vector3 = Vec3;
+#[cfg(feature = "f64")]
+pub type Vector3 = dvec3::DVec3;
+
+#[cfg(not(feature = "f64"))]
+pub fn seconds(duration: Duration) -> Scalar {
+    duration.as_secs_f32()
+}
+
+#[cfg(feature = "f64")]
+pub fn seconds(duration: Duration) -> Scalar {
+    duration.as_secs_f64()
+}
+
+#[cfg(not(feature = "f64"))]
+pub fn duration(seconds: Scalar) -> Duration {
+    Duration::from_secs_f32(seconds)
+}
+
+#[cfg(feature = "f64")]
+pub fn duration(seconds: Scalar) -> Duration {
+    Duration::from_secs_f64(seconds)
+}
+
+/// Widens to `f64` for accumulators and statistics.
+#[cfg(not(feature = "f64"))]
+pub fn widen(value: Scalar) -> f64 {
+    value.into()
+}
+
+#[cfg(feature = "f64")]
+pub fn widen(value: Scalar) -> f64 {
+    value
+}
+
+#[cfg(not(feature = "f64"))]
+pub fn narrow(value: Scalar) -> f32 {
+    value
+}
+
+#[cfg(feature = "f64")]
+pub fn narrow(value: Scalar) -> f32 {
+    value as f32
+}
+
+#[cfg(not(feature = "f64"))]
+pub fn to_render(vector: Vector) -> Vec2 {
+    vector
+}
+
+#[cfg(feature = "f64")]
+pub fn to_render(vector: Vector) -> Vec2 {
+    Vec2::new(vector.x() as f32, vector.y() as f32)
+}
+
+#[cfg(not(feature = "f64"))]
+pub fn from_render(vector: Vec2) -> Vector {
+    vector
+}
+
+#[cfg(feature = "f64")]
+pub fn from_render(vector: Vec2) -> Vector {
+    Vector::new(vector.x().into(), vector.y().into())
+}
+
+#[cfg(not(feature = "f64"))]
+pub fn to_render3(vector: Vector3) -> Vec3 {
+    vector
+}
+
+#[cfg(feature = "f64")]
+pub fn to_render3(vector: Vector3) -> Vec3 {
+    Vec3::new(vector.x() as f32, vector.y() as f32, vector.z() as f32)
+}
+
+#[cfg(feature = "f64")]
+pub mod dvec2 {
+    use serde::{Deserialize, Serialize};
+    use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+    /// Double precision counterpart of glam `Vec2` with the subset of its API the core uses.
+    /// Serialized like `Vec2`, so scenes are shared between precisions.
+    #[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+    #[serde(rename = "Vec2")]
+    pub struct DVec2(f64, f64);
+
+    impl DVec2 {
+        pub fn new(x: f64, y: f64) -> Self {
+            Self(x, y)
+        }
+
+        pub fn zero() -> Self {
+            Self(0f64, 0f64)
+        }
+
+        pub fn one() -> Self {
+            Self(1f64, 1f64)
+        }
+
+        pub fn splat(v: f64) -> Self {
+            Self(v, v)
+        }
+
+        pub fn x(self) -> f64 {
+            self.0
+        }
+
+        pub fn y(self) -> f64 {
+            self.1
+        }
+
+        pub fn set_x(&mut self, x: f64) {
+            self.0 = x;
+        }
+
+        pub fn set_y(&mut self, y: f64) {
+            self.1 = y;
+        }
+
+        pub fn dot(self, other: Self) -> f64 {
+            self.0 * other.0 + self.1 * other.1
+        }
+
+        pub fn length_squared(self) -> f64 {
+            self.dot(self)
+        }
+
+        pub fn length(self) -> f64 {
+            self.length_squared().sqrt()
+        }
+
+        pub fn length_reciprocal(self) -> f64 {
+            1f64 / self.length()
+        }
+
+        pub fn normalize(self) -> Self {
+            self * self.length_reciprocal()
+        }
+
+        pub fn min_element(self) -> f64 {
+            self.0.min(self.1)
+        }
+
+        pub fn max_element(self) -> f64 {
+            self.0.max(self.1)
+        }
+
+        pub fn min(self, other: Self) -> Self {
+            Self(self.0.min(other.0), self.1.min(other.1))
+        }
+
+        pub fn max(self, other: Self) -> Self {
+            Self(self.0.max(other.0), self.1.max(other.1))
+        }
+
+        pub fn abs(self) -> Self {
+            Self(self.0.abs(), self.1.abs())
+        }
+
+        pub fn floor(self) -> Self {
+            Self(self.0.floor(), self.1.floor())
+        }
+
+        pub fn round(self) -> Self {
+            Self(self.0.round(), self.1.round())
+        }
+    }
+
+    impl From<(f64, f64)> for DVec2 {
+        fn from((x, y): (f64, f64)) -> Self {
+            Self(x, y)
+        }
+    }
+
+    impl From<DVec2> for (f64, f64) {
+        fn from(v: DVec2) -> Self {
+            (v.0, v.1)
+        }
+    }
+
+    impl Add for DVec2 {
+        type Output = Self;
+        fn add(self, other: Self) -> Self {
+            Self(self.0 + other.0, self.1 + other.1)
+        }
+    }
+
+    impl Sub for DVec2 {
+        type Output = Self;
+        fn sub(self, other: Self) -> Self {
+            Self(self.0 - other.0, self.1 - other.1)
+        }
+    }
+
+    impl Mul for DVec2 {
+        type Output = Self;
+        fn mul(self, other: Self) -> Self {
+            Self(self.0 * other.0, self.1 * other.1)
+        }
+    }
+
+    impl Mul<f64> for DVec2 {
+        type Output = Self;
+        fn mul(self, s: f64) -> Self {
+            Self(self.0 * s, self.1 * s)
+        }
+    }
+
+    impl Mul<DVec2> for f64 {
+        type Output = DVec2;
+        fn mul(self, v: DVec2) -> DVec2 {
+            v * self
+        }
+    }
+
+    impl Div for DVec2 {
+        type Output = Self;
+        fn div(self, other: Self) -> Self {
+            Self(self.0 / other.0, self.1 / other.1)
+        }
+    }
+
+    impl Div<f64> for DVec2 {
+        type Output = Self;
+        fn div(self, s: f64) -> Self {
+            Self(self.0 / s, self.1 / s)
+        }
+    }
+
+    impl Neg for DVec2 {
+        type Output = Self;
+        fn neg(self) -> Self {
+            Self(-self.0, -self.1)
+        }
+    }
+
+    impl AddAssign for DVec2 {
+        fn add_assign(&mut self, other: Self) {
+            *self = *self + other;
+        }
+    }
+
+    impl SubAssign for DVec2 {
+        fn sub_assign(&mut self, other: Self) {
+            *self = *self - other;
+        }
+    }
+
+    impl MulAssign<f64> for DVec2 {
+        fn mul_assign(&mut self, s: f64) {
+            *self = *self * s;
+        }
+    }
+
+    impl MulAssign for DVec2 {
+        fn mul_assign(&mut self, other: Self) {
+            *self = *self * other;
+        }
+    }
+
+    impl DivAssign<f64> for DVec2 {
+        fn div_assign(&mut self, s: f64) {
+            *self = *self / s;
+        }
+    }
+}
+
+#[cfg(feature = "f64")]
+pub mod dvec3 {
+    use serde::{Deserialize, Serialize};
+    use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+
+    /// Double precision counterpart of glam `Vec3`, serialized like it.
+    #[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(rename = "Vec3")]
+    pub struct DVec3(f64, f64, f64);
+
+    impl DVec3 {
+        pub fn new(x: f64, y: f64, z: f64) -> Self {
+            Self(x, y, z)
+        }
+
+        pub fn zero() -> Self {
+            Self(0f64, 0f64, 0f64)
+        }
+
+        pub fn one() -> Self {
+            Self(1f64, 1f64, 1f64)
+        }
+
+        pub fn splat(v: f64) -> Self {
+            Self(v, v, v)
+        }
+
+        pub fn x(self) -> f64 {
+            self.0
+        }
+
+        pub fn y(self) -> f64 {
+            self.1
+        }
+
+        pub fn z(self) -> f64 {
+            self.2
+        }
+
+        pub fn dot(self, other: Self) -> f64 {
+            self.0 * other.0 + self.1 * other.1 + self.2 * other.2
+        }
+
+        pub fn length_squared(self) -> f64 {
+            self.dot(self)
+        }
+
+        pub fn length(self) -> f64 {
+            self.length_squared().sqrt()
+        }
+
+        pub fn length_reciprocal(self) -> f64 {
+            1f64 / self.length()
+        }
+
+        pub fn normalize(self) -> Self {
+            self * self.length_reciprocal()
+        }
+
+        pub fn min_element(self) -> f64 {
+            self.0.min(self.1).min(self.2)
+        }
+
+        pub fn max_element(self) -> f64 {
+            self.0.max(self.1).max(self.2)
+        }
+
+        pub fn min(self, other: Self) -> Self {
+            Self(
+                self.0.min(other.0),
+                self.1.min(other.1),
+                self.2.min(other.2),
+            )
+        }
+
+        pub fn max(self, other: Self) -> Self {
+            Self(
+                self.0.max(other.0),
+                self.1.max(other.1),
+                self.2.max(other.2),
+            )
+        }
+
+        pub fn abs(self) -> Self {
+            Self(self.0.abs(), self.1.abs(), self.2.abs())
+        }
+    }
+
+    impl From<(f64, f64, f64)> for DVec3 {
+        fn from((x, y, z): (f64, f64, f64)) -> Self {
+            Self(x, y, z)
+        }
+    }
+
+    impl Add for DVec3 {
+        type Output = Self;
+        fn add(self, other: Self) -> Self {
+            Self(self.0 + other.0, self.1 + other.1, self.2 + other.2)
+        }
+    }
+
+    impl Sub for DVec3 {
+        type Output = Self;
+        fn sub(self, other: Self) -> Self {
+            Self(self.0 - other.0, self.1 - other.1, self.2 - other.2)
+        }
+    }
+
+    impl Mul for DVec3 {
+        type Output = Self;
+        fn mul(self, other: Self) -> Self {
+            Self(self.0 * other.0, self.1 * other.1, self.2 * other.2)
+        }
+    }
+
+    impl Mul<f64> for DVec3 {
+        type Output = Self;
+        fn mul(self, s: f64) -> Self {
+            Self(self.0 * s, self.1 * s, self.2 * s)
+        }
+    }
+
+    impl Mul<DVec3> for f64 {
+        type Output = DVec3;
+        fn mul(self, v: DVec3) -> DVec3 {
+            v * self
+        }
+    }
+
+    impl Div for DVec3 {
+        type Output = Self;
+        fn div(self, other: Self) -> Self {
+            Self(self.0 / other.0, self.1 / other.1, self.2 / other.2)
+        }
+    }
+
+    impl Div<f64> for DVec3 {
+        type Output = Self;
+        fn div(self, s: f64) -> Self {
+            Self(self.0 / s, self.1 / s, self.2 / s)
+        }
+    }
+
+    impl Neg for DVec3 {
+        type Output = Self;
+        fn neg(self) -> Self {
+            Self(-self.0, -self.1, -self.2)
+        }
+    }
+
+    impl AddAssign for DVec3 {
+        fn add_assign(&mut self, other: Self) {
+            *self = *self + other;
+        }
+    }
+
+    impl SubAssign for DVec3 {
+        fn sub_assign(&mut self, other: Self) {
+            *self = *self - other;
+        }
+    }
+
+    impl MulAssign<f64> for DVec3 {
+        fn mul_assign(&mut self, s: f64) {
+            *self = *self * s;
+        }
+    }
+
+    impl MulAssign for DVec3 {
+        fn mul_assign(&mut self, other: Self) {
+            *self = *self * other;
+        }
+    }
+}
@@ -0,0 +1,567 @@
+use crate::circles_app::circle::Circle;
+use crate::circles_app::compound::Compound;
+use crate::circles_app::filter::CollisionFilter;
+use crate::circles_app::material::Material;
+use crate::circles_app::obstacle::Obstacle;
+use crate::circles_app::scalar::{Scalar, Vector};
+use crate::circles_app::sensor::{Sensor, SensorShape};
+use crate::circles_app::simulation::Simulation;
+use crate::circles_app::sink::Sink;
+use crate::circles_app::units::Units;
+use crate::circles_app::world::{World, WorldError};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use slog::Logger;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+pub const SCENE_VERSION: u32 = 4;
+pub const SCENE_EXTENSION: &str = "ron";
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Format(ron::Error),
+    UnsupportedVersion(u32),
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io(e) => Some(e),
+            SceneError::Format(e) => Some(e),
+            SceneError::UnsupportedVersion(_) => None,
+        }
+    }
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            SceneError::Io(e) => Display::fmt(e, f),
+            SceneError::Format(e) => Display::fmt(e, f),
+            SceneError::UnsupportedVersion(v) => write!(
+                f,
+                "Scene version {} is newer than supported {}",
+                v, SCENE_VERSION
+            ),
+        }
+    }
+}
+
+impl From<std::io::Error> for SceneError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ron::Error> for SceneError {
+    fn from(e: ron::Error) -> Self {
+        Self::Format(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub version: u32,
+    /// Units of all quantities below. Scenes without them are in SI.
+    #[serde(default)]
+    pub units: Units,
+    pub field_size: (Scalar, Scalar),
+    #[serde(default)]
+    pub gravity: (Scalar, Scalar),
+    #[serde(default)]
+    pub circles: Vec<SceneCircle>,
+    #[serde(default)]
+    pub sensors: Vec<SceneRegion>,
+    #[serde(default)]
+    pub sinks: Vec<SceneRegion>,
+    #[serde(default)]
+    pub compounds: Vec<SceneCompound>,
+    #[serde(default)]
+    pub obstacles: Vec<SceneRegion>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneCircle {
+    pub center: (Scalar, Scalar),
+    pub radius: Scalar,
+    #[serde(default)]
+    pub speed: (Scalar, Scalar),
+    #[serde(default)]
+    pub material: SceneMaterial,
+    #[serde(default = "white")]
+    pub color: (f32, f32, f32, f32),
+    #[serde(default)]
+    pub species: u32,
+    #[serde(default)]
+    pub filter: SceneFilter,
+}
+
+impl From<&Circle> for SceneCircle {
+    fn from(circle: &Circle) -> Self {
+        Self {
+            center: (circle.center().x(), circle.center().y()),
+            radius: circle.radius(),
+            speed: (circle.speed().x(), circle.speed().y()),
+            material: circle.material().into(),
+            color: circle.color().into(),
+            species: circle.species(),
+            filter: circle.filter().into(),
+        }
+    }
+}
+
+impl From<&SceneCircle> for Circle {
+    fn from(c: &SceneCircle) -> Self {
+        let mut circle = Circle::new(c.center.into(), c.radius, c.speed.into());
+        circle.set_material(c.material.into());
+        circle.set_color(c.color.into());
+        circle.set_species(c.species);
+        circle.set_filter(c.filter.into());
+        circle
+    }
+}
+
+impl SceneCircle {
+    fn to_si(self, units: Units) -> Self {
+        Self {
+            center: scaled(self.center, units.length()),
+            radius: self.radius * units.length(),
+            speed: scaled(self.speed, units.speed()),
+            material: SceneMaterial {
+                density: self.material.density * units.density(),
+                ..self.material
+            },
+            ..self
+        }
+    }
+}
+
+/// Members are placed in the field as they are now. Their speeds are ignored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneCompound {
+    pub members: Vec<SceneCircle>,
+    #[serde(default)]
+    pub speed: (Scalar, Scalar),
+    /// Radians per unit of time.
+    #[serde(default)]
+    pub angular_speed: Scalar,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneMaterial {
+    pub density: Scalar,
+    pub restitution: Scalar,
+}
+
+impl Default for SceneMaterial {
+    fn default() -> Self {
+        Material::default().into()
+    }
+}
+
+impl From<Material> for SceneMaterial {
+    fn from(m: Material) -> Self {
+        Self {
+            density: m.density,
+            restitution: m.restitution,
+        }
+    }
+}
+
+impl From<SceneMaterial> for Material {
+    fn from(m: SceneMaterial) -> Self {
+        Material::new(m.density, m.restitution)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneFilter {
+    pub layer: u32,
+    pub mask: u32,
+}
+
+impl Default for SceneFilter {
+    fn default() -> Self {
+        CollisionFilter::default().into()
+    }
+}
+
+impl From<CollisionFilter> for SceneFilter {
+    fn from(f: CollisionFilter) -> Self {
+        Self {
+            layer: f.layer,
+            mask: f.mask,
+        }
+    }
+}
+
+impl From<SceneFilter> for CollisionFilter {
+    fn from(f: SceneFilter) -> Self {
+        CollisionFilter::new(f.layer, f.mask)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SceneShape {
+    Circle {
+        center: (Scalar, Scalar),
+        radius: Scalar,
+    },
+    Rect {
+        min: (Scalar, Scalar),
+        max: (Scalar, Scalar),
+    },
+}
+
+impl SceneShape {
+    fn scaled(self, k: Scalar) -> Self {
+        match self {
+            SceneShape::Circle { center, radius } => SceneShape::Circle {
+                center: scaled(center, k),
+                radius: radius * k,
+            },
+            SceneShape::Rect { min, max } => SceneShape::Rect {
+                min: scaled(min, k),
+                max: scaled(max, k),
+            },
+        }
+    }
+}
+
+impl From<SensorShape> for SceneShape {
+    fn from(shape: SensorShape) -> Self {
+        match shape {
+            SensorShape::Circle { center, radius } => SceneShape::Circle {
+                center: (center.x(), center.y()),
+                radius,
+            },
+            SensorShape::Rect { min, max } => SceneShape::Rect {
+                min: (min.x(), min.y()),
+                max: (max.x(), max.y()),
+            },
+        }
+    }
+}
+
+impl From<SceneShape> for SensorShape {
+    fn from(shape: SceneShape) -> Self {
+        match shape {
+            SceneShape::Circle { center, radius } => SensorShape::Circle {
+                center: center.into(),
+                radius,
+            },
+            SceneShape::Rect { min, max } => SensorShape::Rect {
+                min: min.into(),
+                max: max.into(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneRegion {
+    pub shape: SceneShape,
+    #[serde(default)]
+    pub filter: SceneFilter,
+}
+
+fn scaled((x, y): (Scalar, Scalar), k: Scalar) -> (Scalar, Scalar) {
+    (x * k, y * k)
+}
+
+fn white() -> (f32, f32, f32, f32) {
+    (1f32, 1f32, 1f32, 1f32)
+}
+
+impl Scene {
+    pub fn new(field_size: (Scalar, Scalar)) -> Self {
+        Self {
+            version: SCENE_VERSION,
+            units: Units::SI,
+            field_size,
+            gravity: (0.0, 0.0),
+            circles: Vec::new(),
+            sensors: Vec::new(),
+            sinks: Vec::new(),
+            compounds: Vec::new(),
+            obstacles: Vec::new(),
+        }
+    }
+
+    pub fn from_simulation(simulation: &Simulation) -> Self {
+        let field_size = simulation.field_size();
+        let gravity = simulation.gravity();
+        let circles = simulation
+            .circles()
+            .map(|(_, circle)| circle.into())
+            .collect();
+        let sensors = simulation
+            .sensors()
+            .map(|(_, sensor)| SceneRegion {
+                shape: sensor.shape().into(),
+                filter: sensor.filter().into(),
+            })
+            .collect();
+        let sinks = simulation
+            .sinks()
+            .map(|(_, sink)| SceneRegion {
+                shape: sink.shape().into(),
+                filter: sink.filter().into(),
+            })
+            .collect();
+        let obstacles = simulation
+            .obstacles()
+            .map(|(_, obstacle)| SceneRegion {
+                shape: obstacle.shape().into(),
+                filter: obstacle.filter().into(),
+            })
+            .collect();
+        let compounds = simulation
+            .compounds()
+            .map(|(_, compound)| SceneCompound {
+                members: compound.members().map(|c| (&c).into()).collect(),
+                speed: (compound.speed().x(), compound.speed().y()),
+                angular_speed: compound.angular_speed(),
+            })
+            .collect();
+        Self {
+            version: SCENE_VERSION,
+            units: Units::SI,
+            field_size: (field_size.x(), field_size.y()),
+            gravity: (gravity.x(), gravity.y()),
+            circles,
+            sensors,
+            sinks,
+            compounds,
+            obstacles,
+        }
+    }
+
+    /// The same scene with quantities converted into SI units.
+    pub fn to_si(&self) -> Self {
+        let units = self.units;
+        let length = units.length();
+        let region = |region: &SceneRegion| SceneRegion {
+            shape: region.shape.scaled(length),
+            ..*region
+        };
+        Self {
+            version: self.version,
+            units: Units::SI,
+            field_size: scaled(self.field_size, length),
+            gravity: scaled(self.gravity, units.acceleration()),
+            circles: self.circles.iter().map(|c| c.to_si(units)).collect(),
+            sensors: self.sensors.iter().map(region).collect(),
+            sinks: self.sinks.iter().map(region).collect(),
+            compounds: self
+                .compounds
+                .iter()
+                .map(|c| SceneCompound {
+                    members: c.members.iter().map(|m| m.to_si(units)).collect(),
+                    speed: scaled(c.speed, units.speed()),
+                    angular_speed: c.angular_speed * units.angular_speed(),
+                })
+                .collect(),
+            obstacles: self.obstacles.iter().map(region).collect(),
+        }
+    }
+
+    pub fn to_simulation(&self, logger: Logger) -> Simulation {
+        if self.units != Units::SI {
+            return self.to_si().to_simulation(logger);
+        }
+        let mut simulation = Simulation::new(self.field_size.into(), logger);
+        simulation.set_gravity(self.gravity.into());
+        for c in &self.circles {
+            simulation.add_circle(c.into());
+        }
+        for c in self.compounds.iter().filter(|c| !c.members.is_empty()) {
+            let mut compound = Compound::new(c.members.iter().map(Circle::from).collect());
+            compound.set_speed(c.speed.into());
+            compound.set_angular_speed(c.angular_speed);
+            simulation.add_compound(compound);
+        }
+        for region in &self.sensors {
+            simulation.add_sensor(Sensor::new(region.shape.into(), region.filter.into()));
+        }
+        for region in &self.sinks {
+            simulation.add_sink(Sink::new(region.shape.into(), region.filter.into()));
+        }
+        for region in &self.obstacles {
+            simulation.add_obstacle(Obstacle::new(region.shape.into(), region.filter.into()));
+        }
+        simulation
+    }
+
+    /// Circles and gravity of the scene in an unbounded world, focused on the scene field.
+    /// Everything else of the scene has no counterpart in a world and is left out.
+    pub fn to_world(&self, chunk_size: Scalar, logger: Logger) -> Result<World, WorldError> {
+        if self.units != Units::SI {
+            return self.to_si().to_world(chunk_size, logger);
+        }
+        let skipped = self.compounds.len() + self.sensors.len() + self.sinks.len();
+        if skipped + self.obstacles.len() > 0 {
+            warn!(
+                logger,
+                "World leaves out compounds, sensors, sinks and obstacles of the scene"
+            );
+        }
+        let mut world = World::new(chunk_size, logger);
+        world.set_gravity(self.gravity.into());
+        for c in &self.circles {
+            world.add_circle(c.into())?;
+        }
+        let field_size = Vector::from(self.field_size);
+        world.set_focus(&[field_size / 2.0], field_size.max_element() / 2.0);
+        Ok(world)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, SceneError> {
+        let scene: Self = ron::from_str(text)?;
+        if scene.version > SCENE_VERSION {
+            return Err(SceneError::UnsupportedVersion(scene.version));
+        }
+        Ok(scene)
+    }
+
+    pub fn to_ron(&self) -> Result<String, SceneError> {
+        Ok(ron::ser::to_string_pretty(self, PrettyConfig::new())?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SceneError> {
+        Self::from_ron(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SceneError> {
+        Ok(std::fs::write(path, self.to_ron()?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scene, SceneError, SCENE_VERSION};
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::compound::Compound;
+    use crate::circles_app::filter::CollisionFilter;
+    use crate::circles_app::material::Material;
+    use crate::circles_app::obstacle::Obstacle;
+    use crate::circles_app::scalar::consts::PI;
+    use crate::circles_app::sensor::{Sensor, SensorShape};
+    use crate::circles_app::simulation::Simulation;
+    use assert_approx_eq::assert_approx_eq;
+    use slog::{Discard, Logger};
+
+    #[test]
+    fn round_trip() {
+        let mut sim = Simulation::new((200.0, 100.0).into(), Logger::root(Discard, o!()));
+        sim.set_gravity((0.0, 9.81).into());
+        let mut circle = Circle::new((10.0, 20.0).into(), 3.0, (1.0, -2.0).into());
+        circle.set_material(Material::new(2.0, 0.9));
+        circle.set_color((1f32, 0f32, 0f32, 1f32).into());
+        circle.set_species(4);
+        sim.add_circle(circle);
+        let shape = SensorShape::Rect {
+            min: (0.0, 0.0).into(),
+            max: (10.0, 10.0).into(),
+        };
+        sim.add_sensor(Sensor::new(shape, CollisionFilter::new(2, 3)));
+        let mut gear = Compound::gear((100.0, 50.0).into(), 10.0, 6, 2.0);
+        gear.set_speed((3.0, 0.0).into());
+        gear.set_angular_speed(0.5);
+        sim.add_compound(gear.clone());
+        let wall = Obstacle::new(
+            SensorShape::Rect {
+                min: (99.0, 0.0).into(),
+                max: (101.0, 30.0).into(),
+            },
+            CollisionFilter::new(4, 5),
+        );
+        sim.add_obstacle(wall);
+
+        let scene = Scene::from_simulation(&sim);
+        let text = scene.to_ron().unwrap();
+        let loaded = Scene::from_ron(&text).unwrap();
+        assert_eq!(loaded, scene);
+
+        let restored = loaded.to_simulation(Logger::root(Discard, o!()));
+        assert_eq!(restored.circles().next().unwrap().1, &circle);
+        assert_eq!(restored.gravity(), (0.0, 9.81).into());
+        assert_eq!(restored.sensors().count(), 1);
+        assert_eq!(restored.obstacles().next().unwrap().1, &wall);
+        let (_, restored_gear) = restored.compounds().next().unwrap();
+        assert_approx_eq!(restored_gear.mass(), gear.mass());
+        assert_approx_eq!(restored_gear.inertia(), gear.inertia(), 1e-2);
+        assert_eq!(restored_gear.speed(), gear.speed());
+        assert_eq!(restored_gear.angular_speed(), gear.angular_speed());
+    }
+
+    #[test]
+    fn compound_spin_is_converted_to_si() {
+        let text = "(
+            version: 3,
+            units: (length: Centimetre, mass: Gram, time: Millisecond),
+            field_size: (200, 100),
+            compounds: [(
+                members: [(center: (40, 50), radius: 10), (center: (60, 50), radius: 10)],
+                speed: (0.1, 0),
+                angular_speed: 0.002,
+            )],
+        )";
+        let scene = Scene::from_ron(text).unwrap();
+        let loaded = Scene::from_ron(&scene.to_ron().unwrap()).unwrap();
+        assert_eq!(loaded, scene);
+
+        let sim = loaded.to_simulation(Logger::root(Discard, o!()));
+        let (_, compound) = sim.compounds().next().unwrap();
+        assert_approx_eq!(compound.center().x(), 0.5);
+        assert_approx_eq!(compound.speed().x(), 1.0, 1e-4);
+        assert_approx_eq!(compound.angular_speed(), 2.0, 1e-4);
+
+        let restored = Scene::from_simulation(&sim).to_simulation(Logger::root(Discard, o!()));
+        let (_, compound) = restored.compounds().next().unwrap();
+        assert_approx_eq!(compound.angular_speed(), 2.0, 1e-4);
+    }
+
+    #[test]
+    fn minimal_scene_uses_defaults() {
+        let text = "(version: 1, field_size: (10, 10), circles: [(center: (5, 5), radius: 1)])";
+        let scene = Scene::from_ron(text).unwrap();
+        let sim = scene.to_simulation(Logger::root(Discard, o!()));
+        let (_, circle) = sim.circles().next().unwrap();
+        assert_eq!(circle.material(), Material::default());
+        assert_eq!(circle.speed(), (0.0, 0.0).into());
+    }
+
+    #[test]
+    fn units_are_converted_to_si() {
+        let text = "(
+            version: 2,
+            units: (length: Centimetre, mass: Gram, time: Second),
+            field_size: (200, 100),
+            gravity: (0, 981),
+            circles: [(center: (50, 50), radius: 10, speed: (100, 0), material: (density: 1, restitution: 1))],
+        )";
+        let sim = Scene::from_ron(text)
+            .unwrap()
+            .to_simulation(Logger::root(Discard, o!()));
+        assert_approx_eq!(sim.field_size().x(), 2.0);
+        assert_approx_eq!(sim.gravity().y(), 9.81);
+        let (_, circle) = sim.circles().next().unwrap();
+        assert_approx_eq!(circle.radius(), 0.1);
+        assert_approx_eq!(circle.speed().x(), 1.0);
+        // 1 g/cm² is 10 kg/m², so mass is the same whichever units it is computed in.
+        assert_approx_eq!(circle.mass(), 1e-3 * PI * 100.0, 1e-6);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let text = format!("(version: {}, field_size: (10, 10))", SCENE_VERSION + 1);
+        match Scene::from_ron(&text) {
+            Err(SceneError::UnsupportedVersion(v)) => assert_eq!(v, SCENE_VERSION + 1),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use crate::circles_app::circle::Circle;
+use crate::circles_app::filter::CollisionFilter;
+use crate::circles_app::scalar::{Scalar, Vector};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SensorShape {
+    Circle { center: Vector, radius: Scalar },
+    Rect { min: Vector, max: Vector },
+}
+
+impl SensorShape {
+    pub fn overlaps(&self, circle: &Circle) -> bool {
+        match *self {
+            SensorShape::Circle { center, radius } => {
+                (circle.center() - center).length() < radius + circle.radius()
+            }
+            SensorShape::Rect { min, max } => {
+                let closest = circle.center().max(min).min(max);
+                (circle.center() - closest).length() < circle.radius()
+            }
+        }
+    }
+}
+
+/// Non-solid region. Reports circles entering and leaving it without affecting their motion.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sensor {
+    shape: SensorShape,
+    filter: CollisionFilter,
+}
+
+impl Sensor {
+    pub fn new(shape: SensorShape, filter: CollisionFilter) -> Self {
+        Self { shape, filter }
+    }
+
+    pub fn shape(&self) -> SensorShape {
+        self.shape
+    }
+
+    pub fn filter(&self) -> CollisionFilter {
+        self.filter
+    }
+
+    pub fn overlaps(&self, circle: &Circle) -> bool {
+        self.filter.interacts(circle.filter()) && self.shape.overlaps(circle)
+    }
+}
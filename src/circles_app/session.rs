@@ -0,0 +1,272 @@
+use crate::circles_app::command::Command;
+use crate::circles_app::replay::{
+    self, Frame, Replay, ReplayError, ReplayPlayer, ReplayRecorder, DEFAULT_KEYFRAME_INTERVAL,
+};
+use crate::circles_app::rewind::{self, Rewind};
+use crate::circles_app::scene::{Scene, SceneError};
+use crate::circles_app::simulation::{Simulation, SimulationState};
+use crate::circles_app::snapshot::{self, SnapshotError};
+use slog::Logger;
+use std::path::Path;
+use std::time::Duration;
+
+/// Simulation with its timeline: pause, rewind, replay recording and playback.
+/// Everything the windowed app does to a simulation apart from showing it.
+pub struct Session {
+    simulation: Simulation,
+    recorder: Option<ReplayRecorder>,
+    player: Option<ReplayPlayer>,
+    rewind: Rewind,
+    paused: bool,
+    logger: Logger,
+}
+
+impl Session {
+    pub fn new(simulation: Simulation, logger: Logger) -> Self {
+        Self {
+            simulation,
+            recorder: None,
+            player: None,
+            rewind: Rewind::new(rewind::DEFAULT_BUDGET, rewind::DEFAULT_KEYFRAME_INTERVAL),
+            paused: false,
+            logger,
+        }
+    }
+
+    pub fn simulation(&self) -> &Simulation {
+        &self.simulation
+    }
+
+    /// Changes made here bypass recording. Use `apply_command` for those that should be replayed.
+    pub fn simulation_mut(&mut self) -> &mut Simulation {
+        &mut self.simulation
+    }
+
+    /// All changes of a running simulation go through here, so they can be recorded.
+    pub fn apply_command(&mut self, command: Command) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record_command(command.clone());
+        }
+        self.simulation.apply(command);
+    }
+
+    /// Starts over from `state`. Playback stops and rewound states of the previous simulation
+    /// are forgotten, so scrubbing can't bring them back.
+    pub fn replace_state(&mut self, state: SimulationState) {
+        self.stop_playback();
+        self.rewind.clear();
+        self.apply_command(Command::ReplaceState(Box::new(state)));
+    }
+
+    pub fn load_scene(&mut self, path: &Path) -> Result<(), SceneError> {
+        let scene = Scene::load(path)?;
+        let simulation = scene.to_simulation(self.logger.clone());
+        self.replace_state(simulation.state().clone());
+        info!(self.logger, "Scene loaded from {:?}", path);
+        Ok(())
+    }
+
+    pub fn save_scene(&self, path: &Path) -> Result<(), SceneError> {
+        Scene::from_simulation(&self.simulation).save(path)?;
+        info!(self.logger, "Scene saved to {:?}", path);
+        Ok(())
+    }
+
+    pub fn save_snapshot(&self, path: &Path) -> Result<(), SnapshotError> {
+        snapshot::save(&self.simulation, path)?;
+        info!(self.logger, "Snapshot saved to {:?}", path);
+        Ok(())
+    }
+
+    pub fn load_snapshot(&mut self, path: &Path) -> Result<(), SnapshotError> {
+        let simulation = snapshot::load(path, self.logger.clone())?;
+        self.replace_state(simulation.state().clone());
+        info!(
+            self.logger,
+            "Snapshot loaded from {:?} at step {}",
+            path,
+            self.simulation.steps()
+        );
+        Ok(())
+    }
+
+    /// States kept for rewinding are limited by `budget` bytes.
+    pub fn set_rewind_budget(&mut self, budget: usize) {
+        self.rewind = Rewind::new(budget, rewind::DEFAULT_KEYFRAME_INTERVAL);
+    }
+
+    /// Steps the simulation and remembers the new state for rewinding and recording.
+    pub fn step(&mut self, dt: Duration) {
+        self.simulation.step(dt);
+        self.rewind.push(self.simulation.state());
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record_step(dt, &self.simulation);
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        info!(self.logger, "Paused at step {}", self.simulation.steps());
+    }
+
+    /// Resuming from a rewound state branches the timeline: states after it are forgotten.
+    pub fn resume(&mut self) {
+        if self.rewind.is_scrubbing() {
+            self.rewind.branch();
+            if let Some(recorder) = &mut self.recorder {
+                let state = Box::new(self.simulation.state().clone());
+                recorder.record_command(Command::ReplaceState(state));
+            }
+            info!(
+                self.logger,
+                "Timeline branched at step {}",
+                self.simulation.steps()
+            );
+        }
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.paused {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Moves through recent states while paused. Returns the step of the restored state,
+    /// `None` when there is nothing to rewind to.
+    pub fn scrub(&mut self, offset: isize) -> Option<u64> {
+        if !self.paused {
+            self.pause();
+        }
+        let state = self.rewind.scrub(offset)?;
+        self.simulation.set_state(state);
+        debug!(self.logger, "Rewound to step {}", self.simulation.steps());
+        Some(self.simulation.steps())
+    }
+
+    pub fn start_recording(&mut self) {
+        self.stop_playback();
+        self.recorder = Some(ReplayRecorder::new(
+            &self.simulation,
+            DEFAULT_KEYFRAME_INTERVAL,
+        ));
+        info!(self.logger, "Replay recording started");
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Replay> {
+        let replay = self.recorder.take()?.finish();
+        info!(self.logger, "Replay recorded: {} steps", replay.len());
+        Some(replay)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Replaces current simulation with the replay's initial state and plays it back.
+    pub fn start_playback(&mut self, replay: Replay) {
+        if self.recorder.take().is_some() {
+            warn!(self.logger, "Replay recording discarded by playback");
+        }
+        self.replace_state(
+            replay
+                .initial_simulation(self.logger.clone())
+                .state()
+                .clone(),
+        );
+        info!(
+            self.logger,
+            "Playing replay: {} steps, {:?}",
+            replay.len(),
+            replay.duration()
+        );
+        self.player = Some(ReplayPlayer::new(replay));
+    }
+
+    /// Simulation continues live from the current playback position.
+    pub fn stop_playback(&mut self) {
+        if let Some(player) = self.player.take() {
+            info!(
+                self.logger,
+                "Playback stopped at step {}",
+                player.position()
+            );
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.player.is_some()
+    }
+
+    pub fn load_replay(&mut self, path: &Path) -> Result<(), ReplayError> {
+        let replay = replay::load(path)?;
+        info!(self.logger, "Replay loaded from {:?}", path);
+        self.start_playback(replay);
+        Ok(())
+    }
+
+    /// Frames of the playback due after `wall_elapsed`, `None` when not playing.
+    pub fn due_frames(&mut self, wall_elapsed: Duration) -> Option<Vec<Frame>> {
+        self.player
+            .as_mut()
+            .map(|player| player.due_frames(wall_elapsed))
+    }
+
+    pub fn seek_playback(&mut self, offset: isize) {
+        if let Some(player) = &mut self.player {
+            let target = (player.position() as isize + offset).max(0) as usize;
+            player.seek(target, &mut self.simulation);
+            self.simulation.events().drain();
+            debug!(self.logger, "Playback position: {}", player.position());
+        }
+    }
+
+    pub fn scale_playback_speed(&mut self, factor: f32) {
+        if let Some(player) = &mut self.player {
+            player.set_speed(player.speed() * factor);
+            info!(self.logger, "Playback speed: {}", player.speed());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Session;
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::scene::Scene;
+    use crate::circles_app::simulation::Simulation;
+    use slog::{Discard, Logger};
+    use std::time::Duration;
+
+    fn logger() -> Logger {
+        Logger::root(Discard, o!())
+    }
+
+    #[test]
+    fn loading_scene_forgets_rewound_states() {
+        let mut simulation = Simulation::new((100.0, 100.0).into(), logger());
+        simulation.add_circle(Circle::new((50.0, 50.0).into(), 5.0, (10.0, 0.0).into()));
+        let mut session = Session::new(simulation, logger());
+        for _ in 0..20 {
+            session.step(Duration::from_millis(10));
+        }
+        assert!(session.scrub(-1).is_some());
+        session.resume();
+
+        let path = std::env::temp_dir().join(format!("circles-session-{}.ron", std::process::id()));
+        Scene::new((30.0, 30.0)).save(&path).unwrap();
+        let loaded = session.load_scene(&path);
+        std::fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+
+        assert_eq!(session.scrub(-1), None);
+        assert_eq!(session.simulation().field_size(), (30.0, 30.0).into());
+        assert_eq!(session.simulation().population(), 0);
+    }
+}
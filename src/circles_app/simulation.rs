@@ -0,0 +1,1297 @@
+use crate::circles_app::broad_phase;
+use crate::circles_app::circle::Circle;
+use crate::circles_app::command::Command;
+use crate::circles_app::compound::Compound;
+use crate::circles_app::emitter::Emitter;
+use crate::circles_app::events::{
+    CollisionEvent, CollisionEvents, Contact, ObstacleHit, RemoveCause, WallHit, WallSide,
+};
+use crate::circles_app::growth::Growth;
+use crate::circles_app::handle::{
+    CircleHandle, CompoundHandle, EmitterHandle, ObstacleHandle, SensorHandle, SinkHandle,
+};
+use crate::circles_app::obstacle::Obstacle;
+use crate::circles_app::scalar::consts::PI;
+use crate::circles_app::scalar::{seconds, Scalar, Vector};
+use crate::circles_app::sensor::Sensor;
+use crate::circles_app::sink::Sink;
+use crate::circles_app::stats::{Observables, PressureGauge, SpeedHistogram};
+use crate::circles_app::thermostat::{self, Thermostat};
+use rand::SeedableRng;
+use rand_pcg::Pcg64Mcg;
+use serde::{Deserialize, Serialize};
+use slog::Logger;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+const OVERLAP_ITERATIONS: usize = 8;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct GrowthState {
+    growth: Growth,
+    initial_radius: Scalar,
+    started: Duration,
+}
+
+/// Everything that determines how simulation continues. Cloned and serialized by snapshots.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SimulationState {
+    core: CoreState,
+    compounds: CompoundState,
+    obstacles: ObstacleState,
+}
+
+impl SimulationState {
+    pub(crate) fn from_parts(
+        core: CoreState,
+        compounds: CompoundState,
+        obstacles: ObstacleState,
+    ) -> Self {
+        Self {
+            core,
+            compounds,
+            obstacles,
+        }
+    }
+
+    pub(crate) fn core(&self) -> &CoreState {
+        &self.core
+    }
+
+    pub(crate) fn compounds(&self) -> &CompoundState {
+        &self.compounds
+    }
+
+    pub(crate) fn obstacles(&self) -> &ObstacleState {
+        &self.obstacles
+    }
+}
+
+/// Part of the state stored in the state section of snapshots. Its layout is fixed by snapshot
+/// format version 1, later additions go into their own parts and sections.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct CoreState {
+    circles: Vec<Circle>,
+    handles: Vec<CircleHandle>,
+    next_handle: u64,
+    field_size: Vector,
+    contacts: BTreeSet<(CircleHandle, CircleHandle)>,
+    sensors: Vec<(SensorHandle, Sensor)>,
+    next_sensor_handle: u64,
+    sensor_overlaps: BTreeSet<(SensorHandle, CircleHandle)>,
+    emitters: Vec<(EmitterHandle, Emitter)>,
+    next_emitter_handle: u64,
+    sinks: Vec<(SinkHandle, Sink)>,
+    next_sink_handle: u64,
+    max_population: Option<usize>,
+    growth: BTreeMap<CircleHandle, GrowthState>,
+    radii_changed: bool,
+    time: Duration,
+    steps: u64,
+    thermostat: Option<Thermostat>,
+    gravity: Vector,
+    pressure: PressureGauge,
+    rng: Pcg64Mcg,
+}
+
+/// Stored in the compounds section of snapshots.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct CompoundState {
+    list: Vec<(CompoundHandle, Compound)>,
+    next_handle: u64,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct ObstacleState {
+    list: Vec<(ObstacleHandle, Obstacle)>,
+    next_handle: u64,
+}
+
+pub struct Simulation {
+    state: SimulationState,
+    events: CollisionEvents,
+    logger: Logger,
+}
+
+impl Simulation {
+    pub fn new(field_size: Vector, logger: Logger) -> Self {
+        let core = CoreState {
+            circles: Vec::new(),
+            handles: Vec::new(),
+            next_handle: 0,
+            field_size,
+            contacts: BTreeSet::new(),
+            sensors: Vec::new(),
+            next_sensor_handle: 0,
+            sensor_overlaps: BTreeSet::new(),
+            emitters: Vec::new(),
+            next_emitter_handle: 0,
+            sinks: Vec::new(),
+            next_sink_handle: 0,
+            max_population: None,
+            growth: BTreeMap::new(),
+            radii_changed: false,
+            time: Duration::from_secs(0),
+            steps: 0,
+            thermostat: None,
+            gravity: Vector::zero(),
+            pressure: PressureGauge::new(Duration::from_secs(1)),
+            rng: Pcg64Mcg::seed_from_u64(0),
+        };
+        let state =
+            SimulationState::from_parts(core, CompoundState::default(), ObstacleState::default());
+        Self::from_state(state, logger)
+    }
+
+    /// Event subscriptions are not part of the state and start empty.
+    pub fn from_state(state: SimulationState, logger: Logger) -> Self {
+        Self {
+            state,
+            events: CollisionEvents::default(),
+            logger,
+        }
+    }
+
+    pub fn state(&self) -> &SimulationState {
+        &self.state
+    }
+
+    pub fn set_state(&mut self, state: SimulationState) {
+        self.state = state;
+    }
+
+    pub fn apply(&mut self, command: Command) {
+        match command {
+            Command::AddCircle(circle) => {
+                self.add_circle(circle);
+            }
+            Command::RemoveCircle(handle) => {
+                self.remove_circle(handle);
+            }
+            Command::SetRadius(handle, radius) => self.set_radius(handle, radius),
+            Command::SetGrowth(handle, growth) => self.set_growth(handle, growth),
+            Command::AddSensor(sensor) => {
+                self.add_sensor(sensor);
+            }
+            Command::RemoveSensor(handle) => {
+                self.remove_sensor(handle);
+            }
+            Command::AddEmitter(emitter) => {
+                self.add_emitter(emitter);
+            }
+            Command::RemoveEmitter(handle) => {
+                self.remove_emitter(handle);
+            }
+            Command::AddSink(sink) => {
+                self.add_sink(sink);
+            }
+            Command::RemoveSink(handle) => {
+                self.remove_sink(handle);
+            }
+            Command::SetMaxPopulation(max) => self.set_max_population(max),
+            Command::SetGravity(gravity) => self.set_gravity(gravity),
+            Command::SetThermostat(thermostat) => self.set_thermostat(thermostat),
+            Command::SetSeed(seed) => self.set_seed(seed),
+            Command::ReplaceState(state) => self.set_state(*state),
+            Command::AddCompound(compound) => {
+                self.add_compound(compound);
+            }
+            Command::RemoveCompound(handle) => {
+                self.remove_compound(handle);
+            }
+            Command::AddObstacle(obstacle) => {
+                self.add_obstacle(obstacle);
+            }
+            Command::RemoveObstacle(handle) => {
+                self.remove_obstacle(handle);
+            }
+        }
+    }
+
+    pub fn add_circle(&mut self, circle: Circle) -> CircleHandle {
+        let handle = CircleHandle::new(self.state.core.next_handle);
+        self.state.core.next_handle += 1;
+        self.state.core.circles.push(circle);
+        self.state.core.handles.push(handle);
+        handle
+    }
+
+    pub fn remove_circle(&mut self, handle: CircleHandle) -> Option<Circle> {
+        let index = self.index_of(handle)?;
+        Some(self.remove_at(index, RemoveCause::Manual))
+    }
+
+    /// Sets radius immediately. Overlaps it creates are resolved on the next step.
+    pub fn set_radius(&mut self, handle: CircleHandle, radius: Scalar) {
+        if let Some(index) = self.index_of(handle) {
+            self.state.core.circles[index].set_radius(radius);
+            self.state.core.radii_changed = true;
+        }
+    }
+
+    pub fn set_growth(&mut self, handle: CircleHandle, growth: Option<Growth>) {
+        let index = match self.index_of(handle) {
+            Some(index) => index,
+            None => return,
+        };
+        match growth {
+            Some(growth) => {
+                let state = GrowthState {
+                    growth,
+                    initial_radius: self.state.core.circles[index].radius(),
+                    started: self.state.core.time,
+                };
+                self.state.core.growth.insert(handle, state);
+            }
+            None => {
+                self.state.core.growth.remove(&handle);
+            }
+        }
+    }
+
+    pub fn circle(&self, handle: CircleHandle) -> Option<&Circle> {
+        self.index_of(handle).map(|i| &self.state.core.circles[i])
+    }
+
+    pub fn circles(&self) -> impl Iterator<Item = (CircleHandle, &Circle)> {
+        self.state
+            .core
+            .handles
+            .iter()
+            .copied()
+            .zip(self.state.core.circles.iter())
+    }
+
+    pub fn add_sensor(&mut self, sensor: Sensor) -> SensorHandle {
+        let handle = SensorHandle::new(self.state.core.next_sensor_handle);
+        self.state.core.next_sensor_handle += 1;
+        self.state.core.sensors.push((handle, sensor));
+        handle
+    }
+
+    /// Removes sensor silently: no exit events are reported for circles inside it.
+    pub fn remove_sensor(&mut self, handle: SensorHandle) -> Option<Sensor> {
+        let index = self
+            .state
+            .core
+            .sensors
+            .iter()
+            .position(|(h, _)| *h == handle)?;
+        self.state
+            .core
+            .sensor_overlaps
+            .retain(|(sensor, _)| *sensor != handle);
+        Some(self.state.core.sensors.remove(index).1)
+    }
+
+    pub fn sensors(&self) -> impl Iterator<Item = (SensorHandle, &Sensor)> {
+        self.state
+            .core
+            .sensors
+            .iter()
+            .map(|(handle, sensor)| (*handle, sensor))
+    }
+
+    pub fn add_emitter(&mut self, emitter: Emitter) -> EmitterHandle {
+        let handle = EmitterHandle::new(self.state.core.next_emitter_handle);
+        self.state.core.next_emitter_handle += 1;
+        self.state.core.emitters.push((handle, emitter));
+        handle
+    }
+
+    pub fn remove_emitter(&mut self, handle: EmitterHandle) -> Option<Emitter> {
+        let index = self
+            .state
+            .core
+            .emitters
+            .iter()
+            .position(|(h, _)| *h == handle)?;
+        Some(self.state.core.emitters.remove(index).1)
+    }
+
+    pub fn emitter_mut(&mut self, handle: EmitterHandle) -> Option<&mut Emitter> {
+        self.state
+            .core
+            .emitters
+            .iter_mut()
+            .find(|(h, _)| *h == handle)
+            .map(|(_, emitter)| emitter)
+    }
+
+    pub fn add_sink(&mut self, sink: Sink) -> SinkHandle {
+        let handle = SinkHandle::new(self.state.core.next_sink_handle);
+        self.state.core.next_sink_handle += 1;
+        self.state.core.sinks.push((handle, sink));
+        handle
+    }
+
+    pub fn sinks(&self) -> impl Iterator<Item = (SinkHandle, &Sink)> {
+        self.state
+            .core
+            .sinks
+            .iter()
+            .map(|(handle, sink)| (*handle, sink))
+    }
+
+    pub fn remove_sink(&mut self, handle: SinkHandle) -> Option<Sink> {
+        let index = self
+            .state
+            .core
+            .sinks
+            .iter()
+            .position(|(h, _)| *h == handle)?;
+        Some(self.state.core.sinks.remove(index).1)
+    }
+
+    /// Compounds collide with walls, circles and each other, and feel gravity. They aren't
+    /// thermostatted, sensed or sunk, and report no collision events. Observables and wall
+    /// pressure cover plain circles only. Overlap resolution and emitters treat compounds
+    /// as fixed: circles are pushed out of them and spawns wait for them to move away.
+    pub fn add_compound(&mut self, compound: Compound) -> CompoundHandle {
+        let handle = CompoundHandle::new(self.state.compounds.next_handle);
+        self.state.compounds.next_handle += 1;
+        self.state.compounds.list.push((handle, compound));
+        handle
+    }
+
+    pub fn remove_compound(&mut self, handle: CompoundHandle) -> Option<Compound> {
+        let index = self
+            .state
+            .compounds
+            .list
+            .iter()
+            .position(|(h, _)| *h == handle)?;
+        Some(self.state.compounds.list.remove(index).1)
+    }
+
+    pub fn compound(&self, handle: CompoundHandle) -> Option<&Compound> {
+        self.state
+            .compounds
+            .list
+            .iter()
+            .find(|(h, _)| *h == handle)
+            .map(|(_, compound)| compound)
+    }
+
+    pub fn compounds(&self) -> impl Iterator<Item = (CompoundHandle, &Compound)> {
+        self.state
+            .compounds
+            .list
+            .iter()
+            .map(|(handle, compound)| (*handle, compound))
+    }
+
+    pub fn add_obstacle(&mut self, obstacle: Obstacle) -> ObstacleHandle {
+        let handle = ObstacleHandle::new(self.state.obstacles.next_handle);
+        self.state.obstacles.next_handle += 1;
+        self.state.obstacles.list.push((handle, obstacle));
+        handle
+    }
+
+    pub fn remove_obstacle(&mut self, handle: ObstacleHandle) -> Option<Obstacle> {
+        let index = self
+            .state
+            .obstacles
+            .list
+            .iter()
+            .position(|(h, _)| *h == handle)?;
+        Some(self.state.obstacles.list.remove(index).1)
+    }
+
+    pub fn obstacles(&self) -> impl Iterator<Item = (ObstacleHandle, &Obstacle)> {
+        self.state
+            .obstacles
+            .list
+            .iter()
+            .map(|(handle, obstacle)| (*handle, obstacle))
+    }
+
+    /// Emitters stop spawning while circles count is at the cap.
+    pub fn set_max_population(&mut self, max_population: Option<usize>) {
+        self.state.core.max_population = max_population;
+    }
+
+    pub fn population(&self) -> usize {
+        self.state.core.circles.len()
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.state.core.rng = Pcg64Mcg::seed_from_u64(seed);
+    }
+
+    /// FNV-1a of the encoded state. Stable across runs and platforms with IEEE floats.
+    pub fn state_hash(&self) -> u64 {
+        let bytes = bincode::serialize(&self.state).expect("Can't encode simulation state");
+        hash_bytes(&bytes)
+    }
+
+    pub fn field_size(&self) -> Vector {
+        self.state.core.field_size
+    }
+
+    pub fn time(&self) -> Duration {
+        self.state.core.time
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.state.core.steps
+    }
+
+    pub fn gravity(&self) -> Vector {
+        self.state.core.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector) {
+        self.state.core.gravity = gravity;
+    }
+
+    pub fn set_thermostat(&mut self, thermostat: Option<Thermostat>) {
+        debug!(self.logger, "Thermostat set: {:?}", thermostat);
+        self.state.core.thermostat = thermostat;
+    }
+
+    pub fn thermostat(&self) -> Option<Thermostat> {
+        self.state.core.thermostat
+    }
+
+    pub fn temperature(&self) -> Scalar {
+        thermostat::temperature(&self.state.core.circles)
+    }
+
+    /// Time window wall pressure is averaged over.
+    pub fn set_pressure_window(&mut self, window: Duration) {
+        self.state.core.pressure = PressureGauge::new(window);
+    }
+
+    pub fn observables(&self) -> Observables {
+        Observables::measure(
+            &self.state.core.circles,
+            self.state.core.pressure.pressure(),
+        )
+    }
+
+    /// Speeds up to four times the most probable speed of a circle of mean mass.
+    pub fn speed_histogram(&self, bins: usize) -> SpeedHistogram {
+        let circles = &self.state.core.circles;
+        let temperature = self.temperature();
+        let mean_mass = circles.iter().map(Circle::mass).sum::<Scalar>() / circles.len() as Scalar;
+        let max_speed = 4.0 * (temperature / mean_mass).sqrt();
+        let max_speed = if max_speed > 0.0 { max_speed } else { 1.0 };
+        SpeedHistogram::new(circles, bins, max_speed, temperature)
+    }
+
+    pub fn packing_fraction(&self) -> Scalar {
+        let area: Scalar = self
+            .state
+            .core
+            .circles
+            .iter()
+            .map(|c| PI * c.radius().powi(2))
+            .sum();
+        area / (self.state.core.field_size.x() * self.state.core.field_size.y())
+    }
+
+    /// Largest penetration depth among interacting pairs.
+    pub fn max_overlap(&self) -> Scalar {
+        broad_phase::candidate_pairs(&self.state.core.circles)
+            .into_iter()
+            .map(|(i, j)| (&self.state.core.circles[i], &self.state.core.circles[j]))
+            .filter(|(a, b)| a.filter().interacts(b.filter()))
+            .map(|(a, b)| a.radius() + b.radius() - (a.center() - b.center()).length())
+            .fold(0.0, Scalar::max)
+    }
+
+    /// Pushes overlapping circles apart, in inverse proportion to their masses, and back
+    /// inside the field and out of obstacles and compounds. Velocities are left untouched.
+    pub fn resolve_overlaps(&mut self, iterations: usize) {
+        for _ in 0..iterations {
+            for (i, j) in broad_phase::candidate_pairs(&self.state.core.circles) {
+                let (a, b) = pair_mut(&mut self.state.core.circles, i, j);
+                if !a.filter().interacts(b.filter()) {
+                    continue;
+                }
+                let to_b = b.center() - a.center();
+                let overlap = a.radius() + b.radius() - to_b.length();
+                if overlap <= 0.0 || to_b == Vector::zero() {
+                    continue;
+                }
+                let normal = to_b.normalize();
+                let a_share = b.mass() / (a.mass() + b.mass());
+                a.set_center(a.center() - normal * overlap * a_share);
+                b.set_center(b.center() + normal * overlap * (1.0 - a_share));
+            }
+            for circle in &mut self.state.core.circles {
+                let min = Vector::splat(circle.radius());
+                let max = (self.state.core.field_size - min).max(min);
+                circle.set_center(circle.center().max(min).min(max));
+                for (_, obstacle) in &self.state.obstacles.list {
+                    if let Some((point, normal)) = obstacle.contact(circle) {
+                        circle.set_center(point + normal * circle.radius());
+                    }
+                }
+                for (_, compound) in &self.state.compounds.list {
+                    for member in compound.members() {
+                        let away = circle.center() - member.center();
+                        let overlap = member.radius() + circle.radius() - away.length();
+                        if !member.filter().interacts(circle.filter())
+                            || overlap <= 0.0
+                            || away == Vector::zero()
+                        {
+                            continue;
+                        }
+                        circle.set_center(circle.center() + away.normalize() * overlap);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn events(&mut self) -> &mut CollisionEvents {
+        &mut self.events
+    }
+
+    pub fn step(&mut self, elapsed_time: Duration) {
+        trace!(self.logger, "Simulation step: {:?}", elapsed_time);
+        self.state.core.time += elapsed_time;
+        self.state.core.steps += 1;
+        self.grow_circles();
+        if self.state.core.radii_changed {
+            self.resolve_overlaps(OVERLAP_ITERATIONS);
+            self.state.core.radii_changed = false;
+        }
+        self.emit_circles(elapsed_time);
+        self.reflect_from_walls();
+        self.reflect_from_obstacles();
+        self.collide_circles();
+        self.collide_compounds();
+        if let Some(thermostat) = self.state.core.thermostat {
+            thermostat.apply(
+                &mut self.state.core.circles,
+                elapsed_time,
+                &mut self.state.core.rng,
+            );
+        }
+        // Half kicks around the drift keep free fall exact for constant gravity.
+        let half_kick = self.state.core.gravity * (0.5 * seconds(elapsed_time));
+        for circle in &mut self.state.core.circles {
+            circle.set_speed(circle.speed() + half_kick);
+            circle.update(elapsed_time);
+            circle.set_speed(circle.speed() + half_kick);
+        }
+        for (_, compound) in &mut self.state.compounds.list {
+            compound.set_speed(compound.speed() + half_kick);
+            compound.update(elapsed_time);
+            compound.set_speed(compound.speed() + half_kick);
+        }
+        self.remove_expired();
+        self.update_sensors();
+        let perimeter = 2.0 * (self.state.core.field_size.x() + self.state.core.field_size.y());
+        self.state.core.pressure.advance(elapsed_time, perimeter);
+    }
+
+    fn index_of(&self, handle: CircleHandle) -> Option<usize> {
+        self.state.core.handles.binary_search(&handle).ok()
+    }
+
+    /// Ends the contacts and sensor overlaps of the circle before reporting it removed.
+    fn remove_at(&mut self, index: usize, cause: RemoveCause) -> Circle {
+        let handle = self.state.core.handles.remove(index);
+        self.state.core.growth.remove(&handle);
+        let events = &mut self.events;
+        self.state.core.contacts.retain(|&(a, b)| {
+            let touches = a == handle || b == handle;
+            if touches {
+                events.emit(CollisionEvent::ContactEnd { a, b });
+            }
+            !touches
+        });
+        self.state.core.sensor_overlaps.retain(|&(sensor, circle)| {
+            let overlaps = circle == handle;
+            if overlaps {
+                events.emit(CollisionEvent::SensorExit { sensor, circle });
+            }
+            !overlaps
+        });
+        debug!(self.logger, "Circle {:?} removed: {:?}", handle, cause);
+        self.events.emit(CollisionEvent::Removed {
+            circle: handle,
+            cause,
+        });
+        self.state.core.circles.remove(index)
+    }
+
+    fn grow_circles(&mut self) {
+        for (handle, state) in &self.state.core.growth {
+            if let Ok(index) = self.state.core.handles.binary_search(handle) {
+                let radius = state
+                    .growth
+                    .radius(state.initial_radius, self.state.core.time - state.started);
+                self.state.core.circles[index].set_radius(radius);
+                self.state.core.radii_changed = true;
+            }
+        }
+    }
+
+    fn emit_circles(&mut self, elapsed_time: Duration) {
+        for (emitter_handle, emitter) in &mut self.state.core.emitters {
+            for _ in 0..emitter.advance(elapsed_time) {
+                if let Some(max_population) = self.state.core.max_population {
+                    if self.state.core.circles.len() >= max_population {
+                        break;
+                    }
+                }
+                let circle = emitter.spawn(&mut self.state.core.rng);
+                let members = self.state.compounds.list.iter();
+                let members = members.flat_map(|(_, compound)| compound.members());
+                let occupied =
+                    self.state
+                        .core
+                        .circles
+                        .iter()
+                        .copied()
+                        .chain(members)
+                        .any(|other| {
+                            other.filter().interacts(circle.filter()) && other.is_intersect(&circle)
+                        });
+                if occupied {
+                    emitter.postpone();
+                    break;
+                }
+                let circle_handle = CircleHandle::new(self.state.core.next_handle);
+                self.state.core.next_handle += 1;
+                self.state.core.circles.push(circle);
+                self.state.core.handles.push(circle_handle);
+                self.events.emit(CollisionEvent::Spawned {
+                    emitter: *emitter_handle,
+                    circle: circle_handle,
+                });
+            }
+        }
+    }
+
+    fn remove_expired(&mut self) {
+        let mut index = 0;
+        while index < self.state.core.circles.len() {
+            let circle = &self.state.core.circles[index];
+            let cause = if circle.is_expired() {
+                Some(RemoveCause::Expired)
+            } else {
+                self.state
+                    .core
+                    .sinks
+                    .iter()
+                    .find(|(_, sink)| sink.captures(circle))
+                    .map(|(sink, _)| RemoveCause::Sink(*sink))
+            };
+
+            match cause {
+                Some(cause) => {
+                    self.remove_at(index, cause);
+                }
+                None => index += 1,
+            }
+        }
+    }
+
+    fn reflect_from_walls(&mut self) {
+        let field_size = self.state.core.field_size;
+        for (circle, &handle) in self
+            .state
+            .core
+            .circles
+            .iter_mut()
+            .zip(&self.state.core.handles)
+        {
+            let speed = circle.speed();
+            let center = circle.center();
+
+            let x_side = if circle.left() < 0.0 && speed.x() < 0.0 {
+                Some((WallSide::Left, Vector::new(0.0, center.y())))
+            } else if circle.right() > field_size.x() && speed.x() > 0.0 {
+                Some((WallSide::Right, Vector::new(field_size.x(), center.y())))
+            } else {
+                None
+            };
+            if let Some((side, point)) = x_side {
+                circle.reflect_x();
+                let relative_speed = speed.x().abs();
+                let restitution = circle.material().restitution;
+                let impulse = (1.0 + restitution) * circle.mass() * relative_speed;
+                self.state.core.pressure.add_impulse(impulse);
+                self.events.emit(CollisionEvent::WallHit(WallHit {
+                    circle: handle,
+                    side,
+                    point,
+                    normal: side.normal(),
+                    impulse,
+                    relative_speed,
+                }));
+            }
+
+            let y_side = if circle.top() < 0.0 && speed.y() < 0.0 {
+                Some((WallSide::Top, Vector::new(center.x(), 0.0)))
+            } else if circle.bot() > field_size.y() && speed.y() > 0.0 {
+                Some((WallSide::Bottom, Vector::new(center.x(), field_size.y())))
+            } else {
+                None
+            };
+            if let Some((side, point)) = y_side {
+                circle.reflect_y();
+                let relative_speed = speed.y().abs();
+                let restitution = circle.material().restitution;
+                let impulse = (1.0 + restitution) * circle.mass() * relative_speed;
+                self.state.core.pressure.add_impulse(impulse);
+                self.events.emit(CollisionEvent::WallHit(WallHit {
+                    circle: handle,
+                    side,
+                    point,
+                    normal: side.normal(),
+                    impulse,
+                    relative_speed,
+                }));
+            }
+        }
+    }
+
+    fn reflect_from_obstacles(&mut self) {
+        for (circle, &handle) in self
+            .state
+            .core
+            .circles
+            .iter_mut()
+            .zip(&self.state.core.handles)
+        {
+            for (obstacle_handle, obstacle) in &self.state.obstacles.list {
+                let (point, normal) = match obstacle.contact(circle) {
+                    Some(contact) => contact,
+                    None => continue,
+                };
+                let relative_speed = -circle.speed().dot(normal);
+                if relative_speed <= 0.0 {
+                    continue;
+                }
+                let restitution = circle.material().restitution;
+                circle.set_speed(circle.speed() + normal * ((1.0 + restitution) * relative_speed));
+                self.events.emit(CollisionEvent::ObstacleHit(ObstacleHit {
+                    circle: handle,
+                    obstacle: *obstacle_handle,
+                    point,
+                    normal,
+                    impulse: (1.0 + restitution) * circle.mass() * relative_speed,
+                    relative_speed,
+                }));
+            }
+        }
+    }
+
+    fn collide_circles(&mut self) {
+        let mut contacts = BTreeSet::new();
+        for (i, j) in broad_phase::candidate_pairs(&self.state.core.circles) {
+            let (a, b) = pair_mut(&mut self.state.core.circles, i, j);
+            if !a.filter().interacts(b.filter()) || !a.is_intersect(b) {
+                continue;
+            }
+            let to_b = b.center() - a.center();
+            if to_b == Vector::zero() {
+                continue;
+            }
+            let normal = to_b.normalize();
+            let relative_speed = (a.speed() - b.speed()).dot(normal);
+            let approaching = relative_speed > 0.0;
+            let impulse = if approaching {
+                let speed_before = a.speed();
+                a.collide(b);
+                a.mass() * (a.speed() - speed_before).length()
+            } else {
+                0.0
+            };
+
+            let key = (self.state.core.handles[i], self.state.core.handles[j]);
+            let contact = Contact {
+                a: key.0,
+                b: key.1,
+                point: a.center() + normal * a.radius(),
+                normal,
+                impulse,
+                relative_speed: relative_speed.max(0.0),
+            };
+            let event = if self.state.core.contacts.contains(&key) {
+                CollisionEvent::ContactPersist(contact)
+            } else {
+                CollisionEvent::ContactBegin(contact)
+            };
+            self.events.emit(event);
+            contacts.insert(key);
+        }
+
+        for &(a, b) in self.state.core.contacts.difference(&contacts) {
+            self.events.emit(CollisionEvent::ContactEnd { a, b });
+        }
+        self.state.core.contacts = contacts;
+    }
+
+    fn collide_compounds(&mut self) {
+        if self.state.compounds.list.is_empty() {
+            return;
+        }
+        for (_, compound) in &mut self.state.compounds.list {
+            compound.reflect_from_walls(self.state.core.field_size);
+            for (_, obstacle) in &self.state.obstacles.list {
+                compound.collide_obstacle(obstacle);
+            }
+        }
+
+        // Plain circles come first, then members of every compound with their owners.
+        let circles = self.state.core.circles.len();
+        let mut bodies = self.state.core.circles.clone();
+        let mut owners = Vec::new();
+        for (index, (_, compound)) in self.state.compounds.list.iter().enumerate() {
+            for member in compound.members() {
+                bodies.push(member);
+                owners.push(index);
+            }
+        }
+        for (i, j) in broad_phase::candidate_pairs(&bodies) {
+            let (a, b) = (&bodies[i], &bodies[j]);
+            if j < circles || !a.filter().interacts(b.filter()) || !a.is_intersect(b) {
+                continue;
+            }
+            let b_owner = owners[j - circles];
+            if i < circles {
+                let circle = &mut self.state.core.circles[i];
+                self.state.compounds.list[b_owner]
+                    .1
+                    .collide_circle(b, circle);
+                continue;
+            }
+            // Members are pushed in compound order, so `i < j` gives `a_owner <= b_owner`.
+            let a_owner = owners[i - circles];
+            if a_owner != b_owner {
+                let (first, second) = pair_mut(&mut self.state.compounds.list, a_owner, b_owner);
+                first.1.collide_compound(a, &mut second.1, b);
+            }
+        }
+    }
+
+    fn update_sensors(&mut self) {
+        let mut overlaps = BTreeSet::new();
+        for (sensor_handle, sensor) in &self.state.core.sensors {
+            for (circle, &circle_handle) in
+                self.state.core.circles.iter().zip(&self.state.core.handles)
+            {
+                if sensor.overlaps(circle) {
+                    overlaps.insert((*sensor_handle, circle_handle));
+                }
+            }
+        }
+
+        for &(sensor, circle) in overlaps.difference(&self.state.core.sensor_overlaps) {
+            self.events
+                .emit(CollisionEvent::SensorEnter { sensor, circle });
+        }
+        for &(sensor, circle) in self.state.core.sensor_overlaps.difference(&overlaps) {
+            self.events
+                .emit(CollisionEvent::SensorExit { sensor, circle });
+        }
+        self.state.core.sensor_overlaps = overlaps;
+    }
+}
+
+/// FNV-1a, stable across runs and platforms unlike the std hasher.
+pub(crate) fn hash_bytes(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Mutable references to two different elements, `i < j`.
+pub(crate) fn pair_mut<T>(items: &mut [T], i: usize, j: usize) -> (&mut T, &mut T) {
+    let (head, tail) = items.split_at_mut(j);
+    (&mut head[i], &mut tail[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Simulation;
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::compound::Compound;
+    use crate::circles_app::emitter::Emitter;
+    use crate::circles_app::events::{CollisionEvent, RemoveCause, WallSide};
+    use crate::circles_app::filter::CollisionFilter;
+    use crate::circles_app::generators;
+    use crate::circles_app::obstacle::Obstacle;
+    use crate::circles_app::scalar::{Scalar, Vector};
+    use crate::circles_app::sensor::{Sensor, SensorShape};
+    use crate::circles_app::sink::Sink;
+    use crate::circles_app::thermostat::Thermostat;
+    use slog::{Discard, Logger};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn simulation() -> Simulation {
+        Simulation::new((100.0, 100.0).into(), Logger::root(Discard, o!()))
+    }
+
+    #[test]
+    fn contact_begin_persist_end() {
+        let mut sim = simulation();
+        let a = sim.add_circle(Circle::new((40.0, 50.0).into(), 5.0, (1.0, 0.0).into()));
+        let b = sim.add_circle(Circle::new((49.0, 50.0).into(), 5.0, (-1.0, 0.0).into()));
+
+        sim.step(Duration::from_millis(100));
+        let events: Vec<_> = sim.events().drain().collect();
+        match events.as_slice() {
+            [CollisionEvent::ContactBegin(contact)] => {
+                assert_eq!((contact.a, contact.b), (a, b));
+                assert!(contact.impulse > 0.0);
+                assert!((contact.relative_speed - 2.0).abs() < 1e-5);
+            }
+            _ => panic!("Unexpected events: {:?}", events),
+        }
+
+        sim.step(Duration::from_millis(100));
+        let events: Vec<_> = sim.events().drain().collect();
+        assert!(
+            matches!(events.as_slice(), [CollisionEvent::ContactPersist(c)] if c.impulse == 0.0)
+        );
+
+        sim.step(Duration::from_secs(5));
+        sim.step(Duration::from_millis(1));
+        let events: Vec<_> = sim.events().drain().collect();
+        assert!(events.contains(&CollisionEvent::ContactEnd { a, b }));
+    }
+
+    #[test]
+    fn wall_hit_callback() {
+        let mut sim = simulation();
+        let hits = Arc::new(Mutex::new(Vec::new()));
+        let sink = hits.clone();
+        sim.events().subscribe(move |event| {
+            if let CollisionEvent::WallHit(hit) = event {
+                sink.lock().unwrap().push(hit.side);
+            }
+        });
+        let handle = sim.add_circle(Circle::new((2.0, 50.0).into(), 5.0, (-3.0, 0.0).into()));
+
+        sim.step(Duration::from_millis(10));
+        assert_eq!(*hits.lock().unwrap(), vec![WallSide::Left]);
+        assert!(sim.circle(handle).unwrap().speed().x() > 0.0);
+        assert_eq!(sim.events().len(), 1);
+    }
+
+    #[test]
+    fn masked_circles_pass_through() {
+        let mut sim = simulation();
+        let mut a = Circle::new((40.0, 50.0).into(), 5.0, (1.0, 0.0).into());
+        a.set_filter(CollisionFilter::new(0b01, 0b01));
+        let mut b = Circle::new((49.0, 50.0).into(), 5.0, (-1.0, 0.0).into());
+        b.set_filter(CollisionFilter::new(0b10, 0b10));
+        let a = sim.add_circle(a);
+        sim.add_circle(b);
+
+        sim.step(Duration::from_millis(100));
+        assert!(sim.events().is_empty());
+        assert_eq!(sim.circle(a).unwrap().speed(), (1.0, 0.0).into());
+    }
+
+    #[test]
+    fn obstacle_mask_is_respected() {
+        let mut sim = simulation();
+        let shape = SensorShape::Rect {
+            min: (50.0, 0.0).into(),
+            max: (60.0, 100.0).into(),
+        };
+        let obstacle = sim.add_obstacle(Obstacle::new(shape, CollisionFilter::new(0b10, 0b10)));
+        let mut solid = Circle::new((45.0, 30.0).into(), 2.0, (10.0, 0.0).into());
+        solid.set_filter(CollisionFilter::new(0b10, CollisionFilter::ALL));
+        let solid = sim.add_circle(solid);
+        let ghost = sim.add_circle(Circle::new((45.0, 70.0).into(), 2.0, (10.0, 0.0).into()));
+
+        for _ in 0..100 {
+            sim.step(Duration::from_millis(20));
+        }
+        let solid_circle = sim.circle(solid).unwrap();
+        assert!(solid_circle.center().x() < 50.0);
+        assert!(solid_circle.speed().x() < 0.0);
+        assert!(sim.circle(ghost).unwrap().center().x() > 60.0);
+        let hits: Vec<_> = sim
+            .events()
+            .drain()
+            .filter_map(|event| match event {
+                CollisionEvent::ObstacleHit(hit) => Some((hit.circle, hit.obstacle)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(hits, vec![(solid, obstacle)]);
+    }
+
+    #[test]
+    fn sensor_enter_exit() {
+        let mut sim = simulation();
+        let shape = SensorShape::Rect {
+            min: (50.0, 0.0).into(),
+            max: (60.0, 100.0).into(),
+        };
+        let sensor = sim.add_sensor(Sensor::new(shape, CollisionFilter::default()));
+        let circle = sim.add_circle(Circle::new((40.0, 50.0).into(), 1.0, (10.0, 0.0).into()));
+
+        sim.step(Duration::from_secs(1));
+        let events: Vec<_> = sim.events().drain().collect();
+        assert_eq!(events, vec![CollisionEvent::SensorEnter { sensor, circle }]);
+        assert_eq!(sim.circle(circle).unwrap().speed(), (10.0, 0.0).into());
+
+        sim.step(Duration::from_secs(2));
+        let events: Vec<_> = sim.events().drain().collect();
+        assert_eq!(events, vec![CollisionEvent::SensorExit { sensor, circle }]);
+    }
+
+    #[test]
+    fn emitter_respects_rate_and_cap() {
+        let mut sim = simulation();
+        let emitter = Emitter::new((50.0, 50.0).into(), 10.0)
+            .radius(0.5, 1.0)
+            .speed(20.0, 30.0)
+            .cone(0.0, 0.5);
+        sim.add_emitter(emitter);
+        sim.set_max_population(Some(15));
+
+        for _ in 0..5 {
+            sim.step(Duration::from_millis(100));
+        }
+        assert_eq!(sim.population(), 5);
+        for (_, circle) in sim.circles() {
+            assert!(circle.radius() >= 0.5 && circle.radius() < 1.0);
+            assert!(circle.speed().length() < 30.0 + 1e-4);
+        }
+
+        for _ in 0..20 {
+            sim.step(Duration::from_millis(100));
+        }
+        assert_eq!(sim.population(), 15);
+    }
+
+    #[test]
+    fn emitter_waits_for_free_spot() {
+        let mut sim = simulation();
+        let blocker = sim.add_circle(Circle::new((50.0, 50.0).into(), 2.0, Vector::zero()));
+        sim.add_emitter(Emitter::new((50.0, 50.0).into(), 10.0).speed(20.0, 20.0));
+
+        sim.step(Duration::from_millis(500));
+        sim.step(Duration::from_millis(500));
+        assert_eq!(sim.population(), 1);
+
+        sim.remove_circle(blocker);
+        sim.step(Duration::from_millis(50));
+        assert_eq!(sim.population(), 1);
+        assert_ne!(sim.circles().next().unwrap().0, blocker);
+    }
+
+    #[test]
+    fn expired_and_sunk_circles_are_removed() {
+        let mut sim = simulation();
+        let mut mortal = Circle::new((10.0, 10.0).into(), 1.0, (0.0, 0.0).into());
+        mortal.set_lifetime(Some(Duration::from_millis(150)));
+        let mortal = sim.add_circle(mortal);
+        let sunk = sim.add_circle(Circle::new((40.0, 50.0).into(), 1.0, (10.0, 0.0).into()));
+        let survivor = sim.add_circle(Circle::new((10.0, 90.0).into(), 1.0, (0.0, 0.0).into()));
+        let shape = SensorShape::Circle {
+            center: (60.0, 50.0).into(),
+            radius: 5.0,
+        };
+        let sink = sim.add_sink(Sink::new(shape, CollisionFilter::default()));
+
+        sim.step(Duration::from_millis(100));
+        assert!(sim.events().is_empty());
+        sim.step(Duration::from_millis(100));
+        sim.step(Duration::from_secs(1));
+        sim.step(Duration::from_secs(1));
+
+        let events: Vec<_> = sim.events().drain().collect();
+        assert_eq!(
+            events,
+            vec![
+                CollisionEvent::Removed {
+                    circle: mortal,
+                    cause: RemoveCause::Expired
+                },
+                CollisionEvent::Removed {
+                    circle: sunk,
+                    cause: RemoveCause::Sink(sink)
+                },
+            ]
+        );
+        assert!(sim.circle(mortal).is_none());
+        assert_eq!(sim.circle(survivor).unwrap().center(), (10.0, 90.0).into());
+    }
+
+    #[test]
+    fn removal_ends_contacts_and_overlaps_first() {
+        let mut sim = simulation();
+        let a = sim.add_circle(Circle::new((40.0, 50.0).into(), 5.0, Vector::zero()));
+        let b = sim.add_circle(Circle::new((49.0, 50.0).into(), 5.0, Vector::zero()));
+        let shape = SensorShape::Circle {
+            center: (40.0, 50.0).into(),
+            radius: 1.0,
+        };
+        let sensor = sim.add_sensor(Sensor::new(shape, CollisionFilter::default()));
+        sim.step(Duration::from_millis(10));
+        sim.events().drain();
+
+        assert!(sim.remove_circle(a).is_some());
+        let events: Vec<_> = sim.events().drain().collect();
+        assert_eq!(
+            events,
+            vec![
+                CollisionEvent::ContactEnd { a, b },
+                CollisionEvent::SensorExit { sensor, circle: a },
+                CollisionEvent::Removed {
+                    circle: a,
+                    cause: RemoveCause::Manual
+                },
+            ]
+        );
+        sim.step(Duration::from_millis(10));
+        assert!(sim.events().is_empty());
+    }
+
+    #[test]
+    fn circle_hit_pushes_and_spins_compound() {
+        let mut sim = Simulation::new((1000.0, 1000.0).into(), Logger::root(Discard, o!()));
+        let dumbbell = sim.add_compound(Compound::dumbbell((500.0, 500.0).into(), 5.0, 40.0));
+        let circle = Circle::new((480.0, 480.0).into(), 5.0, (0.0, 50.0).into());
+        let momentum_before = circle.speed() * circle.mass();
+        let circle = sim.add_circle(circle);
+        for _ in 0..50 {
+            sim.step(Duration::from_millis(10));
+        }
+
+        let compound = sim.compound(dumbbell).unwrap();
+        let circle = sim.circle(circle).unwrap();
+        assert!(compound.speed().y() > 0.0);
+        assert!(compound.angular_speed() < 0.0);
+        let momentum = compound.momentum() + circle.speed() * circle.mass();
+        assert!((momentum - momentum_before).length() < 1e-2 * momentum_before.length());
+    }
+
+    #[test]
+    fn partition_keeps_gases_apart_until_removed() {
+        let field_size = (200.0, 100.0).into();
+        let mut sim = Simulation::new(field_size, Logger::root(Discard, o!()));
+        for circle in generators::two_gases(field_size, 60, (1.0, 2.0), (40.0, 40.0), 7) {
+            sim.add_circle(circle);
+        }
+        let partition = sim.add_obstacle(generators::partition(field_size));
+        let mixed = |sim: &Simulation| {
+            sim.circles()
+                .filter(|(_, c)| (c.species() == 0) != (c.center().x() < 100.0))
+                .count()
+        };
+        for _ in 0..1000 {
+            sim.step(Duration::from_millis(5));
+            assert_eq!(mixed(&sim), 0);
+        }
+        assert!(sim.remove_obstacle(partition).is_some());
+        for _ in 0..1000 {
+            sim.step(Duration::from_millis(5));
+        }
+        assert!(mixed(&sim) > 0);
+    }
+
+    #[test]
+    fn compounds_are_fixed_for_overlaps_and_spawns() {
+        let mut sim = simulation();
+        let mut dumbbell = Compound::dumbbell((50.0, 50.0).into(), 5.0, 30.0);
+        dumbbell.set_speed((-100.0, 0.0).into());
+        sim.add_compound(dumbbell);
+        let circle = sim.add_circle(Circle::new((32.0, 50.0).into(), 2.0, Vector::zero()));
+        sim.resolve_overlaps(1);
+        let pushed = sim.circle(circle).unwrap();
+        assert!((pushed.center() - Vector::new(28.0, 50.0)).length() < 1e-4);
+        sim.remove_circle(circle);
+
+        sim.add_emitter(Emitter::new((65.0, 50.0).into(), 10.0));
+        sim.step(Duration::from_millis(100));
+        assert_eq!(sim.population(), 0);
+        for _ in 0..50 {
+            sim.step(Duration::from_millis(10));
+        }
+        assert!(sim.population() > 0);
+        assert_eq!(sim.observables().pressure, 0.0);
+    }
+
+    #[test]
+    fn compounds_stay_in_field_and_conserve_energy() {
+        let field_size = (200.0, 150.0).into();
+        let mut sim = Simulation::new(field_size, Logger::root(Discard, o!()));
+        for circle in generators::poisson_disk(field_size, 40, (2.0, 4.0), 40.0, 50) {
+            sim.add_circle(circle);
+        }
+        let mut gear = Compound::gear((60.0, 75.0).into(), 12.0, 8, 3.0);
+        gear.set_speed((30.0, 10.0).into());
+        gear.set_angular_speed(2.0);
+        sim.add_compound(gear);
+        let mut dumbbell = Compound::dumbbell((150.0, 40.0).into(), 6.0, 40.0);
+        dumbbell.set_speed((-20.0, 25.0).into());
+        sim.add_compound(dumbbell);
+
+        let energy = |sim: &Simulation| -> Scalar {
+            sim.circles()
+                .map(|(_, c)| c.kinetic_energy())
+                .sum::<Scalar>()
+                + sim
+                    .compounds()
+                    .map(|(_, c)| c.kinetic_energy())
+                    .sum::<Scalar>()
+        };
+        let energy_before = energy(&sim);
+        for _ in 0..2000 {
+            sim.step(Duration::from_millis(5));
+        }
+        assert!((energy(&sim) - energy_before).abs() < 1e-3 * energy_before);
+        for (_, compound) in sim.compounds() {
+            for member in compound.members() {
+                let slack = 1.0;
+                assert!(
+                    member.left() > -slack && member.top() > -slack,
+                    "{:?}",
+                    member
+                );
+                assert!(member.right() < 200.0 + slack, "{:?}", member);
+                assert!(member.bot() < 150.0 + slack, "{:?}", member);
+            }
+        }
+    }
+
+    fn deterministic_run(seed: u64) -> Simulation {
+        let field_size = (300.0, 200.0).into();
+        let mut sim = Simulation::new(field_size, Logger::root(Discard, o!()));
+        sim.set_seed(seed);
+        for circle in generators::poisson_disk(field_size, 150, (2.0, 6.0), 50.0, 4) {
+            sim.add_circle(circle);
+        }
+        sim.set_gravity((0.0, 9.8).into());
+        sim.set_thermostat(Some(Thermostat::Langevin {
+            target: 1000.0,
+            damping: 0.5,
+        }));
+        sim.add_emitter(Emitter::new((150.0, 20.0).into(), 10.0).speed(10.0, 30.0));
+        sim.set_max_population(Some(200));
+        for _ in 0..300 {
+            sim.step(Duration::from_millis(10));
+        }
+        sim
+    }
+
+    #[test]
+    fn deterministic_state_hash() {
+        // Golden value changes whenever physics or state layout changes. Update it deliberately.
+        #[cfg(not(feature = "f64"))]
+        const GOLDEN: u64 = 0x2214_737e_0c4d_f4bc;
+        #[cfg(feature = "f64")]
+        const GOLDEN: u64 = 0x109d_666d_d61a_74ca;
+        let sim = deterministic_run(38);
+        assert_eq!(sim.state_hash(), deterministic_run(38).state_hash());
+        assert_ne!(sim.state_hash(), deterministic_run(39).state_hash());
+        assert_eq!(sim.state_hash(), GOLDEN, "{:#018x}", sim.state_hash());
+    }
+}
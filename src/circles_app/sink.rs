@@ -0,0 +1,29 @@
+use crate::circles_app::circle::Circle;
+use crate::circles_app::filter::CollisionFilter;
+use crate::circles_app::sensor::SensorShape;
+use serde::{Deserialize, Serialize};
+
+/// Region that deletes circles entering it.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sink {
+    shape: SensorShape,
+    filter: CollisionFilter,
+}
+
+impl Sink {
+    pub fn new(shape: SensorShape, filter: CollisionFilter) -> Self {
+        Self { shape, filter }
+    }
+
+    pub fn shape(&self) -> SensorShape {
+        self.shape
+    }
+
+    pub fn filter(&self) -> CollisionFilter {
+        self.filter
+    }
+
+    pub fn captures(&self, circle: &Circle) -> bool {
+        self.filter.interacts(circle.filter()) && self.shape.overlaps(circle)
+    }
+}
@@ -0,0 +1,391 @@
+use crate::circles_app::scalar::SCALAR_SIZE;
+use crate::circles_app::simulation::{
+    CompoundState, CoreState, ObstacleState, Simulation, SimulationState,
+};
+use serde::{Deserialize, Serialize};
+use slog::Logger;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+// Layout:
+//   magic, format version (u16 LE), header length (u32 LE), header,
+//   sections: tag, payload length (u64 LE), payload.
+// Newer writers may append fields to the header and add sections. Readers ignore both.
+// Format version is increased only when old readers can't read new snapshots.
+pub const MAGIC: [u8; 4] = *b"CSNP";
+pub const FORMAT_VERSION: u16 = 1;
+pub const SNAPSHOT_EXTENSION: &str = "csnp";
+const STATE_SECTION: [u8; 4] = *b"STAT";
+/// `SCALAR_SIZE` of the writer. Snapshots without it are decoded as if it matched.
+const PRECISION_SECTION: [u8; 4] = *b"PREC";
+/// Compounds, the circles welded together. Snapshots without it have none.
+const COMPOUND_SECTION: [u8; 4] = *b"CMPD";
+/// Snapshots without it have no obstacles.
+const OBSTACLE_SECTION: [u8; 4] = *b"OBST";
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    Encoding(bincode::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    MissingSection([u8; 4]),
+    /// Scalar size in bytes of the build that wrote the snapshot.
+    PrecisionMismatch(u8),
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(e) => Some(e),
+            SnapshotError::Encoding(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(e) => Display::fmt(e, f),
+            SnapshotError::Encoding(e) => Display::fmt(e, f),
+            SnapshotError::BadMagic => write!(f, "Not a circles snapshot"),
+            SnapshotError::UnsupportedVersion(v) => write!(
+                f,
+                "Snapshot format version {} is newer than supported {}",
+                v, FORMAT_VERSION
+            ),
+            SnapshotError::MissingSection(tag) => {
+                write!(
+                    f,
+                    "Snapshot has no {:?} section",
+                    String::from_utf8_lossy(tag)
+                )
+            }
+            SnapshotError::PrecisionMismatch(size) => write!(
+                f,
+                "Snapshot has {}-byte scalars, this build uses {}-byte ones",
+                size, SCALAR_SIZE
+            ),
+        }
+    }
+}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<bincode::Error> for SnapshotError {
+    fn from(e: bincode::Error) -> Self {
+        Self::Encoding(e)
+    }
+}
+
+/// Summary readable without decoding the whole state.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    pub steps: u64,
+    pub time: Duration,
+    pub circles: u64,
+}
+
+pub fn encode_state(state: &SimulationState) -> Result<Vec<u8>, SnapshotError> {
+    Ok(bincode::serialize(state)?)
+}
+
+pub fn decode_state(bytes: &[u8]) -> Result<SimulationState, SnapshotError> {
+    Ok(bincode::deserialize(bytes)?)
+}
+
+pub fn write<W: Write>(simulation: &Simulation, mut writer: W) -> Result<(), SnapshotError> {
+    let header = bincode::serialize(&SnapshotHeader {
+        steps: simulation.steps(),
+        time: simulation.time(),
+        circles: simulation.population() as u64,
+    })?;
+    let state = simulation.state();
+    let core = bincode::serialize(state.core())?;
+    let compounds = bincode::serialize(state.compounds())?;
+    let obstacles = bincode::serialize(state.obstacles())?;
+
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    writer.write_all(&(header.len() as u32).to_le_bytes())?;
+    writer.write_all(&header)?;
+    write_section(&mut writer, PRECISION_SECTION, &[SCALAR_SIZE])?;
+    write_section(&mut writer, STATE_SECTION, &core)?;
+    write_section(&mut writer, COMPOUND_SECTION, &compounds)?;
+    write_section(&mut writer, OBSTACLE_SECTION, &obstacles)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_section<W: Write>(mut writer: W, tag: [u8; 4], payload: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&tag)?;
+    writer.write_all(&(payload.len() as u64).to_le_bytes())?;
+    writer.write_all(payload)
+}
+
+pub fn read<R: Read>(mut reader: R, logger: Logger) -> Result<Simulation, SnapshotError> {
+    let version = read_version(&mut reader)?;
+    let header = read_header_body(&mut reader)?;
+    debug!(logger, "Reading snapshot version {}: {:?}", version, header);
+
+    let mut precision = None;
+    let mut core = None;
+    let mut compounds = None;
+    let mut obstacles = None;
+    while let Some((tag, payload)) = read_section(&mut reader)? {
+        match tag {
+            PRECISION_SECTION => precision = payload.first().copied(),
+            STATE_SECTION => core = Some(payload),
+            COMPOUND_SECTION => compounds = Some(payload),
+            OBSTACLE_SECTION => obstacles = Some(payload),
+            _ => trace!(logger, "Skipping unknown snapshot section {:?}", tag),
+        }
+    }
+    match precision {
+        Some(size) if size != SCALAR_SIZE => return Err(SnapshotError::PrecisionMismatch(size)),
+        _ => {}
+    }
+    let core: CoreState =
+        bincode::deserialize(&core.ok_or(SnapshotError::MissingSection(STATE_SECTION))?)?;
+    let compounds: CompoundState = match compounds {
+        Some(payload) => bincode::deserialize(&payload)?,
+        None => CompoundState::default(),
+    };
+    let obstacles: ObstacleState = match obstacles {
+        Some(payload) => bincode::deserialize(&payload)?,
+        None => ObstacleState::default(),
+    };
+    let state = SimulationState::from_parts(core, compounds, obstacles);
+    Ok(Simulation::from_state(state, logger))
+}
+
+/// Tag and payload.
+type Section = ([u8; 4], Vec<u8>);
+
+/// `None` at the end of the snapshot.
+fn read_section<R: Read>(mut reader: R) -> Result<Option<Section>, SnapshotError> {
+    let mut tag = [0u8; 4];
+    match reader.read_exact(&mut tag) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let mut len = [0u8; 8];
+    reader.read_exact(&mut len)?;
+    let payload = read_payload(reader, u64::from_le_bytes(len))?;
+    Ok(Some((tag, payload)))
+}
+
+pub fn read_header<R: Read>(mut reader: R) -> Result<SnapshotHeader, SnapshotError> {
+    read_version(&mut reader)?;
+    read_header_body(reader)
+}
+
+fn read_version<R: Read>(mut reader: R) -> Result<u16, SnapshotError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(SnapshotError::BadMagic);
+    }
+    let mut version = [0u8; 2];
+    reader.read_exact(&mut version)?;
+    let version = u16::from_le_bytes(version);
+    if version > FORMAT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+    Ok(version)
+}
+
+fn read_header_body<R: Read>(mut reader: R) -> Result<SnapshotHeader, SnapshotError> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let header = read_payload(reader, u32::from_le_bytes(len).into())?;
+    Ok(bincode::deserialize(&header)?)
+}
+
+/// Reads `len` bytes, growing the buffer only as data arrives, so a corrupted length fails
+/// at the end of the file instead of allocating it up front.
+fn read_payload<R: Read>(reader: R, len: u64) -> Result<Vec<u8>, SnapshotError> {
+    let mut payload = Vec::new();
+    reader.take(len).read_to_end(&mut payload)?;
+    if (payload.len() as u64) < len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(payload)
+}
+
+pub fn save(simulation: &Simulation, path: &Path) -> Result<(), SnapshotError> {
+    write(simulation, BufWriter::new(File::create(path)?))
+}
+
+pub fn load(path: &Path, logger: Logger) -> Result<Simulation, SnapshotError> {
+    read(BufReader::new(File::open(path)?), logger)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        encode_state, read, read_header, write, SnapshotError, COMPOUND_SECTION, MAGIC,
+        PRECISION_SECTION, STATE_SECTION,
+    };
+    use crate::circles_app::compound::Compound;
+    use crate::circles_app::emitter::Emitter;
+    use crate::circles_app::filter::CollisionFilter;
+    use crate::circles_app::generators;
+    use crate::circles_app::growth::Growth;
+    use crate::circles_app::handle::CircleHandle;
+    use crate::circles_app::obstacle::Obstacle;
+    use crate::circles_app::scalar::SCALAR_SIZE;
+    use crate::circles_app::sensor::SensorShape;
+    use crate::circles_app::simulation::Simulation;
+    use crate::circles_app::thermostat::Thermostat;
+    use slog::{Discard, Logger};
+    use std::io::ErrorKind;
+    use std::time::Duration;
+
+    fn logger() -> Logger {
+        Logger::root(Discard, o!())
+    }
+
+    fn busy_simulation() -> Simulation {
+        let field_size = (200.0, 200.0).into();
+        let mut sim = Simulation::new(field_size, logger());
+        sim.set_seed(17);
+        for circle in generators::poisson_disk(field_size, 100, (1.0, 4.0), 30.0, 3) {
+            sim.add_circle(circle);
+        }
+        sim.set_gravity((0.0, 5.0).into());
+        sim.set_thermostat(Some(Thermostat::Andersen {
+            target: 500.0,
+            frequency: 1.0,
+        }));
+        sim.add_emitter(Emitter::new((100.0, 20.0).into(), 5.0).speed(5.0, 20.0));
+        sim.set_max_population(Some(150));
+        sim.set_growth(CircleHandle::new(0), Some(Growth::Rate(0.5)));
+        sim.add_compound(Compound::dumbbell((100.0, 150.0).into(), 6.0, 30.0));
+        let shape = SensorShape::Circle {
+            center: (60.0, 120.0).into(),
+            radius: 10.0,
+        };
+        sim.add_obstacle(Obstacle::new(shape, CollisionFilter::default()));
+        sim
+    }
+
+    #[test]
+    fn restored_run_is_bit_identical() {
+        let dt = Duration::from_millis(10);
+        let mut original = busy_simulation();
+        for _ in 0..100 {
+            original.step(dt);
+        }
+
+        let mut bytes = Vec::new();
+        write(&original, &mut bytes).unwrap();
+        let header = read_header(bytes.as_slice()).unwrap();
+        assert_eq!(header.steps, 100);
+        let mut restored = read(bytes.as_slice(), logger()).unwrap();
+        assert_eq!(restored.obstacles().count(), 1);
+
+        for _ in 0..300 {
+            original.step(dt);
+            restored.step(dt);
+        }
+        assert_eq!(restored.steps(), 400);
+        assert_eq!(
+            encode_state(original.state()).unwrap(),
+            encode_state(restored.state()).unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_sections_are_skipped() {
+        let sim = busy_simulation();
+        let mut bytes = Vec::new();
+        write(&sim, &mut bytes).unwrap();
+
+        let header_end = 10 + u32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]) as usize;
+        let mut extended = bytes[..header_end].to_vec();
+        extended.extend_from_slice(b"XTRA");
+        extended.extend_from_slice(&3u64.to_le_bytes());
+        extended.extend_from_slice(&[1, 2, 3]);
+        extended.extend_from_slice(&bytes[header_end..]);
+
+        let restored = read(extended.as_slice(), logger()).unwrap();
+        assert_eq!(restored.population(), sim.population());
+    }
+
+    #[test]
+    fn snapshot_without_compound_section_has_no_compounds() {
+        let sim = busy_simulation();
+        let mut bytes = Vec::new();
+        write(&sim, &mut bytes).unwrap();
+
+        let tag = bytes
+            .windows(4)
+            .position(|window| window == COMPOUND_SECTION)
+            .unwrap();
+        let mut len = [0u8; 8];
+        len.copy_from_slice(&bytes[tag + 4..tag + 12]);
+        let end = tag + 12 + u64::from_le_bytes(len) as usize;
+        let mut old = bytes[..tag].to_vec();
+        old.extend_from_slice(&bytes[end..]);
+
+        let restored = read(old.as_slice(), logger()).unwrap();
+        assert_eq!(sim.compounds().count(), 1);
+        assert_eq!(restored.compounds().count(), 0);
+        assert_eq!(restored.population(), sim.population());
+    }
+
+    #[test]
+    fn other_precision_is_rejected() {
+        let mut bytes = Vec::new();
+        write(&busy_simulation(), &mut bytes).unwrap();
+        let header_end = 10 + u32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]) as usize;
+        assert_eq!(bytes[header_end..header_end + 4], PRECISION_SECTION);
+        let other = if SCALAR_SIZE == 4 { 8 } else { 4 };
+        bytes[header_end + 12] = other;
+        let result = read(bytes.as_slice(), logger());
+        assert!(matches!(result, Err(SnapshotError::PrecisionMismatch(size)) if size == other));
+    }
+
+    #[test]
+    fn truncated_state_is_rejected() {
+        let mut bytes = Vec::new();
+        write(&busy_simulation(), &mut bytes).unwrap();
+        let tag = bytes
+            .windows(4)
+            .position(|window| window == STATE_SECTION)
+            .unwrap();
+        bytes.truncate(tag + 100);
+        let result = read(bytes.as_slice(), logger());
+        assert!(
+            matches!(result, Err(SnapshotError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof)
+        );
+
+        bytes[tag + 4..tag + 12].copy_from_slice(&u64::MAX.to_le_bytes());
+        let result = read(bytes.as_slice(), logger());
+        assert!(
+            matches!(result, Err(SnapshotError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn rejects_foreign_data() {
+        let result = read(&b"not a snapshot"[..], logger());
+        assert!(matches!(result, Err(SnapshotError::BadMagic)));
+
+        let mut newer = MAGIC.to_vec();
+        newer.extend_from_slice(&99u16.to_le_bytes());
+        let result = read(newer.as_slice(), logger());
+        assert!(matches!(result, Err(SnapshotError::UnsupportedVersion(99))));
+    }
+}
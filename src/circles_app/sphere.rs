@@ -0,0 +1,169 @@
+use crate::circles_app::material::Material;
+use crate::circles_app::scalar::consts::PI;
+use crate::circles_app::scalar::{seconds, Scalar, Vector3};
+use glam::Vec4;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// `Circle` of the 3D mode. Walls are the faces of an axis aligned box.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sphere {
+    center: Vector3,
+    radius: Scalar,
+    speed: Vector3,
+    material: Material,
+    color: Vec4,
+}
+
+impl Sphere {
+    pub fn new(center: Vector3, radius: Scalar, speed: Vector3) -> Self {
+        Self {
+            center,
+            radius,
+            speed,
+            material: Material::default(),
+            color: Vec4::one(),
+        }
+    }
+
+    /// Material density is taken per unit of volume here.
+    pub fn mass(&self) -> Scalar {
+        self.material.density * 4.0 / 3.0 * PI * self.radius.powi(3)
+    }
+
+    pub fn kinetic_energy(&self) -> Scalar {
+        0.5 * self.mass() * self.speed.length_squared()
+    }
+
+    pub fn center(&self) -> Vector3 {
+        self.center
+    }
+
+    pub fn set_center(&mut self, center: Vector3) {
+        self.center = center;
+    }
+
+    /// Corner of the bounding box with the smallest coordinates.
+    pub fn lower(&self) -> Vector3 {
+        self.center - Vector3::splat(self.radius)
+    }
+
+    pub fn upper(&self) -> Vector3 {
+        self.center + Vector3::splat(self.radius)
+    }
+
+    pub fn radius(&self) -> Scalar {
+        self.radius
+    }
+
+    pub fn speed(&self) -> Vector3 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: Vector3) {
+        self.speed = speed;
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    pub fn color(&self) -> Vec4 {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: Vec4) {
+        self.color = color;
+    }
+
+    pub fn is_intersect(&self, other: &Self) -> bool {
+        (self.center - other.center).length() < (self.radius + other.radius)
+    }
+
+    pub fn update(&mut self, elapsed_time: Duration) {
+        self.center += seconds(elapsed_time) * self.speed;
+    }
+
+    pub fn reflect_x(&mut self) {
+        self.speed *= Vector3::new(-self.material.restitution, 1.0, 1.0);
+    }
+
+    pub fn reflect_y(&mut self) {
+        self.speed *= Vector3::new(1.0, -self.material.restitution, 1.0);
+    }
+
+    pub fn reflect_z(&mut self) {
+        self.speed *= Vector3::new(1.0, 1.0, -self.material.restitution);
+    }
+
+    /// Same exchange of normal momentum as `Circle::collide`, tangential speeds are kept.
+    pub fn collide(&mut self, other: &mut Self) {
+        let v1 = self.speed;
+        let v2 = other.speed;
+        let to_other = other.center - self.center;
+        let to_self = -to_other;
+        let cos_a1 = Self::v_dv_cos(v1, to_self);
+        let cos_a2 = Self::v_dv_cos(v2, to_other);
+        let m1 = self.mass();
+        let m2 = other.mass();
+        let v1_len = v1.length();
+        let v2_len = v2.length();
+        let restitution = self.material.combined_restitution(other.material);
+        let dv1_len =
+            ((1.0 + restitution) * (v1_len * cos_a1 + v2_len * cos_a2) * m2 / (m1 + m2)).abs();
+        let dv2_len = dv1_len * m1 / m2;
+        self.speed += to_self.normalize() * dv1_len;
+        other.speed += to_other.normalize() * dv2_len;
+    }
+
+    fn v_dv_cos(v: Vector3, dv: Vector3) -> Scalar {
+        if v == Vector3::zero() || dv == Vector3::zero() {
+            return 0.0;
+        }
+        v.dot(dv) * v.length_reciprocal() * dv.length_reciprocal()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sphere;
+    use crate::circles_app::scalar::{Scalar, Vector3};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn line_collide() {
+        let mut a = Sphere::new(Vector3::zero(), 1.0, Vector3::new(0.0, 0.0, 5.0));
+        let mut b = Sphere::new(
+            Vector3::new(0.0, 0.0, 1.5),
+            1.0,
+            Vector3::new(0.0, 0.0, -5.0),
+        );
+        assert!(a.is_intersect(&b));
+        a.collide(&mut b);
+        assert_approx_eq!(a.speed().z(), -5.0);
+        assert_approx_eq!(b.speed().z(), 5.0);
+    }
+
+    #[test]
+    fn oblique_collide_conserves_momentum_and_energy() {
+        let mut a = Sphere::new(Vector3::zero(), 1.0, Vector3::new(3.0, 1.0, -2.0));
+        let mut b = Sphere::new(
+            Vector3::new(1.0, 1.0, 1.0),
+            0.5,
+            Vector3::new(-1.0, 0.0, 0.5),
+        );
+        let momentum = |a: &Sphere, b: &Sphere| a.speed() * a.mass() + b.speed() * b.mass();
+        let energy = |a: &Sphere, b: &Sphere| a.kinetic_energy() + b.kinetic_energy();
+        let (momentum_before, energy_before) = (momentum(&a, &b), energy(&a, &b));
+        a.collide(&mut b);
+        let tolerance: Scalar = 1e-4;
+        assert!((momentum(&a, &b) - momentum_before).length() < tolerance);
+        assert_approx_eq!(energy(&a, &b), energy_before, tolerance);
+        let normal = (b.center() - a.center()).normalize();
+        assert!((b.speed() - a.speed()).dot(normal) > 0.0);
+    }
+}
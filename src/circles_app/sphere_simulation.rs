@@ -0,0 +1,169 @@
+use crate::circles_app::broad_phase;
+use crate::circles_app::scalar::{seconds, Scalar, Vector3};
+use crate::circles_app::simulation;
+use crate::circles_app::sphere::Sphere;
+use slog::Logger;
+use std::time::Duration;
+
+/// Elastic spheres in a box spanning from the origin to `field_size`. A reduced `Simulation`:
+/// no events, emitters, sensors or snapshots yet.
+pub struct SphereSimulation {
+    spheres: Vec<Sphere>,
+    field_size: Vector3,
+    gravity: Vector3,
+    time: Duration,
+    steps: u64,
+    logger: Logger,
+}
+
+impl SphereSimulation {
+    pub fn new(field_size: Vector3, logger: Logger) -> Self {
+        Self {
+            spheres: Vec::new(),
+            field_size,
+            gravity: Vector3::zero(),
+            time: Duration::from_secs(0),
+            steps: 0,
+            logger,
+        }
+    }
+
+    pub fn add_sphere(&mut self, sphere: Sphere) {
+        self.spheres.push(sphere);
+    }
+
+    pub fn spheres(&self) -> &[Sphere] {
+        &self.spheres
+    }
+
+    pub fn field_size(&self) -> Vector3 {
+        self.field_size
+    }
+
+    pub fn gravity(&self) -> Vector3 {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector3) {
+        self.gravity = gravity;
+    }
+
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn kinetic_energy(&self) -> Scalar {
+        self.spheres.iter().map(Sphere::kinetic_energy).sum()
+    }
+
+    pub fn momentum(&self) -> Vector3 {
+        self.spheres
+            .iter()
+            .fold(Vector3::zero(), |sum, s| sum + s.speed() * s.mass())
+    }
+
+    pub fn step(&mut self, elapsed_time: Duration) {
+        trace!(self.logger, "Sphere simulation step: {:?}", elapsed_time);
+        self.time += elapsed_time;
+        self.steps += 1;
+        self.reflect_from_walls();
+        self.collide_spheres();
+        let half_kick = self.gravity * (0.5 * seconds(elapsed_time));
+        for sphere in &mut self.spheres {
+            sphere.set_speed(sphere.speed() + half_kick);
+            sphere.update(elapsed_time);
+            sphere.set_speed(sphere.speed() + half_kick);
+        }
+    }
+
+    fn reflect_from_walls(&mut self) {
+        let field_size = self.field_size;
+        for sphere in &mut self.spheres {
+            let (speed, lower, upper) = (sphere.speed(), sphere.lower(), sphere.upper());
+            if (lower.x() < 0.0 && speed.x() < 0.0)
+                || (upper.x() > field_size.x() && speed.x() > 0.0)
+            {
+                sphere.reflect_x();
+            }
+            if (lower.y() < 0.0 && speed.y() < 0.0)
+                || (upper.y() > field_size.y() && speed.y() > 0.0)
+            {
+                sphere.reflect_y();
+            }
+            if (lower.z() < 0.0 && speed.z() < 0.0)
+                || (upper.z() > field_size.z() && speed.z() > 0.0)
+            {
+                sphere.reflect_z();
+            }
+        }
+    }
+
+    fn collide_spheres(&mut self) {
+        for (i, j) in broad_phase::sphere_pairs(&self.spheres) {
+            let (a, b) = simulation::pair_mut(&mut self.spheres, i, j);
+            if !a.is_intersect(b) {
+                continue;
+            }
+            let to_b = b.center() - a.center();
+            if to_b != Vector3::zero() && (a.speed() - b.speed()).dot(to_b) > 0.0 {
+                a.collide(b);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SphereSimulation;
+    use crate::circles_app::generators;
+    use crate::circles_app::scalar::Vector3;
+    use crate::circles_app::sphere::Sphere;
+    use slog::{Discard, Logger};
+    use std::time::Duration;
+
+    #[test]
+    fn spheres_stay_in_box_and_conserve_energy() {
+        let field_size = Vector3::new(4.0, 3.0, 2.0);
+        let mut simulation = SphereSimulation::new(field_size, Logger::root(Discard, o!()));
+        for sphere in generators::random_spheres(field_size, 50, (0.05, 0.15), 1.0, 48) {
+            simulation.add_sphere(sphere);
+        }
+        let energy = simulation.kinetic_energy();
+        for _ in 0..2000 {
+            simulation.step(Duration::from_millis(5));
+        }
+        assert!((simulation.kinetic_energy() - energy).abs() < 1e-3 * energy);
+        for sphere in simulation.spheres() {
+            let (lower, upper) = (sphere.lower(), sphere.upper());
+            let slack = 0.01;
+            assert!(lower.min_element() > -slack, "{:?}", sphere);
+            assert!((upper - field_size).max_element() < slack, "{:?}", sphere);
+        }
+    }
+
+    #[test]
+    fn wall_reflects_only_approaching_sphere() {
+        let mut simulation =
+            SphereSimulation::new(Vector3::splat(10.0), Logger::root(Discard, o!()));
+        simulation.add_sphere(Sphere::new(
+            Vector3::new(5.0, 5.0, 9.5),
+            1.0,
+            Vector3::new(0.0, 0.0, 2.0),
+        ));
+        simulation.add_sphere(Sphere::new(
+            Vector3::new(5.0, 0.5, 2.0),
+            1.0,
+            Vector3::new(0.0, 2.0, 0.0),
+        ));
+        simulation.step(Duration::from_millis(10));
+        assert_eq!(
+            simulation.spheres()[0].speed(),
+            Vector3::new(0.0, 0.0, -2.0)
+        );
+        assert_eq!(simulation.spheres()[1].speed(), Vector3::new(0.0, 2.0, 0.0));
+    }
+}
@@ -0,0 +1,136 @@
+use crate::app::status::Status;
+use crate::app::App;
+use crate::circles_app::camera::PerspectiveCamera;
+use crate::circles_app::generators;
+use crate::circles_app::scalar::{Scalar, Vector3};
+use crate::circles_app::sphere_simulation::SphereSimulation;
+use crate::vulkan::present::WindowData;
+use crate::vulkan::render::sphere_geometry;
+use crate::vulkan::render::sphere_pipeline::ViewProjection;
+use crate::vulkan::Vulkan;
+use raw_window_handle::HasRawWindowHandle;
+use slog::Logger;
+use std::time::{Duration, Instant};
+use winit::dpi::{PhysicalSize, Size};
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
+use winit::event_loop::{EventLoop, EventLoopWindowTarget};
+use winit::window::{Window, WindowBuilder, WindowId};
+
+const ORBIT_STEP: f32 = 0.1;
+const DOLLY_STEP: f32 = 0.9;
+const MAX_STEP: Duration = Duration::from_millis(20);
+const GRAVITY: Scalar = 9.81;
+
+/// 3D mode: spheres in a box seen through a perspective camera.
+pub struct SpheresApp {
+    simulation: SphereSimulation,
+    camera: PerspectiveCamera,
+    previous_update: Instant,
+    paused: bool,
+    logger: Logger,
+    vk: Vulkan,
+    window: Window,
+}
+
+impl SpheresApp {
+    /// `field_size` is in metres.
+    pub fn new(logger: Logger, field_size: Vector3, event_loop: &EventLoop<()>) -> Self {
+        let window = WindowBuilder::new()
+            .with_title("Spheres")
+            .with_inner_size(Size::Physical(PhysicalSize::new(800, 600)))
+            .build(event_loop)
+            .expect("Can't create spheres window");
+        let window_data = WindowData {
+            window_handle: window.raw_window_handle(),
+            width: window.inner_size().width,
+            height: window.inner_size().height,
+        };
+        let mut vk = Vulkan::new("Spheres", window_data, logger.clone());
+        vk.set_geometry(&[], &[]);
+
+        let mut simulation = SphereSimulation::new(field_size, logger.clone());
+        for sphere in generators::random_spheres(field_size, 300, (0.1, 0.25), 1.0, 0) {
+            simulation.add_sphere(sphere);
+        }
+
+        Self {
+            simulation,
+            camera: PerspectiveCamera::fit(field_size),
+            previous_update: Instant::now(),
+            paused: false,
+            logger,
+            vk,
+            window,
+        }
+    }
+
+    pub fn camera(&self) -> PerspectiveCamera {
+        self.camera
+    }
+
+    pub fn set_camera(&mut self, camera: PerspectiveCamera) {
+        self.camera = camera;
+    }
+
+    fn toggle_gravity(&mut self) {
+        let gravity = if self.simulation.gravity() == Vector3::zero() {
+            Vector3::new(0.0, GRAVITY, 0.0)
+        } else {
+            Vector3::zero()
+        };
+        self.simulation.set_gravity(gravity);
+    }
+
+    fn aspect_ratio(&self) -> f32 {
+        let resolution = self.vk.get_resolution();
+        resolution.width as f32 / resolution.height as f32
+    }
+}
+
+impl App for SpheresApp {
+    type Event = KeyboardInput;
+
+    fn process_event(&mut self, event: &Self::Event, _wt: &EventLoopWindowTarget<()>) -> Status {
+        if event.state != ElementState::Pressed {
+            return Status::Run;
+        }
+        match event.virtual_keycode {
+            Some(VirtualKeyCode::Left) => self.camera.orbit(-ORBIT_STEP),
+            Some(VirtualKeyCode::Right) => self.camera.orbit(ORBIT_STEP),
+            Some(VirtualKeyCode::Up) => self.camera.dolly(DOLLY_STEP),
+            Some(VirtualKeyCode::Down) => self.camera.dolly(1f32 / DOLLY_STEP),
+            Some(VirtualKeyCode::Space) => self.paused = !self.paused,
+            Some(VirtualKeyCode::G) => self.toggle_gravity(),
+            _ => {}
+        }
+        Status::Run
+    }
+
+    fn update(&mut self, _wt: &EventLoopWindowTarget<()>) -> Status {
+        let now = Instant::now();
+        // Long frames would let fast spheres tunnel through walls.
+        let elapsed_time = (now - self.previous_update).min(MAX_STEP);
+        self.previous_update = now;
+        if !self.paused {
+            self.simulation.step(elapsed_time);
+            trace!(
+                self.logger,
+                "Sphere kinetic energy: {}",
+                self.simulation.kinetic_energy()
+            );
+        }
+        std::thread::sleep(Duration::from_millis(15));
+        self.window.request_redraw();
+        Status::Run
+    }
+
+    fn draw(&mut self, _window_id: WindowId) {
+        let instances = sphere_geometry::sphere_instances(self.simulation.spheres().iter());
+        let view_projection = ViewProjection {
+            view: self.camera.view(),
+            projection: self.camera.projection(self.aspect_ratio()),
+        };
+        self.vk.set_spheres(&instances, view_projection);
+        self.vk.render();
+    }
+}
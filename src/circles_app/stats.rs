@@ -0,0 +1,206 @@
+use crate::circles_app::circle::Circle;
+use crate::circles_app::scalar::{seconds, Scalar};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Averages momentum transferred to the walls over time windows.
+/// Pressure in 2D is force per unit of wall length.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PressureGauge {
+    window: Duration,
+    elapsed: Duration,
+    impulse: Scalar,
+    pressure: Scalar,
+}
+
+impl PressureGauge {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            elapsed: Duration::from_secs(0),
+            impulse: 0.0,
+            pressure: 0.0,
+        }
+    }
+
+    pub fn add_impulse(&mut self, impulse: Scalar) {
+        self.impulse += impulse;
+    }
+
+    pub fn advance(&mut self, elapsed_time: Duration, perimeter: Scalar) {
+        self.elapsed += elapsed_time;
+        if self.elapsed >= self.window {
+            self.pressure = self.impulse / (perimeter * seconds(self.elapsed));
+            self.impulse = 0.0;
+            self.elapsed = Duration::from_secs(0);
+        }
+    }
+
+    /// Pressure averaged over the last complete window.
+    pub fn pressure(&self) -> Scalar {
+        self.pressure
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct SpeciesStats {
+    pub count: usize,
+    pub kinetic_energy: Scalar,
+    pub temperature: Scalar,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observables {
+    pub count: usize,
+    pub kinetic_energy: Scalar,
+    pub temperature: Scalar,
+    pub pressure: Scalar,
+    pub species: BTreeMap<u32, SpeciesStats>,
+}
+
+impl Observables {
+    pub fn measure(circles: &[Circle], pressure: Scalar) -> Self {
+        let mut species: BTreeMap<u32, SpeciesStats> = BTreeMap::new();
+        for circle in circles {
+            let stats = species.entry(circle.species()).or_default();
+            stats.count += 1;
+            stats.kinetic_energy += circle.kinetic_energy();
+        }
+        for stats in species.values_mut() {
+            stats.temperature = stats.kinetic_energy / stats.count as Scalar;
+        }
+
+        let count = circles.len();
+        let kinetic_energy = species.values().map(|s| s.kinetic_energy).sum();
+        let temperature = if count > 0 {
+            kinetic_energy / count as Scalar
+        } else {
+            0.0
+        };
+        Self {
+            count,
+            kinetic_energy,
+            temperature,
+            pressure,
+            species,
+        }
+    }
+}
+
+/// Speed histogram with expected counts from 2D Maxwell-Boltzmann distribution
+/// `f(v) = m v / T * exp(-m v^2 / 2T)`, summed over circles masses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeedHistogram {
+    pub bin_width: Scalar,
+    pub counts: Vec<usize>,
+    pub expected: Vec<Scalar>,
+}
+
+impl SpeedHistogram {
+    pub fn new(circles: &[Circle], bins: usize, max_speed: Scalar, temperature: Scalar) -> Self {
+        let bin_width = max_speed / bins as Scalar;
+        let mut counts = vec![0; bins];
+        let mut expected = vec![0.0; bins];
+        for circle in circles {
+            let bin = (circle.speed().length() / bin_width) as usize;
+            if bin < bins {
+                counts[bin] += 1;
+            }
+            if temperature > 0.0 {
+                let cdf = |v: Scalar| 1.0 - (-circle.mass() * v * v / (2.0 * temperature)).exp();
+                for (bin, expected) in expected.iter_mut().enumerate() {
+                    let low = bin as Scalar * bin_width;
+                    *expected += cdf(low + bin_width) - cdf(low);
+                }
+            }
+        }
+        Self {
+            bin_width,
+            counts,
+            expected,
+        }
+    }
+
+    /// Pearson's chi-squared statistic over bins with non-negligible expected counts.
+    pub fn chi_squared(&self) -> Scalar {
+        self.counts
+            .iter()
+            .zip(&self.expected)
+            .filter(|(_, expected)| **expected > 1.0)
+            .map(|(&count, &expected)| (count as Scalar - expected).powi(2) / expected)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Observables, SpeedHistogram};
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::scalar::Scalar;
+    use crate::circles_app::simulation::Simulation;
+    use rand::{Rng, SeedableRng};
+    use rand_pcg::Pcg64Mcg;
+    use slog::{Discard, Logger};
+    use std::time::Duration;
+
+    #[test]
+    fn per_species_energy() {
+        let mut a = Circle::new((0.0, 0.0).into(), 1.0, (2.0, 0.0).into());
+        a.set_species(1);
+        let b = Circle::new((0.0, 0.0).into(), 1.0, (0.0, 1.0).into());
+        let observables = Observables::measure(&[a, b], 0.0);
+        assert_eq!(observables.species[&1].kinetic_energy, a.kinetic_energy());
+        assert_eq!(observables.species[&0].kinetic_energy, b.kinetic_energy());
+        assert_eq!(
+            observables.temperature,
+            (a.kinetic_energy() + b.kinetic_energy()) / 2.0
+        );
+    }
+
+    #[test]
+    fn ideal_gas_pressure() {
+        let size = 100.0;
+        let mut sim = Simulation::new((size, size).into(), Logger::root(Discard, o!()));
+        let mut rng = Pcg64Mcg::seed_from_u64(3);
+        for _ in 0..200 {
+            let center = (rng.gen_range(1.0, 99.0), rng.gen_range(1.0, 99.0)).into();
+            let speed = (rng.gen_range(-20.0, 20.0), rng.gen_range(-20.0, 20.0)).into();
+            sim.add_circle(Circle::new(center, 0.1, speed));
+        }
+        sim.set_pressure_window(Duration::from_secs(20));
+        for _ in 0..2000 {
+            sim.step(Duration::from_millis(10));
+        }
+
+        let observables = sim.observables();
+        let ideal = observables.count as Scalar * observables.temperature / (size * size);
+        assert!(
+            (observables.pressure - ideal).abs() < 0.1 * ideal,
+            "pressure {}, ideal {}",
+            observables.pressure,
+            ideal
+        );
+    }
+
+    #[test]
+    fn histogram_matches_maxwell_boltzmann() {
+        let mut rng = Pcg64Mcg::seed_from_u64(5);
+        let temperature = 4.0;
+        let circles: Vec<_> = (0..2000)
+            .map(|_| {
+                let mut circle = Circle::new((0.0, 0.0).into(), 1.0, (0.0, 0.0).into());
+                let sigma = (temperature / circle.mass()).sqrt();
+                let speed = (
+                    rng.sample::<Scalar, _>(rand_distr::StandardNormal) * sigma,
+                    rng.sample::<Scalar, _>(rand_distr::StandardNormal) * sigma,
+                );
+                circle.set_speed(speed.into());
+                circle
+            })
+            .collect();
+
+        let histogram = SpeedHistogram::new(&circles, 20, 5.0, temperature);
+        assert!(histogram.chi_squared() < 40.0, "{:?}", histogram);
+    }
+}
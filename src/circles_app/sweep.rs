@@ -0,0 +1,645 @@
+use crate::circles_app::circle::Circle;
+use crate::circles_app::events::CollisionEvent;
+use crate::circles_app::generators;
+use crate::circles_app::headless::{self, RunLength};
+use crate::circles_app::material::Material;
+use crate::circles_app::scalar::{duration, widen, Scalar};
+use crate::circles_app::scene::{Scene, SceneError};
+use crate::circles_app::simulation::Simulation;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use slog::Logger;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const JOURNAL_FILE: &str = "runs.csv";
+pub const RESULTS_FILE: &str = "results.csv";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Parameter {
+    /// Overrides restitution of every circle.
+    Restitution(Vec<Scalar>),
+    /// Overrides density of every circle.
+    Density(Vec<Scalar>),
+    /// Number of circles. Without `generate`, the first circles of the scene are kept.
+    Count(Vec<usize>),
+}
+
+impl Parameter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Parameter::Restitution(_) => "restitution",
+            Parameter::Density(_) => "density",
+            Parameter::Count(_) => "count",
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Parameter::Restitution(values) | Parameter::Density(values) => values.len(),
+            Parameter::Count(values) => values.len(),
+        }
+    }
+
+    fn value(&self, index: usize) -> f64 {
+        match self {
+            Parameter::Restitution(values) | Parameter::Density(values) => widen(values[index]),
+            Parameter::Count(values) => values[index] as f64,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Observable {
+    KineticEnergy,
+    Temperature,
+    Pressure,
+    Population,
+    PackingFraction,
+    /// Contact begins per second of simulated time.
+    CollisionRate,
+}
+
+impl Observable {
+    pub fn name(self) -> &'static str {
+        match self {
+            Observable::KineticEnergy => "kinetic_energy",
+            Observable::Temperature => "temperature",
+            Observable::Pressure => "pressure",
+            Observable::Population => "population",
+            Observable::PackingFraction => "packing_fraction",
+            Observable::CollisionRate => "collision_rate",
+        }
+    }
+}
+
+/// Circles regenerated for every run with the run seed, so seeds give independent initial states.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Generate {
+    pub radius: (Scalar, Scalar),
+    pub speed: Scalar,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SweepSpec {
+    /// Relative to the spec file.
+    pub scene: PathBuf,
+    pub steps: u64,
+    /// Timestep in seconds.
+    pub dt: Scalar,
+    /// Runs per parameter point, with seeds `0..seeds`.
+    pub seeds: u64,
+    #[serde(default)]
+    pub generate: Option<Generate>,
+    /// Grid is the cartesian product of all values. The first parameter changes slowest.
+    #[serde(default)]
+    pub parameters: Vec<Parameter>,
+    pub observables: Vec<Observable>,
+}
+
+impl SweepSpec {
+    pub fn from_ron(text: &str) -> Result<Self, SweepError> {
+        let spec: Self = ron::de::from_str(text).map_err(SweepError::Spec)?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    fn validate(&self) -> Result<(), SweepError> {
+        if !(self.dt > 0.0 && self.dt.is_finite()) {
+            return Err(SweepError::Invalid(format!(
+                "dt must be a positive number, got {}",
+                self.dt
+            )));
+        }
+        if self.seeds == 0 {
+            return Err(SweepError::Invalid("seeds must be at least 1".to_string()));
+        }
+        if let Some(parameter) = self.parameters.iter().find(|p| p.len() == 0) {
+            return Err(SweepError::Invalid(format!(
+                "{} has no values",
+                parameter.name()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Scene path is resolved against the spec directory.
+    pub fn load(path: &Path) -> Result<Self, SweepError> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        let mut spec = Self::from_ron(&text)?;
+        if let Some(dir) = path.parent() {
+            spec.scene = dir.join(&spec.scene);
+        }
+        Ok(spec)
+    }
+
+    pub fn points(&self) -> usize {
+        self.parameters.iter().map(Parameter::len).product()
+    }
+
+    /// Parameter values of the grid point.
+    pub fn point(&self, point: usize) -> Vec<f64> {
+        let indices = self.indices(point);
+        let values = self.parameters.iter().zip(indices);
+        values.map(|(parameter, i)| parameter.value(i)).collect()
+    }
+
+    /// Index into values of every parameter.
+    fn indices(&self, mut point: usize) -> Vec<usize> {
+        let mut indices = vec![0; self.parameters.len()];
+        for (i, parameter) in self.parameters.iter().enumerate().rev() {
+            indices[i] = point % parameter.len();
+            point /= parameter.len();
+        }
+        indices
+    }
+
+    fn header(&self) -> Vec<&'static str> {
+        let parameters = self.parameters.iter().map(Parameter::name);
+        let observables = self.observables.iter().map(|o| o.name());
+        parameters.chain(observables).collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum SweepError {
+    Io(io::Error),
+    Spec(ron::Error),
+    /// Spec parsed but its values can't be run.
+    Invalid(String),
+    Scene(SceneError),
+    /// Existing journal was written for a different spec.
+    Journal(String),
+    ThreadPool(rayon::ThreadPoolBuildError),
+}
+
+impl Error for SweepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SweepError::Io(e) => Some(e),
+            SweepError::Spec(e) => Some(e),
+            SweepError::Scene(e) => Some(e),
+            SweepError::ThreadPool(e) => Some(e),
+            SweepError::Invalid(_) | SweepError::Journal(_) => None,
+        }
+    }
+}
+
+impl Display for SweepError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            SweepError::Io(e) => Display::fmt(e, f),
+            SweepError::Spec(e) => write!(f, "Sweep spec: {}", e),
+            SweepError::Invalid(e) => write!(f, "Sweep spec: {}", e),
+            SweepError::Scene(e) => write!(f, "Scene: {}", e),
+            SweepError::Journal(e) => write!(f, "Journal: {}", e),
+            SweepError::ThreadPool(e) => Display::fmt(e, f),
+        }
+    }
+}
+
+impl From<io::Error> for SweepError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<SceneError> for SweepError {
+    fn from(e: SceneError) -> Self {
+        Self::Scene(e)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Estimate {
+    pub mean: f64,
+    /// Half-width of the 95% confidence interval of the mean. NaN for a single run.
+    pub ci95: f64,
+}
+
+impl Estimate {
+    pub fn from_samples(samples: &[f64]) -> Self {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        if samples.len() < 2 {
+            return Self {
+                mean,
+                ci95: f64::NAN,
+            };
+        }
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1f64);
+        Self {
+            mean,
+            ci95: student_t95(samples.len() - 1) * (variance / n).sqrt(),
+        }
+    }
+}
+
+/// Two-sided 95% quantile of Student's t distribution.
+fn student_t95(degrees_of_freedom: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+    TABLE
+        .get(degrees_of_freedom.wrapping_sub(1))
+        .copied()
+        .unwrap_or(1.960)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointSummary {
+    pub parameters: Vec<f64>,
+    pub runs: usize,
+    /// In order of spec observables.
+    pub estimates: Vec<Estimate>,
+}
+
+/// Runs every (point, seed) pair not yet in the journal of `output_dir`, then writes the results
+/// table. Completed runs are appended to the journal immediately, so an interrupted sweep
+/// continues where it stopped when started again with the same spec. Journaled runs whose
+/// parameter values no longer match the spec are run again.
+pub fn run(
+    spec: &SweepSpec,
+    output_dir: &Path,
+    threads: Option<usize>,
+    logger: Logger,
+) -> Result<Vec<PointSummary>, SweepError> {
+    let scene = Scene::load(&spec.scene)?;
+    std::fs::create_dir_all(output_dir)?;
+    let journal_path = output_dir.join(JOURNAL_FILE);
+    let mut results = read_journal(spec, &journal_path)?;
+
+    let pending: Vec<(usize, u64)> = (0..spec.points())
+        .flat_map(|point| (0..spec.seeds).map(move |seed| (point, seed)))
+        .filter(|key| !results.contains_key(key))
+        .collect();
+    info!(
+        logger,
+        "Sweep: {} points x {} seeds, {} runs done, {} pending",
+        spec.points(),
+        spec.seeds,
+        results.len(),
+        pending.len()
+    );
+
+    let journal = Mutex::new(open_journal(spec, &journal_path)?);
+    let mut builder = rayon::ThreadPoolBuilder::new();
+    if let Some(threads) = threads {
+        builder = builder.num_threads(threads);
+    }
+    let pool = builder.build().map_err(SweepError::ThreadPool)?;
+    let completed: Vec<((usize, u64), Vec<f64>)> = pool.install(|| {
+        pending
+            .par_iter()
+            .map(|&(point, seed)| {
+                let values = run_point(spec, &scene, point, seed, logger.clone());
+                let mut journal = journal.lock().expect("Journal lock is poisoned");
+                write_journal_line(&mut *journal, point, seed, &spec.point(point), &values)?;
+                debug!(logger, "Run done: point {}, seed {}", point, seed);
+                Ok(((point, seed), values))
+            })
+            .collect::<io::Result<_>>()
+    })?;
+    results.extend(completed);
+
+    let summaries = summarize(spec, &results);
+    write_results(spec, &summaries, &output_dir.join(RESULTS_FILE))?;
+    info!(logger, "Sweep results written to {:?}", output_dir);
+    Ok(summaries)
+}
+
+/// Simulation of one grid point with given seed, before any step.
+pub fn prepare(
+    spec: &SweepSpec,
+    scene: &Scene,
+    point: usize,
+    seed: u64,
+    logger: Logger,
+) -> Simulation {
+    let mut base = scene.clone();
+    base.circles.clear();
+    let mut simulation = base.to_simulation(logger.clone());
+    simulation.set_seed(seed);
+
+    let indices = spec.indices(point);
+    let count = spec
+        .parameters
+        .iter()
+        .zip(&indices)
+        .find_map(|(parameter, &i)| match parameter {
+            Parameter::Count(values) => Some(values[i]),
+            _ => None,
+        });
+    let mut circles: Vec<Circle> = match spec.generate {
+        Some(generate) => generators::poisson_disk(
+            simulation.field_size(),
+            count.unwrap_or(scene.circles.len()),
+            generate.radius,
+            generate.speed,
+            seed,
+        ),
+        None => {
+            let full = scene.to_simulation(logger);
+            let circles = full.circles().map(|(_, circle)| *circle);
+            circles.take(count.unwrap_or(usize::MAX)).collect()
+        }
+    };
+    for (parameter, &index) in spec.parameters.iter().zip(&indices) {
+        for circle in &mut circles {
+            let material = circle.material();
+            match parameter {
+                Parameter::Restitution(values) => {
+                    circle.set_material(Material::new(material.density, values[index]))
+                }
+                Parameter::Density(values) => {
+                    circle.set_material(Material::new(values[index], material.restitution))
+                }
+                Parameter::Count(_) => {}
+            }
+        }
+    }
+    for circle in circles {
+        simulation.add_circle(circle);
+    }
+    simulation
+}
+
+fn run_point(spec: &SweepSpec, scene: &Scene, point: usize, seed: u64, logger: Logger) -> Vec<f64> {
+    let mut simulation = prepare(spec, scene, point, seed, logger);
+    let mut collisions = 0u64;
+    let dt = duration(spec.dt);
+    let counted: Result<(), ()> =
+        headless::advance(&mut simulation, RunLength::Steps(spec.steps), dt, |sim| {
+            for event in sim.events().drain() {
+                if let CollisionEvent::ContactBegin(_) = event {
+                    collisions += 1;
+                }
+            }
+            Ok(())
+        });
+    counted.expect("Counting collisions can't fail");
+
+    let observables = simulation.observables();
+    let time = simulation.time().as_secs_f64();
+    spec.observables
+        .iter()
+        .map(|observable| match observable {
+            Observable::KineticEnergy => widen(observables.kinetic_energy),
+            Observable::Temperature => widen(observables.temperature),
+            Observable::Pressure => widen(observables.pressure),
+            Observable::Population => observables.count as f64,
+            Observable::PackingFraction => widen(simulation.packing_fraction()),
+            Observable::CollisionRate if time > 0f64 => collisions as f64 / time,
+            Observable::CollisionRate => 0f64,
+        })
+        .collect()
+}
+
+fn journal_header(spec: &SweepSpec) -> String {
+    let mut header = vec!["point", "seed"];
+    header.extend(spec.header());
+    header.join(",")
+}
+
+fn read_journal(
+    spec: &SweepSpec,
+    path: &Path,
+) -> Result<BTreeMap<(usize, u64), Vec<f64>>, SweepError> {
+    let mut results = BTreeMap::new();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(results),
+        Err(e) => return Err(e.into()),
+    };
+    let mut lines = BufReader::new(file).lines();
+    match lines.next().transpose()? {
+        Some(header) if header == journal_header(spec) => {}
+        Some(_) => {
+            return Err(SweepError::Journal(format!(
+                "{:?} was written for another sweep spec",
+                path
+            )))
+        }
+        None => return Ok(results),
+    }
+    let skip = 2 + spec.parameters.len();
+    for line in lines {
+        let line = line?;
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() != skip + spec.observables.len() {
+            // Line cut by interruption. The run is repeated.
+            continue;
+        }
+        let point = fields[0].parse::<usize>();
+        let seed = fields[1].parse::<u64>();
+        let parameters: Result<Vec<f64>, _> = fields[2..skip].iter().map(|v| v.parse()).collect();
+        let values: Result<Vec<f64>, _> = fields[skip..].iter().map(|v| v.parse()).collect();
+        if let (Ok(point), Ok(seed), Ok(parameters), Ok(values)) = (point, seed, parameters, values)
+        {
+            // Runs of parameter values since changed in the spec are repeated.
+            if point < spec.points() && seed < spec.seeds && parameters == spec.point(point) {
+                results.insert((point, seed), values);
+            }
+        }
+    }
+    Ok(results)
+}
+
+fn open_journal(spec: &SweepSpec, path: &Path) -> io::Result<BufWriter<File>> {
+    let existing = std::fs::read(path).unwrap_or_default();
+    let mut journal = BufWriter::new(OpenOptions::new().create(true).append(true).open(path)?);
+    if existing.is_empty() {
+        writeln!(journal, "{}", journal_header(spec))?;
+    } else if existing.last() != Some(&b'\n') {
+        writeln!(journal)?;
+    }
+    journal.flush()?;
+    Ok(journal)
+}
+
+fn write_journal_line<W: Write>(
+    journal: &mut W,
+    point: usize,
+    seed: u64,
+    parameters: &[f64],
+    values: &[f64],
+) -> io::Result<()> {
+    let columns: Vec<String> = parameters
+        .iter()
+        .chain(values)
+        .map(|value| value.to_string())
+        .collect();
+    writeln!(journal, "{},{},{}", point, seed, columns.join(","))?;
+    journal.flush()
+}
+
+fn summarize(spec: &SweepSpec, results: &BTreeMap<(usize, u64), Vec<f64>>) -> Vec<PointSummary> {
+    let points: BTreeSet<usize> = results.keys().map(|(point, _)| *point).collect();
+    points
+        .into_iter()
+        .map(|point| {
+            let runs: Vec<&Vec<f64>> = results
+                .range((point, 0)..=(point, u64::MAX))
+                .map(|(_, values)| values)
+                .collect();
+            let estimates = (0..spec.observables.len())
+                .map(|i| {
+                    let samples: Vec<f64> = runs.iter().map(|values| values[i]).collect();
+                    Estimate::from_samples(&samples)
+                })
+                .collect();
+            PointSummary {
+                parameters: spec.point(point),
+                runs: runs.len(),
+                estimates,
+            }
+        })
+        .collect()
+}
+
+fn write_results(spec: &SweepSpec, summaries: &[PointSummary], path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut header: Vec<String> = spec
+        .parameters
+        .iter()
+        .map(|p| p.name().to_string())
+        .collect();
+    header.push("runs".to_string());
+    for observable in &spec.observables {
+        header.push(format!("{}_mean", observable.name()));
+        header.push(format!("{}_ci95", observable.name()));
+    }
+    writeln!(writer, "{}", header.join(","))?;
+    for summary in summaries {
+        let mut row: Vec<String> = summary.parameters.iter().map(f64::to_string).collect();
+        row.push(summary.runs.to_string());
+        for estimate in &summary.estimates {
+            row.push(estimate.mean.to_string());
+            row.push(estimate.ci95.to_string());
+        }
+        writeln!(writer, "{}", row.join(","))?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, Estimate, SweepError, SweepSpec, JOURNAL_FILE, RESULTS_FILE};
+    use crate::circles_app::scene::Scene;
+    use assert_approx_eq::assert_approx_eq;
+    use slog::{Discard, Logger};
+    use std::path::PathBuf;
+
+    const SPEC: &str = "(
+        scene: \"scene.ron\",
+        steps: 20,
+        dt: 0.01,
+        seeds: 3,
+        generate: Some((radius: (2, 4), speed: 30)),
+        parameters: [Restitution([0.5, 1.0]), Count([10, 20])],
+        observables: [KineticEnergy, Population, CollisionRate],
+    )";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("circles-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Scene::new((100.0, 100.0))
+            .save(&dir.join("scene.ron"))
+            .unwrap();
+        dir
+    }
+
+    #[test]
+    fn estimate_of_samples() {
+        let estimate = Estimate::from_samples(&[1f64, 2f64, 3f64, 4f64]);
+        assert_approx_eq!(estimate.mean, 2.5f64);
+        // t(3) * s / sqrt(n) = 3.182 * 1.291 / 2
+        assert_approx_eq!(estimate.ci95, 2.0540, 1e-3);
+        assert!(Estimate::from_samples(&[1f64]).ci95.is_nan());
+    }
+
+    #[test]
+    fn invalid_specs_are_rejected() {
+        assert!(SweepSpec::from_ron(SPEC).is_ok());
+        for invalid in &[
+            SPEC.replace("dt: 0.01", "dt: -0.01"),
+            SPEC.replace("dt: 0.01", "dt: NaN"),
+            SPEC.replace("seeds: 3", "seeds: 0"),
+            SPEC.replace("Count([10, 20])", "Count([])"),
+        ] {
+            match SweepSpec::from_ron(invalid) {
+                Err(SweepError::Invalid(_)) => {}
+                other => panic!("{:?} for {}", other, invalid),
+            }
+        }
+    }
+
+    #[test]
+    fn sweep_covers_grid_and_resumes() {
+        let dir = temp_dir("sweep");
+        std::fs::write(dir.join("spec.ron"), SPEC).unwrap();
+        let spec = SweepSpec::load(&dir.join("spec.ron")).unwrap();
+        assert_eq!(spec.points(), 4);
+        assert_eq!(spec.point(1), vec![0.5f64, 20f64]);
+
+        let output = dir.join("out");
+        let logger = Logger::root(Discard, o!());
+        let summaries = run(&spec, &output, Some(2), logger.clone()).unwrap();
+        assert_eq!(summaries.len(), 4);
+        for summary in &summaries {
+            assert_eq!(summary.runs, 3);
+            assert_approx_eq!(summary.estimates[1].mean, summary.parameters[1]);
+        }
+        let results = std::fs::read_to_string(output.join(RESULTS_FILE)).unwrap();
+        assert_eq!(
+            results.lines().next().unwrap(),
+            "restitution,count,runs,kinetic_energy_mean,kinetic_energy_ci95,\
+             population_mean,population_ci95,collision_rate_mean,collision_rate_ci95"
+        );
+        assert_eq!(results.lines().count(), 5);
+
+        // Interrupted sweep: last run lost, the one before cut in the middle of a line.
+        let journal = std::fs::read_to_string(output.join(JOURNAL_FILE)).unwrap();
+        let lines: Vec<&str> = journal.lines().collect();
+        assert_eq!(lines.len(), 1 + 12);
+        let cut = &lines[11][..lines[11].len() / 2];
+        std::fs::write(
+            output.join(JOURNAL_FILE),
+            format!("{}\n{}", lines[..11].join("\n"), cut),
+        )
+        .unwrap();
+        let resumed = run(&spec, &output, Some(2), logger).unwrap();
+        assert_eq!(resumed, summaries);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn changed_parameter_values_are_run_again() {
+        let dir = temp_dir("sweep-changed");
+        let output = dir.join("out");
+        let logger = Logger::root(Discard, o!());
+        std::fs::write(dir.join("spec.ron"), SPEC).unwrap();
+        let spec = SweepSpec::load(&dir.join("spec.ron")).unwrap();
+        run(&spec, &output, Some(2), logger.clone()).unwrap();
+
+        let changed = SPEC.replace("Count([10, 20])", "Count([10, 30])");
+        std::fs::write(dir.join("spec.ron"), changed).unwrap();
+        let spec = SweepSpec::load(&dir.join("spec.ron")).unwrap();
+        let summaries = run(&spec, &output, Some(2), logger).unwrap();
+        assert_eq!(summaries.len(), 4);
+        for summary in &summaries {
+            assert_eq!(summary.runs, 3);
+            assert_approx_eq!(summary.estimates[1].mean, summary.parameters[1]);
+        }
+        assert_eq!(summaries[1].parameters, vec![0.5f64, 30f64]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use crate::circles_app::circle::Circle;
+use crate::circles_app::scalar::{seconds, Scalar, Vector};
+use rand::Rng;
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Temperature is measured in energy units with Boltzmann constant of 1.
+/// Circles have 2 degrees of freedom each, so `kinetic energy = N * T`.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Thermostat {
+    /// Rescales all velocities toward `target` with relaxation time `tau`.
+    /// Steps not shorter than `tau`, and any step with zero `tau`, reach `target` at once.
+    Berendsen { target: Scalar, tau: Duration },
+    /// Resamples velocity of each circle from Maxwell-Boltzmann distribution
+    /// with `frequency` resamplings per circle per second.
+    Andersen { target: Scalar, frequency: Scalar },
+    /// Applies damping and random Brownian kicks balancing each other at `target`.
+    Langevin { target: Scalar, damping: Scalar },
+}
+
+impl Thermostat {
+    pub fn target(&self) -> Scalar {
+        match *self {
+            Thermostat::Berendsen { target, .. } => target,
+            Thermostat::Andersen { target, .. } => target,
+            Thermostat::Langevin { target, .. } => target,
+        }
+    }
+
+    pub fn apply<R: Rng>(&self, circles: &mut [Circle], elapsed_time: Duration, rng: &mut R) {
+        let dt = seconds(elapsed_time);
+        match *self {
+            Thermostat::Berendsen { target, tau } => {
+                let current = temperature(circles);
+                if current <= 0.0 {
+                    return;
+                }
+                let coupling = if dt >= seconds(tau) {
+                    1.0
+                } else {
+                    dt / seconds(tau)
+                };
+                let ratio = 1.0 + coupling * (target / current - 1.0);
+                let scale = ratio.max(0.0).sqrt();
+                for circle in circles {
+                    circle.set_speed(circle.speed() * scale);
+                }
+            }
+            Thermostat::Andersen { target, frequency } => {
+                let probability = (frequency * dt).min(1.0);
+                for circle in circles {
+                    if rng.gen::<Scalar>() < probability {
+                        let sigma = (target / circle.mass()).sqrt();
+                        circle.set_speed(normal_vec(rng) * sigma);
+                    }
+                }
+            }
+            Thermostat::Langevin { target, damping } => {
+                let decay = (-damping * dt).exp();
+                for circle in circles {
+                    let sigma = (target / circle.mass() * (1.0 - decay * decay)).sqrt();
+                    circle.set_speed(circle.speed() * decay + normal_vec(rng) * sigma);
+                }
+            }
+        }
+    }
+}
+
+pub fn temperature(circles: &[Circle]) -> Scalar {
+    if circles.is_empty() {
+        return 0.0;
+    }
+    let energy: Scalar = circles.iter().map(Circle::kinetic_energy).sum();
+    energy / circles.len() as Scalar
+}
+
+fn normal_vec<R: Rng>(rng: &mut R) -> Vector {
+    Vector::new(rng.sample(StandardNormal), rng.sample(StandardNormal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{temperature, Thermostat};
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::scalar::Scalar;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+    use std::time::Duration;
+
+    fn gas() -> Vec<Circle> {
+        (0..500)
+            .map(|i| {
+                let angle = i as Scalar;
+                let speed = (angle.cos() * 3.0, angle.sin() * 3.0).into();
+                Circle::new((0.0, 0.0).into(), 1.0, speed)
+            })
+            .collect()
+    }
+
+    fn relax(thermostat: Thermostat) -> Scalar {
+        let mut circles = gas();
+        let mut rng = Pcg64Mcg::seed_from_u64(7);
+        let dt = Duration::from_millis(10);
+        for _ in 0..2000 {
+            thermostat.apply(&mut circles, dt, &mut rng);
+        }
+        temperature(&circles)
+    }
+
+    #[test]
+    fn thermostats_reach_target() {
+        let target = 2.0;
+        let thermostats = [
+            Thermostat::Berendsen {
+                target,
+                tau: Duration::from_millis(100),
+            },
+            Thermostat::Andersen {
+                target,
+                frequency: 5.0,
+            },
+            Thermostat::Langevin {
+                target,
+                damping: 2.0,
+            },
+        ];
+        for thermostat in &thermostats {
+            let measured = relax(*thermostat);
+            assert!(
+                (measured - target).abs() < 0.15 * target,
+                "{:?} measured {}",
+                thermostat,
+                measured
+            );
+        }
+    }
+
+    #[test]
+    fn zero_tau_rescales_at_once() {
+        let mut circles = gas();
+        let mut rng = Pcg64Mcg::seed_from_u64(7);
+        let thermostat = Thermostat::Berendsen {
+            target: 2.0,
+            tau: Duration::from_secs(0),
+        };
+        thermostat.apply(&mut circles, Duration::from_millis(10), &mut rng);
+        assert!((temperature(&circles) - 2.0).abs() < 1e-3);
+        thermostat.apply(&mut circles, Duration::from_secs(0), &mut rng);
+        assert!((temperature(&circles) - 2.0).abs() < 1e-3);
+    }
+}
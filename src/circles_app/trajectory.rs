@@ -0,0 +1,450 @@
+use crate::circles_app::events::{CollisionEvent, RemoveCause, WallSide};
+use crate::circles_app::scalar::{Scalar, Vector};
+use crate::circles_app::simulation::Simulation;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Field {
+    Id,
+    Position,
+    Velocity,
+    Radius,
+    Mass,
+}
+
+impl Field {
+    pub const ALL: [Field; 5] = [
+        Field::Id,
+        Field::Position,
+        Field::Velocity,
+        Field::Radius,
+        Field::Mass,
+    ];
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Sampling {
+    /// Every n-th step.
+    Steps(u64),
+    /// First step after each interval of simulated time.
+    Interval(Duration),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportSettings {
+    pub format: ExportFormat,
+    /// Circle columns after step and time, in given order.
+    pub fields: Vec<Field>,
+    pub sampling: Sampling,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::Csv,
+            fields: Field::ALL.to_vec(),
+            sampling: Sampling::Steps(1),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Value<'a> {
+    Int(u64),
+    Scalar(Scalar),
+    F64(f64),
+    Text(&'a str),
+    Missing,
+}
+
+/// Writes rows as they come, so memory use doesn't depend on run length.
+struct RowWriter<W: Write> {
+    writer: W,
+    format: ExportFormat,
+    header_written: bool,
+}
+
+impl<W: Write> RowWriter<W> {
+    fn new(writer: W, format: ExportFormat) -> Self {
+        Self {
+            writer,
+            format,
+            header_written: false,
+        }
+    }
+
+    fn write(&mut self, row: &[(&str, Value)]) -> io::Result<()> {
+        match self.format {
+            ExportFormat::Csv => {
+                if !self.header_written {
+                    let names: Vec<&str> = row.iter().map(|(name, _)| *name).collect();
+                    writeln!(self.writer, "{}", names.join(","))?;
+                    self.header_written = true;
+                }
+                for (i, (_, value)) in row.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.writer, ",")?;
+                    }
+                    match value {
+                        Value::Int(v) => write!(self.writer, "{}", v)?,
+                        Value::Scalar(v) => write!(self.writer, "{}", v)?,
+                        Value::F64(v) => write!(self.writer, "{}", v)?,
+                        Value::Text(v) => write!(self.writer, "{}", v)?,
+                        Value::Missing => {}
+                    }
+                }
+                writeln!(self.writer)
+            }
+            ExportFormat::Ndjson => {
+                write!(self.writer, "{{")?;
+                for (i, (name, value)) in row.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.writer, ",")?;
+                    }
+                    write!(self.writer, "\"{}\":", name)?;
+                    match value {
+                        Value::Int(v) => write!(self.writer, "{}", v)?,
+                        Value::Scalar(v) if v.is_finite() => write!(self.writer, "{}", v)?,
+                        Value::F64(v) if v.is_finite() => write!(self.writer, "{}", v)?,
+                        Value::Text(v) => write!(self.writer, "\"{}\"", v)?,
+                        _ => write!(self.writer, "null")?,
+                    }
+                }
+                writeln!(self.writer, "}}")
+            }
+        }
+    }
+}
+
+/// Streams sampled circle states and all collision events into separate outputs.
+pub struct TrajectoryExporter<W: Write> {
+    settings: ExportSettings,
+    circles: RowWriter<W>,
+    events: Option<RowWriter<W>>,
+    last_sample: Option<(u64, Duration)>,
+}
+
+impl TrajectoryExporter<BufWriter<File>> {
+    pub fn create(
+        settings: ExportSettings,
+        circles_path: &Path,
+        events_path: Option<&Path>,
+    ) -> io::Result<Self> {
+        let circles = BufWriter::new(File::create(circles_path)?);
+        let events = match events_path {
+            Some(path) => Some(BufWriter::new(File::create(path)?)),
+            None => None,
+        };
+        Ok(Self::new(settings, circles, events))
+    }
+}
+
+impl<W: Write> TrajectoryExporter<W> {
+    pub fn new(settings: ExportSettings, circles: W, events: Option<W>) -> Self {
+        let format = settings.format;
+        Self {
+            settings,
+            circles: RowWriter::new(circles, format),
+            events: events.map(|events| RowWriter::new(events, format)),
+            last_sample: None,
+        }
+    }
+
+    pub fn settings(&self) -> &ExportSettings {
+        &self.settings
+    }
+
+    /// Writes circle states if a sample is due. Call after every step.
+    pub fn record(&mut self, simulation: &Simulation) -> io::Result<()> {
+        let (step, time) = (simulation.steps(), simulation.time());
+        let due = match (self.last_sample, self.settings.sampling) {
+            (None, _) => true,
+            (Some((last, _)), Sampling::Steps(every)) => step >= last + every.max(1),
+            (Some((_, last)), Sampling::Interval(interval)) => time >= last + interval,
+        };
+        if !due {
+            return Ok(());
+        }
+        self.last_sample = Some((step, time));
+
+        let mut row = Vec::with_capacity(2 + 2 * self.settings.fields.len());
+        for (handle, circle) in simulation.circles() {
+            row.clear();
+            row.push(("step", Value::Int(step)));
+            row.push(("time", Value::F64(time.as_secs_f64())));
+            for field in &self.settings.fields {
+                match field {
+                    Field::Id => row.push(("id", Value::Int(handle.id()))),
+                    Field::Position => push_vec(&mut row, ("x", "y"), circle.center()),
+                    Field::Velocity => push_vec(&mut row, ("vx", "vy"), circle.speed()),
+                    Field::Radius => row.push(("radius", Value::Scalar(circle.radius()))),
+                    Field::Mass => row.push(("mass", Value::Scalar(circle.mass()))),
+                }
+            }
+            self.circles.write(&row)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a `discontinuity` event row for a replaced simulation state, so rows before
+    /// and after it are not read as one trajectory, and records the new state at once.
+    pub fn mark_discontinuity(&mut self, simulation: &Simulation) -> io::Result<()> {
+        if let Some(events) = &mut self.events {
+            events.write(&[
+                ("step", Value::Int(simulation.steps())),
+                ("time", Value::F64(simulation.time().as_secs_f64())),
+                ("kind", Value::Text("discontinuity")),
+                ("circle", Value::Missing),
+                ("other", Value::Missing),
+                ("x", Value::Missing),
+                ("y", Value::Missing),
+                ("impulse", Value::Missing),
+                ("relative_speed", Value::Missing),
+            ])?;
+        }
+        self.last_sample = None;
+        self.record(simulation)
+    }
+
+    /// Events are written immediately. `step` and `time` are those of the step that emitted it.
+    pub fn write_event(
+        &mut self,
+        step: u64,
+        time: Duration,
+        event: &CollisionEvent,
+    ) -> io::Result<()> {
+        let events = match &mut self.events {
+            Some(events) => events,
+            None => return Ok(()),
+        };
+        let id = |id: u64| Value::Int(id);
+        let float = Value::Scalar;
+        let (kind, circle, other, point, impulse, relative_speed) = match event {
+            CollisionEvent::ContactBegin(c) | CollisionEvent::ContactPersist(c) => {
+                let kind = match event {
+                    CollisionEvent::ContactBegin(_) => "contact_begin",
+                    _ => "contact_persist",
+                };
+                let (a, b) = (id(c.a.id()), id(c.b.id()));
+                (
+                    kind,
+                    a,
+                    b,
+                    Some(c.point),
+                    float(c.impulse),
+                    float(c.relative_speed),
+                )
+            }
+            CollisionEvent::ContactEnd { a, b } => {
+                let (a, b) = (id(a.id()), id(b.id()));
+                ("contact_end", a, b, None, Value::Missing, Value::Missing)
+            }
+            CollisionEvent::WallHit(hit) => {
+                let kind = match hit.side {
+                    WallSide::Left => "wall_left",
+                    WallSide::Right => "wall_right",
+                    WallSide::Top => "wall_top",
+                    WallSide::Bottom => "wall_bottom",
+                };
+                let circle = id(hit.circle.id());
+                let (impulse, speed) = (float(hit.impulse), float(hit.relative_speed));
+                (
+                    kind,
+                    circle,
+                    Value::Missing,
+                    Some(hit.point),
+                    impulse,
+                    speed,
+                )
+            }
+            CollisionEvent::ObstacleHit(hit) => {
+                let (circle, obstacle) = (id(hit.circle.id()), id(hit.obstacle.id()));
+                let (impulse, speed) = (float(hit.impulse), float(hit.relative_speed));
+                (
+                    "obstacle",
+                    circle,
+                    obstacle,
+                    Some(hit.point),
+                    impulse,
+                    speed,
+                )
+            }
+            CollisionEvent::SensorEnter { sensor, circle }
+            | CollisionEvent::SensorExit { sensor, circle } => {
+                let kind = match event {
+                    CollisionEvent::SensorEnter { .. } => "sensor_enter",
+                    _ => "sensor_exit",
+                };
+                let (circle, sensor) = (id(circle.id()), id(sensor.id()));
+                (kind, circle, sensor, None, Value::Missing, Value::Missing)
+            }
+            CollisionEvent::Spawned { emitter, circle } => {
+                let (circle, emitter) = (id(circle.id()), id(emitter.id()));
+                (
+                    "spawned",
+                    circle,
+                    emitter,
+                    None,
+                    Value::Missing,
+                    Value::Missing,
+                )
+            }
+            CollisionEvent::Removed { circle, cause } => {
+                let (kind, other) = match cause {
+                    RemoveCause::Sink(sink) => ("removed_sink", id(sink.id())),
+                    RemoveCause::Expired => ("removed_expired", Value::Missing),
+                    RemoveCause::Manual => ("removed_manual", Value::Missing),
+                };
+                let circle = id(circle.id());
+                (kind, circle, other, None, Value::Missing, Value::Missing)
+            }
+        };
+        let mut row = vec![
+            ("step", Value::Int(step)),
+            ("time", Value::F64(time.as_secs_f64())),
+            ("kind", Value::Text(kind)),
+            ("circle", circle),
+            ("other", other),
+        ];
+        match point {
+            Some(point) => push_vec(&mut row, ("x", "y"), point),
+            None => row.extend_from_slice(&[("x", Value::Missing), ("y", Value::Missing)]),
+        }
+        row.push(("impulse", impulse));
+        row.push(("relative_speed", relative_speed));
+        events.write(&row)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.circles.writer.flush()?;
+        if let Some(events) = &mut self.events {
+            events.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Flushes and returns circle and event outputs.
+    pub fn finish(mut self) -> io::Result<(W, Option<W>)> {
+        self.flush()?;
+        Ok((self.circles.writer, self.events.map(|events| events.writer)))
+    }
+}
+
+fn push_vec<'a>(row: &mut Vec<(&'a str, Value<'a>)>, names: (&'a str, &'a str), v: Vector) {
+    row.push((names.0, Value::Scalar(v.x())));
+    row.push((names.1, Value::Scalar(v.y())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExportFormat, ExportSettings, Field, Sampling, TrajectoryExporter};
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::simulation::Simulation;
+    use slog::{Discard, Logger};
+    use std::time::Duration;
+
+    fn run(settings: ExportSettings) -> (String, String) {
+        let mut sim = Simulation::new((100.0, 100.0).into(), Logger::root(Discard, o!()));
+        sim.add_circle(Circle::new((20.0, 50.0).into(), 5.0, (10.0, 0.0).into()));
+        sim.add_circle(Circle::new((40.0, 50.0).into(), 5.0, (-10.0, 0.0).into()));
+        let mut exporter = TrajectoryExporter::new(settings, Vec::new(), Some(Vec::new()));
+        for _ in 0..10 {
+            sim.step(Duration::from_millis(100));
+            let (step, time) = (sim.steps(), sim.time());
+            for event in sim.events().drain() {
+                exporter.write_event(step, time, &event).unwrap();
+            }
+            exporter.record(&sim).unwrap();
+        }
+        let (circles, events) = exporter.finish().unwrap();
+        (
+            String::from_utf8(circles).unwrap(),
+            String::from_utf8(events.unwrap()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn csv_has_selected_fields_and_sampled_rows() {
+        let (circles, events) = run(ExportSettings {
+            format: ExportFormat::Csv,
+            fields: vec![Field::Id, Field::Velocity],
+            sampling: Sampling::Steps(3),
+        });
+        let lines: Vec<&str> = circles.lines().collect();
+        assert_eq!(lines[0], "step,time,id,vx,vy");
+        // Steps 1, 4, 7 and 10, two circles each.
+        assert_eq!(lines.len(), 1 + 4 * 2);
+        assert!(lines[1].starts_with("1,0.1,0,10,0"), "{}", lines[1]);
+        assert!(lines[3].starts_with("4,"), "{}", lines[3]);
+
+        let header = events.lines().next().unwrap();
+        assert_eq!(
+            header,
+            "step,time,kind,circle,other,x,y,impulse,relative_speed"
+        );
+        assert!(
+            events
+                .lines()
+                .any(|line| line.contains(",contact_begin,0,1,31,50,")),
+            "{}",
+            events
+        );
+    }
+
+    #[test]
+    fn discontinuity_restarts_sampling() {
+        let settings = ExportSettings {
+            sampling: Sampling::Steps(10),
+            ..ExportSettings::default()
+        };
+        let mut sim = Simulation::new((100.0, 100.0).into(), Logger::root(Discard, o!()));
+        sim.add_circle(Circle::new((20.0, 50.0).into(), 5.0, (10.0, 0.0).into()));
+        let mut exporter = TrajectoryExporter::new(settings, Vec::new(), Some(Vec::new()));
+        let start = sim.state().clone();
+        sim.step(Duration::from_millis(100));
+        exporter.record(&sim).unwrap();
+        sim.set_state(start);
+        exporter.mark_discontinuity(&sim).unwrap();
+
+        let (circles, events) = exporter.finish().unwrap();
+        let circles = String::from_utf8(circles).unwrap();
+        let lines: Vec<&str> = circles.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("1,"), "{}", lines[1]);
+        assert!(lines[2].starts_with("0,0,"), "{}", lines[2]);
+        let events = String::from_utf8(events.unwrap()).unwrap();
+        assert_eq!(events.lines().nth(1), Some("0,0,discontinuity,,,,,,"));
+    }
+
+    #[test]
+    fn ndjson_rows_are_objects() {
+        let (circles, events) = run(ExportSettings {
+            format: ExportFormat::Ndjson,
+            fields: vec![Field::Position, Field::Mass],
+            sampling: Sampling::Interval(Duration::from_millis(450)),
+        });
+        let lines: Vec<&str> = circles.lines().collect();
+        // Steps 1, 6 (time 0.6 >= 0.1 + 0.45) two circles each.
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("{\"step\":1,\"time\":0.1,\"x\":"));
+        assert!(lines[0].contains("\"mass\":78.5"), "{}", lines[0]);
+        let end = events
+            .lines()
+            .find(|line| line.contains("contact_end"))
+            .unwrap();
+        assert!(end.contains("\"x\":null"), "{}", end);
+        assert!(events
+            .lines()
+            .all(|l| l.starts_with('{') && l.ends_with('}')));
+    }
+}
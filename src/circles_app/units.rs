@@ -0,0 +1,121 @@
+//! The simulation works in SI: metres, kilograms and seconds. Scenes may be written in other
+//! units and are converted on load. Density is areal, in mass per square length.
+use crate::circles_app::scalar::Scalar;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LengthUnit {
+    Millimetre,
+    Centimetre,
+    Metre,
+    Kilometre,
+}
+
+impl LengthUnit {
+    pub fn in_metres(self) -> Scalar {
+        match self {
+            LengthUnit::Millimetre => 1e-3,
+            LengthUnit::Centimetre => 1e-2,
+            LengthUnit::Metre => 1.0,
+            LengthUnit::Kilometre => 1e3,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MassUnit {
+    Gram,
+    Kilogram,
+    Tonne,
+}
+
+impl MassUnit {
+    pub fn in_kilograms(self) -> Scalar {
+        match self {
+            MassUnit::Gram => 1e-3,
+            MassUnit::Kilogram => 1.0,
+            MassUnit::Tonne => 1e3,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TimeUnit {
+    Millisecond,
+    Second,
+    Minute,
+}
+
+impl TimeUnit {
+    pub fn in_seconds(self) -> Scalar {
+        match self {
+            TimeUnit::Millisecond => 1e-3,
+            TimeUnit::Second => 1.0,
+            TimeUnit::Minute => 60.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Units {
+    pub length: LengthUnit,
+    pub mass: MassUnit,
+    pub time: TimeUnit,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self::SI
+    }
+}
+
+impl Units {
+    pub const SI: Units = Units {
+        length: LengthUnit::Metre,
+        mass: MassUnit::Kilogram,
+        time: TimeUnit::Second,
+    };
+
+    /// Factor converting lengths into metres, the methods below do it for derived quantities.
+    pub fn length(&self) -> Scalar {
+        self.length.in_metres()
+    }
+
+    pub fn speed(&self) -> Scalar {
+        self.length() / self.time.in_seconds()
+    }
+
+    /// Angles themselves are always in radians.
+    pub fn angular_speed(&self) -> Scalar {
+        1.0 / self.time.in_seconds()
+    }
+
+    pub fn acceleration(&self) -> Scalar {
+        self.speed() / self.time.in_seconds()
+    }
+
+    pub fn density(&self) -> Scalar {
+        self.mass.in_kilograms() / self.length().powi(2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LengthUnit, MassUnit, TimeUnit, Units};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn factors_to_si() {
+        let units = Units {
+            length: LengthUnit::Centimetre,
+            mass: MassUnit::Gram,
+            time: TimeUnit::Millisecond,
+        };
+        assert_approx_eq!(units.length(), 0.01);
+        assert_approx_eq!(units.speed(), 10.0);
+        assert_approx_eq!(units.angular_speed(), 1e3, 1e-2);
+        assert_approx_eq!(units.acceleration(), 1e4, 1e-1);
+        assert_approx_eq!(units.density(), 10.0, 1e-4);
+        assert_eq!(Units::default().acceleration(), 1.0);
+    }
+}
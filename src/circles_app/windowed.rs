@@ -0,0 +1,563 @@
+use crate::app::status::Status;
+use crate::app::App;
+use crate::circles_app::analysis::Analysis;
+use crate::circles_app::camera::Camera;
+use crate::circles_app::circle::Circle;
+use crate::circles_app::command::Command;
+use crate::circles_app::diagnostics::{ConservationMonitor, Tolerance};
+use crate::circles_app::replay::{Replay, ReplayError, REPLAY_EXTENSION};
+use crate::circles_app::scalar::Vector;
+use crate::circles_app::scene::{SceneError, SCENE_EXTENSION};
+use crate::circles_app::session::Session;
+use crate::circles_app::simulation::Simulation;
+use crate::circles_app::snapshot::{SnapshotError, SNAPSHOT_EXTENSION};
+use crate::circles_app::trajectory::TrajectoryExporter;
+use crate::circles_app::{generators, replay};
+use crate::vulkan::present::WindowData;
+use crate::vulkan::render::{circle_geometry, histogram_geometry};
+use crate::vulkan::Vulkan;
+use glam::Vec2;
+use raw_window_handle::HasRawWindowHandle;
+use slog::Logger;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use winit::dpi::{PhysicalSize, Size};
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
+use winit::event_loop::{EventLoop, EventLoopWindowTarget};
+use winit::window::{Window, WindowBuilder, WindowId};
+
+const PLAYBACK_SEEK_STEPS: isize = 200;
+const MAX_FIXED_STEPS_PER_UPDATE: u32 = 10;
+const SCRUB_STEPS: isize = 10;
+const HISTOGRAM_BINS: usize = 24;
+
+pub struct CirclesApp {
+    session: Session,
+    previous_update: Instant,
+    first_update: bool,
+    fixed_timestep: Option<Duration>,
+    unsimulated_time: Duration,
+    stats_overlay: bool,
+    analysis: Option<(Analysis, PathBuf)>,
+    conservation: Option<ConservationMonitor>,
+    trajectory: Option<TrajectoryExporter<BufWriter<File>>>,
+    camera: Camera,
+    logger: Logger,
+    vk: Vulkan,
+    mesh_window: Window,
+    // sprite_window: Window,
+}
+
+impl CirclesApp {
+    /// `field_size` is in metres, initially the whole field is fitted into the window.
+    pub fn new(logger: Logger, field_size: Vector, event_loop: &EventLoop<()>) -> Self {
+        let mesh_window = Self::create_mesh_window(event_loop);
+        let window_data = WindowData {
+            window_handle: mesh_window.raw_window_handle(),
+            width: mesh_window.inner_size().width,
+            height: mesh_window.inner_size().height,
+        };
+        let vk = Vulkan::new("Circles", window_data, logger.clone());
+
+        let mut simulation = Simulation::new(field_size, logger.clone());
+        for circle in generators::poisson_disk(field_size, 200, (0.05, 0.15), 1.0, 0) {
+            simulation.add_circle(circle);
+        }
+        let camera = Camera::fit(field_size, Self::viewport(&vk));
+
+        Self {
+            session: Session::new(simulation, logger.clone()),
+            previous_update: Instant::now(),
+            first_update: true,
+            fixed_timestep: None,
+            unsimulated_time: Duration::from_secs(0),
+            stats_overlay: false,
+            analysis: None,
+            conservation: None,
+            trajectory: None,
+            camera,
+            logger,
+            mesh_window,
+            vk,
+        }
+    }
+
+    fn create_mesh_window(event_loop: &EventLoop<()>) -> Window {
+        WindowBuilder::new()
+            .with_title("Circles")
+            .with_inner_size(Size::Physical(PhysicalSize::new(800, 600)))
+            .build(event_loop)
+            .expect("Can't create mesh window")
+    }
+
+    fn viewport(vk: &Vulkan) -> Vec2 {
+        let resolution = vk.get_resolution();
+        Vec2::new(resolution.width as f32, resolution.height as f32)
+    }
+
+    pub fn camera(&self) -> Camera {
+        self.camera
+    }
+
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+    }
+
+    /// Shows temperature, pressure and energy in the window title and the speed histogram
+    /// against Maxwell-Boltzmann distribution in the lower left corner.
+    pub fn set_stats_overlay(&mut self, enabled: bool) {
+        self.stats_overlay = enabled;
+        if !enabled {
+            self.mesh_window.set_title("Circles");
+        }
+    }
+
+    /// Analysis results are written as CSV files into `output_dir` when the app is dropped.
+    pub fn enable_analysis(&mut self, analysis: Analysis, output_dir: PathBuf) {
+        self.analysis = Some((analysis, output_dir));
+    }
+
+    /// Starts tracking drift of conserved quantities from the current state.
+    pub fn enable_conservation_monitor(&mut self, tolerance: Tolerance) {
+        let monitor =
+            ConservationMonitor::new(self.session.simulation(), tolerance, self.logger.clone());
+        self.conservation = Some(monitor);
+    }
+
+    /// Physics is advanced in equal `timestep` steps with RNG seeded by `seed`. Wall clock only
+    /// decides how many steps are made per update, so runs with the same commands are bit-identical.
+    pub fn set_deterministic(&mut self, seed: u64, timestep: Duration) {
+        self.apply_command(Command::SetSeed(seed));
+        self.fixed_timestep = Some(timestep);
+        self.unsimulated_time = Duration::from_secs(0);
+        info!(
+            self.logger,
+            "Deterministic mode: seed {}, timestep {:?}", seed, timestep
+        );
+    }
+
+    pub fn set_variable_timestep(&mut self) {
+        self.fixed_timestep = None;
+    }
+
+    /// Circle states and events of every following step are streamed into the exporter.
+    pub fn enable_trajectory_export(&mut self, exporter: TrajectoryExporter<BufWriter<File>>) {
+        self.trajectory = Some(exporter);
+    }
+
+    /// All changes of a running simulation go through here, so they can be recorded.
+    pub fn apply_command(&mut self, command: Command) {
+        self.session.apply_command(command);
+    }
+
+    /// Refits the camera to a changed field, restarts conservation tracking and analysis,
+    /// and marks the jump in the trajectory export.
+    fn state_replaced(&mut self, field_size: Vector) {
+        let simulation = self.session.simulation();
+        if simulation.field_size() != field_size {
+            self.camera = Camera::fit(simulation.field_size(), Self::viewport(&self.vk));
+        }
+        if let Some(monitor) = &self.conservation {
+            let tolerance = monitor.tolerance();
+            self.enable_conservation_monitor(tolerance);
+        }
+        if let Some((analysis, _)) = &mut self.analysis {
+            analysis.reset();
+        }
+        if let Some(exporter) = &mut self.trajectory {
+            if let Err(e) = exporter.mark_discontinuity(self.session.simulation()) {
+                error!(self.logger, "Trajectory export stopped: {}", e);
+                self.trajectory = None;
+            }
+        }
+    }
+
+    pub fn load_scene(&mut self, path: &Path) -> Result<(), SceneError> {
+        let field_size = self.session.simulation().field_size();
+        self.session.load_scene(path)?;
+        self.state_replaced(field_size);
+        Ok(())
+    }
+
+    pub fn save_scene(&self, path: &Path) -> Result<(), SceneError> {
+        self.session.save_scene(path)
+    }
+
+    pub fn save_snapshot(&self, path: &Path) -> Result<(), SnapshotError> {
+        self.session.save_snapshot(path)
+    }
+
+    pub fn load_snapshot(&mut self, path: &Path) -> Result<(), SnapshotError> {
+        let field_size = self.session.simulation().field_size();
+        self.session.load_snapshot(path)?;
+        self.state_replaced(field_size);
+        Ok(())
+    }
+
+    /// States kept for rewinding are limited by `budget` bytes.
+    pub fn set_rewind_budget(&mut self, budget: usize) {
+        self.session.set_rewind_budget(budget);
+    }
+
+    pub fn pause(&mut self) {
+        self.session.pause();
+    }
+
+    /// Resuming from a rewound state branches the timeline: states after it are forgotten.
+    pub fn resume(&mut self) {
+        self.session.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.session.is_paused()
+    }
+
+    /// Moves through recent states while paused.
+    pub fn scrub(&mut self, offset: isize) {
+        let field_size = self.session.simulation().field_size();
+        if self.session.scrub(offset).is_some() {
+            self.state_replaced(field_size);
+        }
+    }
+
+    fn step_back_or_seek(&mut self, steps: isize) {
+        if self.session.is_playing() {
+            let field_size = self.session.simulation().field_size();
+            self.session.seek_playback(steps * PLAYBACK_SEEK_STEPS);
+            self.state_replaced(field_size);
+        } else {
+            self.scrub(steps * SCRUB_STEPS);
+        }
+    }
+
+    pub fn start_recording(&mut self) {
+        self.session.start_recording();
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Replay> {
+        self.session.stop_recording()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.session.is_recording()
+    }
+
+    /// Replaces current simulation with the replay's initial state and plays it back.
+    pub fn start_playback(&mut self, replay: Replay) {
+        let field_size = self.session.simulation().field_size();
+        self.session.start_playback(replay);
+        self.state_replaced(field_size);
+    }
+
+    /// Simulation continues live from the current playback position.
+    pub fn stop_playback(&mut self) {
+        self.session.stop_playback();
+    }
+
+    pub fn load_replay(&mut self, path: &Path) -> Result<(), ReplayError> {
+        let field_size = self.session.simulation().field_size();
+        self.session.load_replay(path)?;
+        self.state_replaced(field_size);
+        Ok(())
+    }
+
+    fn toggle_recording(&mut self) {
+        if !self.is_recording() {
+            self.start_recording();
+            return;
+        }
+        let replay = match self.stop_recording() {
+            Some(replay) => replay,
+            None => return,
+        };
+        let filter = [format!("*.{}", REPLAY_EXTENSION)];
+        let filter: Vec<&str> = filter.iter().map(String::as_str).collect();
+        let path = tinyfiledialogs::save_file_dialog_with_filter(
+            "Save replay",
+            &Self::dialog_dir(),
+            &filter,
+            "Circles replay",
+        );
+        if let Some(path) = path {
+            match replay::save(&replay, Path::new(&path)) {
+                Ok(()) => info!(self.logger, "Replay saved to {:?}", path),
+                Err(e) => error!(self.logger, "Can't save replay {:?}: {}", path, e),
+            }
+        }
+    }
+
+    fn open_replay_dialog(&mut self) {
+        let filter = [format!("*.{}", REPLAY_EXTENSION)];
+        let filter: Vec<&str> = filter.iter().map(String::as_str).collect();
+        let path = tinyfiledialogs::open_file_dialog(
+            "Open replay",
+            &Self::dialog_dir(),
+            Some((&filter, "Circles replay")),
+        );
+        if let Some(path) = path {
+            if let Err(e) = self.load_replay(Path::new(&path)) {
+                error!(self.logger, "Can't load replay {:?}: {}", path, e);
+            }
+        }
+    }
+
+    fn open_snapshot_dialog(&mut self) {
+        let filter = [format!("*.{}", SNAPSHOT_EXTENSION)];
+        let filter: Vec<&str> = filter.iter().map(String::as_str).collect();
+        let path = tinyfiledialogs::open_file_dialog(
+            "Restore snapshot",
+            &Self::dialog_dir(),
+            Some((&filter, "Circles snapshot")),
+        );
+        if let Some(path) = path {
+            if let Err(e) = self.load_snapshot(Path::new(&path)) {
+                error!(self.logger, "Can't load snapshot {:?}: {}", path, e);
+            }
+        }
+    }
+
+    fn save_snapshot_dialog(&self) {
+        let filter = [format!("*.{}", SNAPSHOT_EXTENSION)];
+        let filter: Vec<&str> = filter.iter().map(String::as_str).collect();
+        let path = tinyfiledialogs::save_file_dialog_with_filter(
+            "Save snapshot",
+            &Self::dialog_dir(),
+            &filter,
+            "Circles snapshot",
+        );
+        if let Some(path) = path {
+            if let Err(e) = self.save_snapshot(Path::new(&path)) {
+                error!(self.logger, "Can't save snapshot {:?}: {}", path, e);
+            }
+        }
+    }
+
+    fn open_scene_dialog(&mut self) {
+        let filter = [format!("*.{}", SCENE_EXTENSION)];
+        let filter: Vec<&str> = filter.iter().map(String::as_str).collect();
+        let path = tinyfiledialogs::open_file_dialog(
+            "Open scene",
+            &Self::dialog_dir(),
+            Some((&filter, "Circles scene")),
+        );
+        if let Some(path) = path {
+            if let Err(e) = self.load_scene(Path::new(&path)) {
+                error!(self.logger, "Can't load scene {:?}: {}", path, e);
+            }
+        }
+    }
+
+    fn save_scene_dialog(&self) {
+        let filter = [format!("*.{}", SCENE_EXTENSION)];
+        let filter: Vec<&str> = filter.iter().map(String::as_str).collect();
+        let path = tinyfiledialogs::save_file_dialog_with_filter(
+            "Save scene",
+            &Self::dialog_dir(),
+            &filter,
+            "Circles scene",
+        );
+        if let Some(path) = path {
+            if let Err(e) = self.save_scene(Path::new(&path)) {
+                error!(self.logger, "Can't save scene {:?}: {}", path, e);
+            }
+        }
+    }
+
+    fn dialog_dir() -> String {
+        settings_path::default_settings_path()
+            .map(|path| format!("{}/", path.display()))
+            .unwrap_or_default()
+    }
+
+    fn show_stats(&self) {
+        let observables = self.session.simulation().observables();
+        self.mesh_window.set_title(&format!(
+            "Circles | N: {} | E: {:.3} | T: {:.3} | P: {:.3}",
+            observables.count,
+            observables.kinetic_energy,
+            observables.temperature,
+            observables.pressure
+        ));
+    }
+
+    fn elapsed_time(&self) -> Duration {
+        if self.first_update {
+            trace!(self.logger, "First update");
+            Duration::from_secs(0)
+        } else {
+            let elapse = Instant::now() - self.previous_update;
+            trace!(self.logger, "Elapse time: {:?}.", elapse);
+            elapse
+        }
+    }
+}
+
+impl App for CirclesApp {
+    type Event = KeyboardInput;
+
+    fn process_event(&mut self, event: &Self::Event, _wt: &EventLoopWindowTarget<()>) -> Status {
+        if event.state != ElementState::Pressed {
+            return Status::Run;
+        }
+        match event.virtual_keycode {
+            Some(VirtualKeyCode::O) => self.open_scene_dialog(),
+            Some(VirtualKeyCode::S) => self.save_scene_dialog(),
+            Some(VirtualKeyCode::F5) => self.save_snapshot_dialog(),
+            Some(VirtualKeyCode::F9) => self.open_snapshot_dialog(),
+            Some(VirtualKeyCode::R) => self.toggle_recording(),
+            Some(VirtualKeyCode::P) => self.open_replay_dialog(),
+            Some(VirtualKeyCode::Up) => self.session.scale_playback_speed(2f32),
+            Some(VirtualKeyCode::Down) => self.session.scale_playback_speed(0.5f32),
+            Some(VirtualKeyCode::Left) => self.step_back_or_seek(-1),
+            Some(VirtualKeyCode::Right) => self.step_back_or_seek(1),
+            Some(VirtualKeyCode::Space) => self.session.toggle_pause(),
+            Some(VirtualKeyCode::H) => self.set_stats_overlay(!self.stats_overlay),
+            Some(VirtualKeyCode::Escape) => self.stop_playback(),
+            _ => {}
+        }
+        Status::Run
+    }
+
+    fn update(&mut self, _wt: &EventLoopWindowTarget<()>) -> Status {
+        trace!(self.logger, "App update called");
+        let elapsed_time = self.elapsed_time();
+        self.first_update = false;
+        self.previous_update = Instant::now();
+        if !self.session.is_paused() {
+            match self.session.due_frames(elapsed_time) {
+                Some(frames) => {
+                    for frame in frames {
+                        for command in frame.commands {
+                            self.session.simulation_mut().apply(command);
+                        }
+                        self.advance_simulation(frame.dt);
+                    }
+                }
+                None => match self.fixed_timestep {
+                    Some(timestep) => self.advance_fixed(elapsed_time, timestep),
+                    None => self.advance_simulation(elapsed_time),
+                },
+            }
+        }
+        if self.stats_overlay {
+            self.show_stats();
+        }
+        std::thread::sleep(Duration::from_millis(15));
+        self.mesh_window.request_redraw();
+        Status::Run
+    }
+
+    fn draw(&mut self, _window_id: WindowId) {
+        let simulation = self.session.simulation();
+        let members: Vec<Circle> = simulation
+            .compounds()
+            .flat_map(|(_, compound)| compound.members())
+            .collect();
+        let circles = simulation
+            .circles()
+            .map(|(_, circle)| circle)
+            .chain(&members);
+        let viewport = Self::viewport(&self.vk);
+        let (mut vertices, mut indices) =
+            circle_geometry::circle_quads(circles, &self.camera, viewport);
+        let obstacles = simulation.obstacles().map(|(_, obstacle)| obstacle);
+        let (walls, wall_indices) =
+            circle_geometry::obstacle_quads(obstacles, &self.camera, viewport);
+        let base = vertices.len() as u32;
+        vertices.extend(walls);
+        indices.extend(wall_indices.into_iter().map(|index| base + index));
+        if self.stats_overlay {
+            let histogram = simulation.speed_histogram(HISTOGRAM_BINS);
+            let (bars, bar_indices) = histogram_geometry::histogram_quads(
+                &histogram,
+                Vec2::new(-0.95, 0.55),
+                Vec2::new(-0.35, 0.95),
+            );
+            let base = vertices.len() as u32;
+            vertices.extend(bars);
+            indices.extend(bar_indices.into_iter().map(|index| base + index));
+        }
+        self.vk.set_geometry(&vertices, &indices);
+        self.vk.render();
+    }
+}
+
+impl CirclesApp {
+    fn advance_fixed(&mut self, elapsed_time: Duration, timestep: Duration) {
+        self.unsimulated_time += elapsed_time;
+        let mut steps = 0;
+        while self.unsimulated_time >= timestep {
+            if steps == MAX_FIXED_STEPS_PER_UPDATE {
+                debug!(
+                    self.logger,
+                    "Simulation is behind wall clock, skipping {:?}", self.unsimulated_time
+                );
+                self.unsimulated_time = Duration::from_secs(0);
+                break;
+            }
+            self.unsimulated_time -= timestep;
+            self.advance_simulation(timestep);
+            steps += 1;
+        }
+    }
+
+    fn advance_simulation(&mut self, dt: Duration) {
+        self.session.step(dt);
+        let simulation = self.session.simulation_mut();
+        if let Some(thermostat) = simulation.thermostat() {
+            trace!(
+                self.logger,
+                "Temperature: {} (target {})",
+                simulation.temperature(),
+                thermostat.target()
+            );
+        }
+        let (step, time) = (simulation.steps(), simulation.time());
+        let mut export_result = Ok(());
+        for event in simulation.events().drain() {
+            trace!(self.logger, "Collision event: {:?}", event);
+            if let Some((analysis, _)) = &mut self.analysis {
+                analysis.observe_event(&event);
+            }
+            if let Some(exporter) = &mut self.trajectory {
+                export_result =
+                    export_result.and_then(|_| exporter.write_event(step, time, &event));
+            }
+        }
+        if let Some(exporter) = &mut self.trajectory {
+            let simulation = self.session.simulation();
+            export_result = export_result.and_then(|_| exporter.record(simulation));
+        }
+        if let Err(e) = export_result {
+            error!(self.logger, "Trajectory export stopped: {}", e);
+            self.trajectory = None;
+        }
+        if let Some((analysis, _)) = &mut self.analysis {
+            analysis.update(self.session.simulation());
+        }
+        if let Some(monitor) = &mut self.conservation {
+            monitor.check(self.session.simulation());
+        }
+    }
+}
+
+impl Drop for CirclesApp {
+    fn drop(&mut self) {
+        if let Some(exporter) = &mut self.trajectory {
+            if let Err(e) = exporter.flush() {
+                error!(self.logger, "Can't flush trajectory export: {}", e);
+            }
+        }
+        if let Some((analysis, output_dir)) = &self.analysis {
+            match analysis.write_csv_files(output_dir) {
+                Ok(()) => info!(self.logger, "Analysis written to {:?}", output_dir),
+                Err(e) => error!(
+                    self.logger,
+                    "Can't write analysis to {:?}: {}", output_dir, e
+                ),
+            }
+        }
+    }
+}
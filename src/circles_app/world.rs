@@ -0,0 +1,542 @@
+//! Unbounded plane split into square chunks. Chunks near focus points (usually the camera) are
+//! awake and simulated every step together with their neighbours touched by awake circles.
+//! Other chunks sleep: their circles are frozen and kept as encoded bytes, and with a store
+//! directory the least relevant sleeping chunks are paged to disk. Circles leaving the focus
+//! keep moving for the coast time before their chunks fall asleep.
+//!
+//! A world is a reduced `Simulation`: circles move under gravity and collide with each other,
+//! and that is all. There are no walls, compounds, obstacles, sensors, sinks, emitters or
+//! thermostats, and no collision events. Coordinates are absolute, so very large worlds want
+//! the `f64` feature.
+use crate::circles_app::broad_phase;
+use crate::circles_app::circle::Circle;
+use crate::circles_app::scalar::{seconds, Scalar, Vector};
+use crate::circles_app::simulation;
+use serde::{Deserialize, Serialize};
+use slog::Logger;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const CHUNK_EXTENSION: &str = "cchk";
+pub const DEFAULT_COAST_TIME: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum WorldError {
+    Io(std::io::Error),
+    Encoding(bincode::Error),
+}
+
+impl Error for WorldError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WorldError::Io(e) => Some(e),
+            WorldError::Encoding(e) => Some(e),
+        }
+    }
+}
+
+impl Display for WorldError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            WorldError::Io(e) => write!(f, "Chunk store: {}", e),
+            WorldError::Encoding(e) => write!(f, "Chunk encoding: {}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for WorldError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<bincode::Error> for WorldError {
+    fn from(e: bincode::Error) -> Self {
+        Self::Encoding(e)
+    }
+}
+
+/// Chunk `(x, y)` spans from `(x, y) * chunk_size` to `(x + 1, y + 1) * chunk_size`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ChunkCoord {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl ChunkCoord {
+    pub fn new(x: i64, y: i64) -> Self {
+        Self { x, y }
+    }
+
+    pub fn of(point: Vector, chunk_size: Scalar) -> Self {
+        let cell = (point / chunk_size).floor();
+        Self::new(cell.x() as i64, cell.y() as i64)
+    }
+
+    pub fn center(self, chunk_size: Scalar) -> Vector {
+        Vector::new(self.x as Scalar + 0.5, self.y as Scalar + 0.5) * chunk_size
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ChunkState {
+    Awake,
+    Asleep,
+    Paged,
+}
+
+struct SleepingChunk {
+    population: usize,
+    bytes: Vec<u8>,
+}
+
+struct ChunkStore {
+    dir: PathBuf,
+    max_resident: usize,
+}
+
+impl ChunkStore {
+    fn path(&self, coord: ChunkCoord) -> PathBuf {
+        self.dir
+            .join(format!("{}_{}.{}", coord.x, coord.y, CHUNK_EXTENSION))
+    }
+}
+
+pub struct World {
+    chunk_size: Scalar,
+    awake: BTreeMap<ChunkCoord, Vec<Circle>>,
+    asleep: BTreeMap<ChunkCoord, SleepingChunk>,
+    /// Population of every chunk in the store.
+    paged: BTreeMap<ChunkCoord, usize>,
+    /// Circles moved into chunks which failed to page in, they join the chunk when it wakes.
+    arrivals: BTreeMap<ChunkCoord, Vec<Circle>>,
+    store: Option<ChunkStore>,
+    focus: Vec<Vector>,
+    focus_radius: Scalar,
+    /// Time until which chunks with moving circles stay awake after they left the focus.
+    coasting: BTreeMap<ChunkCoord, Duration>,
+    coast_time: Duration,
+    gravity: Vector,
+    time: Duration,
+    steps: u64,
+    logger: Logger,
+}
+
+impl World {
+    pub fn new(chunk_size: Scalar, logger: Logger) -> Self {
+        Self {
+            chunk_size,
+            awake: BTreeMap::new(),
+            asleep: BTreeMap::new(),
+            paged: BTreeMap::new(),
+            arrivals: BTreeMap::new(),
+            store: None,
+            focus: Vec::new(),
+            focus_radius: 0.0,
+            coasting: BTreeMap::new(),
+            coast_time: DEFAULT_COAST_TIME,
+            gravity: Vector::zero(),
+            time: Duration::from_secs(0),
+            steps: 0,
+            logger,
+        }
+    }
+
+    /// Keeps at most `max_resident` sleeping chunks in memory, the ones farthest from focus
+    /// are written into `dir`.
+    pub fn set_store(&mut self, dir: &Path, max_resident: usize) -> Result<(), WorldError> {
+        std::fs::create_dir_all(dir)?;
+        self.store = Some(ChunkStore {
+            dir: dir.to_path_buf(),
+            max_resident,
+        });
+        self.page_out()
+    }
+
+    /// Chunks within `radius` of any of `points` are awake from the next step on.
+    pub fn set_focus(&mut self, points: &[Vector], radius: Scalar) {
+        self.focus = points.to_vec();
+        self.focus_radius = radius;
+    }
+
+    /// How long circles that left the focus are still simulated.
+    pub fn set_coast_time(&mut self, coast_time: Duration) {
+        self.coast_time = coast_time;
+    }
+
+    pub fn chunk_size(&self) -> Scalar {
+        self.chunk_size
+    }
+
+    pub fn gravity(&self) -> Vector {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector) {
+        self.gravity = gravity;
+    }
+
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Wakes the chunk of the circle.
+    pub fn add_circle(&mut self, circle: Circle) -> Result<(), WorldError> {
+        let coord = ChunkCoord::of(circle.center(), self.chunk_size);
+        self.wake(coord)?;
+        self.awake.entry(coord).or_default().push(circle);
+        Ok(())
+    }
+
+    pub fn population(&self) -> usize {
+        self.awake.values().map(Vec::len).sum::<usize>()
+            + self.asleep.values().map(|c| c.population).sum::<usize>()
+            + self.paged.values().sum::<usize>()
+            + self.arrivals.values().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn awake_circles(&self) -> impl Iterator<Item = &Circle> {
+        self.awake.values().flatten()
+    }
+
+    /// Hash of the circles in memory and of populations of paged chunks, to compare runs.
+    pub fn state_hash(&self) -> u64 {
+        let mut bytes = bincode::serialize(&(&self.awake, &self.paged, &self.arrivals))
+            .expect("Can't encode world state");
+        for (coord, chunk) in &self.asleep {
+            bytes.extend(bincode::serialize(coord).expect("Can't encode chunk coordinate"));
+            bytes.extend(&chunk.bytes);
+        }
+        simulation::hash_bytes(&bytes)
+    }
+
+    pub fn chunk_state(&self, coord: ChunkCoord) -> Option<ChunkState> {
+        if self.awake.contains_key(&coord) {
+            Some(ChunkState::Awake)
+        } else if self.asleep.contains_key(&coord) {
+            Some(ChunkState::Asleep)
+        } else if self.paged.contains_key(&coord) {
+            Some(ChunkState::Paged)
+        } else {
+            None
+        }
+    }
+
+    /// Store errors don't lose circles: a chunk which can't be paged in stays in the store.
+    pub fn step(&mut self, elapsed_time: Duration) -> Result<(), WorldError> {
+        trace!(self.logger, "World step: {:?}", elapsed_time);
+        self.update_residency()?;
+        self.time += elapsed_time;
+        self.steps += 1;
+
+        let mut circles: Vec<Circle> = Vec::new();
+        let mut deadlines: Vec<Duration> = Vec::new();
+        for (coord, chunk) in &mut self.awake {
+            let deadline = self.coasting.get(coord).copied().unwrap_or_default();
+            deadlines.resize(deadlines.len() + chunk.len(), deadline);
+            circles.append(chunk);
+        }
+        for (i, j) in broad_phase::candidate_pairs(&circles) {
+            let (a, b) = simulation::pair_mut(&mut circles, i, j);
+            if !a.filter().interacts(b.filter()) || !a.is_intersect(b) {
+                continue;
+            }
+            let to_b = b.center() - a.center();
+            if to_b != Vector::zero() && (a.speed() - b.speed()).dot(to_b) > 0.0 {
+                a.collide(b);
+            }
+        }
+        let half_kick = self.gravity * (0.5 * seconds(elapsed_time));
+        for circle in &mut circles {
+            circle.set_speed(circle.speed() + half_kick);
+            circle.update(elapsed_time);
+            circle.set_speed(circle.speed() + half_kick);
+        }
+
+        // Fast circles may leave the awake region, they wake the chunk they land in
+        // and bring their coast deadline along.
+        let mut result = Ok(());
+        for (circle, deadline) in circles.into_iter().zip(deadlines) {
+            let coord = ChunkCoord::of(circle.center(), self.chunk_size);
+            let coast = self.coasting.entry(coord).or_default();
+            *coast = (*coast).max(deadline);
+            match self.wake(coord) {
+                Ok(()) => self.awake.entry(coord).or_default().push(circle),
+                Err(e) => {
+                    self.arrivals.entry(coord).or_default().push(circle);
+                    result = result.and(Err(e));
+                }
+            }
+        }
+        result
+    }
+
+    /// Focus chunks, coasting chunks with moving circles and chunks touched by their circles
+    /// stay awake, the rest falls asleep.
+    fn update_residency(&mut self) -> Result<(), WorldError> {
+        let mut wanted = BTreeSet::new();
+        for &point in &self.focus {
+            let reach = Vector::splat(self.focus_radius);
+            wanted.extend(self.chunks_overlapping(point - reach, point + reach));
+        }
+        for &coord in &wanted {
+            self.wake(coord)?;
+            self.coasting.insert(coord, self.time + self.coast_time);
+        }
+        let time = self.time;
+        let coasting = self.awake.iter().filter(|(coord, circles)| {
+            self.coasting.get(coord).copied().unwrap_or_default() > time
+                && circles.iter().any(|c| c.speed() != Vector::zero())
+        });
+        let coasting: Vec<ChunkCoord> = coasting.map(|(&coord, _)| coord).collect();
+        wanted.extend(coasting);
+        let mut halo = BTreeSet::new();
+        for coord in &wanted {
+            for circle in &self.awake[coord] {
+                let reach = Vector::splat(circle.radius());
+                halo.extend(
+                    self.chunks_overlapping(circle.center() - reach, circle.center() + reach),
+                );
+            }
+        }
+        for &coord in &halo {
+            self.wake(coord)?;
+        }
+        wanted.extend(halo);
+
+        let sleepy: Vec<ChunkCoord> = self
+            .awake
+            .keys()
+            .filter(|coord| !wanted.contains(coord))
+            .copied()
+            .collect();
+        for coord in sleepy {
+            self.coasting.remove(&coord);
+            let circles = self.awake.remove(&coord).unwrap_or_default();
+            if !circles.is_empty() {
+                let chunk = SleepingChunk {
+                    population: circles.len(),
+                    bytes: bincode::serialize(&circles)?,
+                };
+                self.asleep.insert(coord, chunk);
+            }
+        }
+        self.page_out()
+    }
+
+    fn chunks_overlapping(&self, lower: Vector, upper: Vector) -> Vec<ChunkCoord> {
+        let (from, to) = (
+            ChunkCoord::of(lower, self.chunk_size),
+            ChunkCoord::of(upper, self.chunk_size),
+        );
+        let mut coords = Vec::new();
+        for x in from.x..=to.x {
+            for y in from.y..=to.y {
+                coords.push(ChunkCoord::new(x, y));
+            }
+        }
+        coords
+    }
+
+    fn wake(&mut self, coord: ChunkCoord) -> Result<(), WorldError> {
+        if self.awake.contains_key(&coord) {
+            return Ok(());
+        }
+        let mut circles: Vec<Circle> = if let Some(chunk) = self.asleep.get(&coord) {
+            let circles = bincode::deserialize(&chunk.bytes)?;
+            self.asleep.remove(&coord);
+            circles
+        } else if self.paged.contains_key(&coord) {
+            let store = self.store.as_ref().expect("Paged chunks have a store");
+            let path = store.path(coord);
+            let circles = bincode::deserialize(&std::fs::read(&path)?)?;
+            std::fs::remove_file(&path)?;
+            self.paged.remove(&coord);
+            debug!(self.logger, "Chunk {:?} paged in", coord);
+            circles
+        } else {
+            Vec::new()
+        };
+        circles.extend(self.arrivals.remove(&coord).unwrap_or_default());
+        self.awake.insert(coord, circles);
+        Ok(())
+    }
+
+    fn page_out(&mut self) -> Result<(), WorldError> {
+        let store = match &self.store {
+            Some(store) if self.asleep.len() > store.max_resident => store,
+            _ => return Ok(()),
+        };
+        let mut by_distance: Vec<(Scalar, ChunkCoord)> = self
+            .asleep
+            .keys()
+            .map(|&coord| (self.focus_distance(coord), coord))
+            .collect();
+        by_distance.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+        let excess = self.asleep.len() - store.max_resident;
+        for &(_, coord) in &by_distance[..excess] {
+            let chunk = self
+                .asleep
+                .remove(&coord)
+                .expect("Sleeping chunk is listed");
+            std::fs::write(store.path(coord), &chunk.bytes)?;
+            self.paged.insert(coord, chunk.population);
+            debug!(self.logger, "Chunk {:?} paged out", coord);
+        }
+        Ok(())
+    }
+
+    fn focus_distance(&self, coord: ChunkCoord) -> Scalar {
+        let center = coord.center(self.chunk_size);
+        self.focus
+            .iter()
+            .map(|&point| (point - center).length())
+            .fold(Scalar::INFINITY, Scalar::min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChunkCoord, ChunkState, World};
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::generators;
+    use crate::circles_app::scalar::{Scalar, Vector};
+    use assert_approx_eq::assert_approx_eq;
+    use slog::{Discard, Logger};
+    use std::time::Duration;
+
+    fn world() -> World {
+        World::new(10.0, Logger::root(Discard, o!()))
+    }
+
+    #[test]
+    fn chunk_coords() {
+        assert_eq!(
+            ChunkCoord::of(Vector::new(5.0, 15.0), 10.0),
+            ChunkCoord::new(0, 1)
+        );
+        assert_eq!(
+            ChunkCoord::of(Vector::new(-0.5, -10.0), 10.0),
+            ChunkCoord::new(-1, -1)
+        );
+        assert_eq!(ChunkCoord::new(-1, 2).center(10.0), Vector::new(-5.0, 25.0));
+    }
+
+    #[test]
+    fn only_chunks_near_focus_are_simulated() {
+        let mut world = world();
+        let near = Circle::new(Vector::new(5.0, 5.0), 1.0, Vector::new(1.0, 0.0));
+        let far = Circle::new(Vector::new(1005.0, -995.0), 1.0, Vector::new(1.0, 0.0));
+        world.add_circle(near).unwrap();
+        world.add_circle(far).unwrap();
+        world.set_focus(&[Vector::new(5.0, 5.0)], 1.0);
+        world.set_coast_time(Duration::from_millis(50));
+        for _ in 0..100 {
+            world.step(Duration::from_millis(10)).unwrap();
+        }
+        assert_eq!(world.population(), 2);
+        let awake: Vec<&Circle> = world.awake_circles().collect();
+        assert_eq!(awake.len(), 1);
+        assert!((awake[0].center() - Vector::new(6.0, 5.0)).length() < 1e-3);
+        let far_chunk = ChunkCoord::of(far.center(), world.chunk_size());
+        assert_eq!(world.chunk_state(far_chunk), Some(ChunkState::Asleep));
+
+        world.set_focus(&[far.center()], 1.0);
+        world.step(Duration::from_millis(10)).unwrap();
+        assert_eq!(world.awake_circles().count(), 2);
+        for _ in 0..10 {
+            world.step(Duration::from_millis(10)).unwrap();
+        }
+        let awake: Vec<&Circle> = world.awake_circles().collect();
+        assert_eq!(awake.len(), 1);
+        assert!((awake[0].center() - Vector::new(1005.11, -995.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn circles_leaving_focus_keep_moving() {
+        let mut world = world();
+        world
+            .add_circle(Circle::new(
+                Vector::new(5.0, 5.0),
+                1.0,
+                Vector::new(100.0, 0.0),
+            ))
+            .unwrap();
+        world.set_focus(&[Vector::new(5.0, 5.0)], 1.0);
+        for _ in 0..50 {
+            world.step(Duration::from_millis(10)).unwrap();
+        }
+        let awake: Vec<&Circle> = world.awake_circles().collect();
+        assert_eq!(awake.len(), 1);
+        assert!((awake[0].center() - Vector::new(55.0, 5.0)).length() < 1e-2);
+
+        for _ in 0..100 {
+            world.step(Duration::from_millis(10)).unwrap();
+        }
+        assert_eq!(world.awake_circles().count(), 0);
+        assert_eq!(world.population(), 1);
+    }
+
+    #[test]
+    fn circles_collide_across_chunk_border() {
+        let mut world = world();
+        world
+            .add_circle(Circle::new(
+                Vector::new(9.0, 5.0),
+                1.0,
+                Vector::new(1.0, 0.0),
+            ))
+            .unwrap();
+        world
+            .add_circle(Circle::new(
+                Vector::new(11.5, 5.0),
+                1.0,
+                Vector::new(-1.0, 0.0),
+            ))
+            .unwrap();
+        world.set_focus(&[Vector::new(5.0, 5.0)], 1.0);
+        for _ in 0..50 {
+            world.step(Duration::from_millis(10)).unwrap();
+        }
+        let mut speeds: Vec<Scalar> = world.awake_circles().map(|c| c.speed().x()).collect();
+        speeds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(speeds.len(), 2);
+        assert_approx_eq!(speeds[0], -1.0);
+        assert_approx_eq!(speeds[1], 1.0);
+    }
+
+    #[test]
+    fn sleeping_chunks_are_paged_to_disk_and_back() {
+        let dir = std::env::temp_dir().join(format!("circles-world-{}", std::process::id()));
+        let mut world = world();
+        world.set_store(&dir, 1).unwrap();
+        let gas = generators::poisson_disk(Vector::new(100.0, 100.0), 200, (0.5, 1.0), 1.0, 49);
+        for circle in &gas {
+            world.add_circle(*circle).unwrap();
+        }
+        world.set_focus(&[Vector::new(-500.0, -500.0)], 1.0);
+        world.step(Duration::from_millis(10)).unwrap();
+        assert_eq!(world.population(), gas.len());
+        assert_eq!(world.awake_circles().count(), 0);
+        let paged = std::fs::read_dir(&dir).unwrap().count();
+        assert!(paged > 50, "{} chunks paged", paged);
+
+        world.set_focus(&[Vector::new(50.0, 50.0)], 100.0);
+        world.step(Duration::from_secs(0)).unwrap();
+        let mut awake: Vec<Circle> = world.awake_circles().copied().collect();
+        let mut expected = gas;
+        let key = |c: &Circle| (c.center().x(), c.center().y());
+        awake.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        expected.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        assert_eq!(awake, expected);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,8 @@
+#[macro_use]
+extern crate slog;
+
+#[cfg(feature = "windowing")]
+pub mod app;
+pub mod circles_app;
+#[cfg(feature = "render-vulkan")]
+pub mod vulkan;
@@ -1,16 +1,12 @@
-pub mod app;
-mod circles_app;
-pub mod vulkan;
-
 #[macro_use]
 extern crate slog;
-use crate::app::App;
-use crate::circles_app::CirclesApp;
-use app::status::Status;
+use circles::app::status::Status;
+use circles::app::App;
+use circles::circles_app::{CirclesApp, SpheresApp};
 use slog::{Drain, Logger};
 use slog_async::Async;
 use slog_term::{CompactFormat, TermDecorator};
-use winit::event::Event;
+use winit::event::{Event, KeyboardInput, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 
 fn main() {
@@ -20,15 +16,35 @@ fn main() {
     let event_loop = EventLoop::new();
     trace!(logger, "Event loop initialized");
 
-    let mut app = CirclesApp::new(logger.clone(), (800f32, 600f32).into(), &event_loop);
-    info!(logger, "App initialized");
+    // 2D is the default, `--spheres` switches to the 3D mode.
+    if std::env::args().any(|arg| arg == "--spheres") {
+        let app = SpheresApp::new(logger.clone(), (8.0, 6.0, 6.0).into(), &event_loop);
+        info!(logger, "Spheres app initialized");
+        run(app, event_loop, logger);
+    } else {
+        let app = CirclesApp::new(logger.clone(), (8.0, 6.0).into(), &event_loop);
+        info!(logger, "App initialized");
+        run(app, event_loop, logger);
+    }
+}
 
+fn run<A: App<Event = KeyboardInput> + 'static>(
+    mut app: A,
+    event_loop: EventLoop<()>,
+    logger: Logger,
+) -> ! {
     event_loop.run(move |event, event_loop_wt, control_flow| {
         *control_flow = ControlFlow::Poll;
-        if let Status::Finish = app.process_event(&(), event_loop_wt) {
-            *control_flow = ControlFlow::Exit;
-            info!(logger, "Application finished. Exitting from event loop.");
-            return;
+        if let Event::WindowEvent {
+            event: WindowEvent::KeyboardInput { input, .. },
+            ..
+        } = &event
+        {
+            if let Status::Finish = app.process_event(input, event_loop_wt) {
+                *control_flow = ControlFlow::Exit;
+                info!(logger, "Application finished. Exitting from event loop.");
+                return;
+            }
         }
         match event {
             Event::MainEventsCleared => {
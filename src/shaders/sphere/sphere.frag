@@ -0,0 +1,31 @@
+#version 450
+
+layout (push_constant) uniform Camera {
+    mat4 view;
+    mat4 projection;
+} camera;
+
+layout (location = 0) in vec3 position;
+layout (location = 1) flat in vec3 center;
+layout (location = 2) flat in float radius;
+layout (location = 3) flat in vec4 color;
+
+layout (location = 0) out vec4 uFragColor;
+
+const vec3 LIGHT = vec3(0.36, 0.56, 0.75);
+
+// Ray cast from the eye through the billboard, the depth is the one of the hit point.
+void main() {
+    vec3 ray = normalize(position);
+    float b = dot(ray, center);
+    float discriminant = b * b - dot(center, center) + radius * radius;
+    if (discriminant < 0.0) {
+        discard;
+    }
+    vec3 hit = ray * (b - sqrt(discriminant));
+    vec3 normal = (hit - center) / radius;
+    float diffuse = max(dot(normal, LIGHT), 0.0);
+    uFragColor = vec4(color.rgb * (0.25 + 0.75 * diffuse), color.a);
+    vec4 clip = camera.projection * vec4(hit, 1.0);
+    gl_FragDepth = clip.z / clip.w;
+}
@@ -0,0 +1,39 @@
+#version 450
+
+layout (location = 0) in vec4 center_radius;
+layout (location = 1) in vec4 color;
+
+layout (push_constant) uniform Camera {
+    mat4 view;
+    mat4 projection;
+} camera;
+
+layout (location = 0) out vec3 o_position;
+layout (location = 1) flat out vec3 o_center;
+layout (location = 2) flat out float o_radius;
+layout (location = 3) flat out vec4 o_color;
+
+const vec2 CORNERS[6] = vec2[6](
+    vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+    vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+);
+
+// Billboard facing the eye, sized to cover the silhouette cone of the sphere.
+void main() {
+    vec3 center = (camera.view * vec4(center_radius.xyz, 1.0)).xyz;
+    float radius = center_radius.w;
+    vec3 forward = normalize(center);
+    vec3 helper = abs(forward.y) > 0.99 ? vec3(1.0, 0.0, 0.0) : vec3(0.0, 1.0, 0.0);
+    vec3 right = normalize(cross(forward, helper));
+    vec3 up = cross(right, forward);
+    float distance2 = dot(center, center);
+    float half_size = radius * sqrt(distance2 / max(distance2 - radius * radius, 1e-6));
+    vec2 corner = CORNERS[gl_VertexIndex];
+    vec3 position = center + (right * corner.x + up * corner.y) * half_size;
+
+    o_position = position;
+    o_center = center;
+    o_radius = radius;
+    o_color = color;
+    gl_Position = camera.projection * vec4(position, 1.0);
+}
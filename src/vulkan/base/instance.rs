@@ -39,6 +39,28 @@ impl Instance {
         }
     }
 
+    /// Checks that an instance with the required layers and extensions can be created and
+    /// exposes at least one physical device, without panicking when Vulkan is missing.
+    pub fn is_supported(entry: &ash::Entry) -> bool {
+        let app_name = CString::new("probe").unwrap();
+        let app_info = Self::app_info(&app_name, &app_name);
+        let layers = vec!["VK_LAYER_KHRONOS_validation\0".as_ptr() as *const i8];
+        let ext_names = Self::extension_names();
+        let create_info = vk::InstanceCreateInfo::builder()
+            .application_info(&app_info)
+            .enabled_layer_names(&layers)
+            .enabled_extension_names(&ext_names);
+        let instance = match unsafe { entry.create_instance(&create_info, None) } {
+            Ok(instance) => instance,
+            Err(_) => return false,
+        };
+        let has_devices = unsafe { instance.enumerate_physical_devices() }
+            .map(|pdevices| !pdevices.is_empty())
+            .unwrap_or(false);
+        unsafe { instance.destroy_instance(None) };
+        has_devices
+    }
+
     pub fn get_vk_instance(&self) -> &ash::Instance {
         &self.instance
     }
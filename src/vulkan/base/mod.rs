@@ -20,6 +20,13 @@ pub struct VulkanBase {
 }
 
 impl VulkanBase {
+    /// Whether the Vulkan loader and a driver are present, so `new` won't panic on them.
+    pub fn is_available() -> bool {
+        Entry::new()
+            .map(|entry| Instance::is_supported(&entry))
+            .unwrap_or(false)
+    }
+
     pub fn new(app_name: &str, logger: Logger) -> Self {
         let entry = Entry::new().expect("Can't create vulkan entry!");
         let instance = instance::Instance::new(&entry, app_name, logger.clone());
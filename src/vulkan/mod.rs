@@ -2,7 +2,10 @@ pub mod base;
 pub mod present;
 pub mod render;
 use crate::vulkan::present::WindowData;
+use crate::vulkan::render::sphere_pipeline::ViewProjection;
+use crate::vulkan::render::vertex::{SphereInstance, Vertex};
 use ash::version::DeviceV1_0;
+use ash::vk;
 use base::VulkanBase;
 use present::VulkanPresent;
 use render::VulkanRenderer;
@@ -28,6 +31,19 @@ impl Vulkan {
         }
     }
 
+    pub fn get_resolution(&self) -> vk::Extent2D {
+        self.present.get_swapchain().get_resolution()
+    }
+
+    pub fn set_geometry(&mut self, vertices: &[Vertex], indices: &[u32]) {
+        self.render.write_geometry(&self.base, vertices, indices);
+    }
+
+    pub fn set_spheres(&mut self, instances: &[SphereInstance], view_projection: ViewProjection) {
+        self.render
+            .write_spheres(&self.base, instances, view_projection);
+    }
+
     pub fn render(&self) {
         self.render.render(&self.base, &self.present);
     }
@@ -0,0 +1,94 @@
+use crate::circles_app::camera::Camera;
+use crate::circles_app::circle::Circle;
+use crate::circles_app::obstacle::Obstacle;
+use crate::circles_app::scalar::Vector;
+use crate::circles_app::sensor::SensorShape;
+use crate::vulkan::render::vertex::Vertex;
+use glam::{Vec2, Vec4};
+
+pub const VERTICES_PER_CIRCLE: usize = 4;
+pub const INDICES_PER_CIRCLE: usize = 6;
+
+/// Bounding quads of circles in clip space, as seen by `camera` on a viewport of given pixels.
+pub fn circle_quads<'a>(
+    circles: impl Iterator<Item = &'a Circle>,
+    camera: &Camera,
+    viewport: Vec2,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let to_clip = Vec2::splat(2f32) / viewport;
+    for circle in circles {
+        let base = vertices.len() as u32;
+        let center = camera.world_to_clip(circle.center(), viewport);
+        let radius = to_clip * camera.to_pixels(circle.radius());
+        for &corner in &[(-1f32, -1f32), (1f32, -1f32), (1f32, 1f32), (-1f32, 1f32)] {
+            let position = center + Vec2::from(corner) * radius;
+            vertices.push(Vertex {
+                position: Vec4::new(position.x(), position.y(), 0f32, 1f32),
+                color: circle.color(),
+            });
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+    (vertices, indices)
+}
+
+/// Bounding quads of obstacles in clip space, drawn the same way as circles.
+pub fn obstacle_quads<'a>(
+    obstacles: impl Iterator<Item = &'a Obstacle>,
+    camera: &Camera,
+    viewport: Vec2,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for obstacle in obstacles {
+        let (min, max) = match obstacle.shape() {
+            SensorShape::Circle { center, radius } => (
+                center - Vector::splat(radius),
+                center + Vector::splat(radius),
+            ),
+            SensorShape::Rect { min, max } => (min, max),
+        };
+        let base = vertices.len() as u32;
+        for &(x, y) in &[
+            (min.x(), min.y()),
+            (max.x(), min.y()),
+            (max.x(), max.y()),
+            (min.x(), max.y()),
+        ] {
+            let position = camera.world_to_clip(Vector::new(x, y), viewport);
+            vertices.push(Vertex {
+                position: Vec4::new(position.x(), position.y(), 0f32, 1f32),
+                color: Vec4::new(0.5, 0.5, 0.5, 1.0),
+            });
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::circle_quads;
+    use crate::circles_app::camera::Camera;
+    use crate::circles_app::circle::Circle;
+    use crate::circles_app::scalar::Vector;
+    use glam::Vec2;
+
+    #[test]
+    fn quads_cover_circles_in_clip_space() {
+        let circles = [
+            Circle::new(Vector::new(0.5, 0.25), 0.1, Vector::zero()),
+            Circle::new(Vector::new(0.9, 0.4), 0.05, Vector::zero()),
+        ];
+        let viewport = Vec2::new(1000f32, 500f32);
+        let camera = Camera::new(1000f32, Vector::new(0.5, 0.25));
+        let (vertices, indices) = circle_quads(circles.iter(), &camera, viewport);
+        assert_eq!(vertices.len(), 8);
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7]);
+        let clip = |i: usize| Vec2::new(vertices[i].position.x(), vertices[i].position.y());
+        assert!((clip(0) - Vec2::new(-0.2, -0.4)).length() < 1e-6);
+        assert!((clip(2) - Vec2::new(0.2, 0.4)).length() < 1e-6);
+    }
+}
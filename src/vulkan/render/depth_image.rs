@@ -1,6 +1,5 @@
 use crate::vulkan::base::physical_device::PhysicalDevice;
 use crate::vulkan::base::VulkanBase;
-use crate::vulkan::present::VulkanPresent;
 use ash::version::DeviceV1_0;
 use ash::vk;
 use slog::Logger;
@@ -13,10 +12,9 @@ pub struct DepthImage {
 }
 
 impl DepthImage {
-    pub fn new(base: &VulkanBase, present: &VulkanPresent, logger: Logger) -> Self {
+    pub fn new(base: &VulkanBase, resolution: vk::Extent2D, logger: Logger) -> Self {
         let pdevice = base.get_physical_device();
         let vk_device = base.get_device().get_vk_device();
-        let resolution = present.get_swapchain().get_resolution();
         let image = DepthImage::create_image(vk_device, resolution);
 
         let memory_requirements = unsafe { vk_device.get_image_memory_requirements(image) };
@@ -14,14 +14,26 @@ pub struct GeometryBuffers {
     memory: vk::DeviceMemory,
     memory_size: vk::DeviceSize,
     index_buffer_offset: vk::DeviceSize,
+    vertex_capacity: usize,
+    index_capacity: usize,
+    index_count: u32,
     logger: Logger,
 }
 
 impl GeometryBuffers {
     pub fn new(base: &VulkanBase, logger: Logger) -> Self {
+        Self::with_capacity(base, 3, 3, logger)
+    }
+
+    pub fn with_capacity(
+        base: &VulkanBase,
+        vertex_capacity: usize,
+        index_capacity: usize,
+        logger: Logger,
+    ) -> Self {
         let vk_device = base.get_device().get_vk_device();
-        let vertices = Self::create_vertex_buffer(vk_device);
-        let indices = Self::create_index_buffer(vk_device);
+        let vertices = Self::create_vertex_buffer(vertex_capacity, vk_device);
+        let indices = Self::create_index_buffer(index_capacity, vk_device);
 
         let (mem_req, index_buffer_offset) =
             Self::get_memory_requirements(vertices, indices, vk_device);
@@ -44,6 +56,9 @@ impl GeometryBuffers {
             memory,
             memory_size,
             index_buffer_offset,
+            vertex_capacity,
+            index_capacity,
+            index_count: 0,
             logger,
         }
     }
@@ -56,10 +71,27 @@ impl GeometryBuffers {
         self.indices
     }
 
+    pub fn fits(&self, vertex_count: usize, index_count: usize) -> bool {
+        vertex_count <= self.vertex_capacity && index_count <= self.index_capacity
+    }
+
+    /// Number of indices written by the last `write`.
+    pub fn get_index_count(&self) -> u32 {
+        self.index_count
+    }
+
     pub fn write_triangle(&mut self, vk_device: &ash::Device) {
+        self.write(&Self::triangle_vertices(), &[0u32, 1, 2], vk_device);
+    }
+
+    /// Copies geometry into the host visible memory. Panics if it exceeds the capacity.
+    pub fn write(&mut self, vertices: &[Vertex], indices: &[u32], vk_device: &ash::Device) {
+        assert!(
+            self.fits(vertices.len(), indices.len()),
+            "Geometry doesn't fit into buffers"
+        );
         let mem_ptr = self.map_memory(0, self.memory_size, vk_device);
 
-        let vertices = Self::triangle_vertices();
         let mut vert_align = unsafe {
             Align::new(
                 mem_ptr,
@@ -67,10 +99,9 @@ impl GeometryBuffers {
                 self.memory_size,
             )
         };
-        vert_align.copy_from_slice(&vertices);
+        vert_align.copy_from_slice(vertices);
 
         let mem_ptr = unsafe { (mem_ptr as *mut u8).offset(self.index_buffer_offset as isize) };
-        let indices = [0u32, 1, 2];
         let mut inds_align = unsafe {
             Align::new(
                 mem_ptr as *mut c_void,
@@ -78,10 +109,11 @@ impl GeometryBuffers {
                 self.memory_size,
             )
         };
-        inds_align.copy_from_slice(&indices);
+        inds_align.copy_from_slice(indices);
         unsafe {
             vk_device.unmap_memory(self.memory);
         }
+        self.index_count = indices.len() as u32;
     }
 
     fn map_memory(
@@ -163,13 +195,13 @@ impl GeometryBuffers {
         parts * align
     }
 
-    fn create_vertex_buffer(vk_device: &ash::Device) -> vk::Buffer {
-        let size = (std::mem::size_of::<Vertex>() * 3) as u64;
+    fn create_vertex_buffer(capacity: usize, vk_device: &ash::Device) -> vk::Buffer {
+        let size = (std::mem::size_of::<Vertex>() * capacity) as u64;
         Self::create_buffer(size, vk::BufferUsageFlags::VERTEX_BUFFER, vk_device)
     }
 
-    fn create_index_buffer(vk_device: &ash::Device) -> vk::Buffer {
-        let size = (std::mem::size_of::<u32>() * 3) as u64;
+    fn create_index_buffer(capacity: usize, vk_device: &ash::Device) -> vk::Buffer {
+        let size = (std::mem::size_of::<u32>() * capacity) as u64;
         Self::create_buffer(size, vk::BufferUsageFlags::INDEX_BUFFER, vk_device)
     }
 
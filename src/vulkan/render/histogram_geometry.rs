@@ -0,0 +1,101 @@
+use crate::circles_app::scalar;
+use crate::circles_app::stats::SpeedHistogram;
+use crate::vulkan::render::vertex::Vertex;
+use glam::{Vec2, Vec4};
+
+const COUNT_COLOR: [f32; 4] = [0.3, 0.6, 1.0, 0.8];
+const EXPECTED_COLOR: [f32; 4] = [1.0, 0.5, 0.2, 1.0];
+/// Height of expected count markers, as part of the plot height.
+const MARKER_HEIGHT: f32 = 0.02;
+
+/// Bars of counts with markers of expected counts on top, filling the clip space rectangle
+/// from `min` to `max`. Bars grow towards `min.y`, the top of the screen in clip space.
+pub fn histogram_quads(
+    histogram: &SpeedHistogram,
+    min: Vec2,
+    max: Vec2,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let bins = histogram.counts.len();
+    let highest = histogram
+        .counts
+        .iter()
+        .map(|&count| count as f32)
+        .chain(histogram.expected.iter().map(|&e| scalar::narrow(e)))
+        .fold(0f32, f32::max);
+    if bins == 0 || highest == 0f32 {
+        return (vertices, indices);
+    }
+    let size = max - min;
+    let bar_width = size.x() / bins as f32;
+    let height = |value: f32| size.y() * value / highest;
+    let marker = size.y() * MARKER_HEIGHT;
+    for bin in 0..bins {
+        let left = min.x() + bin as f32 * bar_width;
+        let right = left + bar_width;
+        let count = height(histogram.counts[bin] as f32);
+        push_quad(
+            &mut vertices,
+            &mut indices,
+            Vec2::new(left, max.y() - count),
+            Vec2::new(right, max.y()),
+            COUNT_COLOR.into(),
+        );
+        let expected = height(scalar::narrow(histogram.expected[bin]));
+        push_quad(
+            &mut vertices,
+            &mut indices,
+            Vec2::new(left, max.y() - expected - marker),
+            Vec2::new(right, max.y() - expected),
+            EXPECTED_COLOR.into(),
+        );
+    }
+    (vertices, indices)
+}
+
+fn push_quad(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    min: Vec2,
+    max: Vec2,
+    color: Vec4,
+) {
+    let base = vertices.len() as u32;
+    for &(x, y) in &[
+        (min.x(), min.y()),
+        (max.x(), min.y()),
+        (max.x(), max.y()),
+        (min.x(), max.y()),
+    ] {
+        vertices.push(Vertex {
+            position: Vec4::new(x, y, 0f32, 1f32),
+            color,
+        });
+    }
+    indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::histogram_quads;
+    use crate::circles_app::stats::SpeedHistogram;
+    use glam::Vec2;
+
+    #[test]
+    fn tallest_bar_fills_plot() {
+        let histogram = SpeedHistogram {
+            bin_width: 1.0,
+            counts: vec![2, 4],
+            expected: vec![3.0, 1.0],
+        };
+        let (vertices, indices) =
+            histogram_quads(&histogram, Vec2::new(-1f32, 0f32), Vec2::new(0f32, 1f32));
+        assert_eq!(vertices.len(), 16);
+        assert_eq!(indices.len(), 24);
+        let top = |quad: usize| vertices[quad * 4].position.y();
+        assert!((top(0) - 0.5).abs() < 1e-6);
+        assert!((top(2) - 0f32).abs() < 1e-6);
+        assert!((vertices[9].position.x() - 0f32).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::vulkan::base::physical_device::PhysicalDevice;
+use crate::vulkan::base::VulkanBase;
+use crate::vulkan::render::geometry_buffers::GeometryBuffers;
+use crate::vulkan::render::vertex::SphereInstance;
+use ash::util::Align;
+use ash::version::DeviceV1_0;
+use ash::vk;
+use slog::Logger;
+
+/// Host visible vertex buffer with per instance data of sphere impostors.
+pub struct InstanceBuffer {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    memory_size: vk::DeviceSize,
+    capacity: usize,
+    count: u32,
+    logger: Logger,
+}
+
+impl InstanceBuffer {
+    pub fn with_capacity(base: &VulkanBase, capacity: usize, logger: Logger) -> Self {
+        let vk_device = base.get_device().get_vk_device();
+        let size = (std::mem::size_of::<SphereInstance>() * capacity.max(1)) as u64;
+        let buffer =
+            GeometryBuffers::create_buffer(size, vk::BufferUsageFlags::VERTEX_BUFFER, vk_device);
+        let mem_req = unsafe { vk_device.get_buffer_memory_requirements(buffer) };
+        let memory = Self::allocate_memory(base.get_physical_device(), vk_device, &mem_req);
+        unsafe { vk_device.bind_buffer_memory(buffer, memory, 0) }
+            .expect("Can't bind instance buffer memory");
+
+        Self {
+            buffer,
+            memory,
+            memory_size: mem_req.size,
+            capacity,
+            count: 0,
+            logger,
+        }
+    }
+
+    pub fn get_buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    pub fn fits(&self, count: usize) -> bool {
+        count <= self.capacity
+    }
+
+    /// Number of instances written by the last `write`.
+    pub fn get_count(&self) -> u32 {
+        self.count
+    }
+
+    /// Panics if instances exceed the capacity.
+    pub fn write(&mut self, instances: &[SphereInstance], vk_device: &ash::Device) {
+        assert!(
+            self.fits(instances.len()),
+            "Instances don't fit into buffer"
+        );
+        let mem_ptr = unsafe {
+            vk_device.map_memory(
+                self.memory,
+                0,
+                self.memory_size,
+                vk::MemoryMapFlags::empty(),
+            )
+        }
+        .expect("Can't map instance buffer memory.");
+        let mut align = unsafe {
+            Align::new(
+                mem_ptr,
+                std::mem::align_of::<SphereInstance>() as vk::DeviceSize,
+                self.memory_size,
+            )
+        };
+        align.copy_from_slice(instances);
+        unsafe {
+            vk_device.unmap_memory(self.memory);
+        }
+        self.count = instances.len() as u32;
+    }
+
+    fn allocate_memory(
+        pdevice: &PhysicalDevice,
+        vk_device: &ash::Device,
+        mem_req: &vk::MemoryRequirements,
+    ) -> vk::DeviceMemory {
+        let memory_type_index = pdevice
+            .find_memorytype_index(mem_req, vk::MemoryPropertyFlags::HOST_VISIBLE)
+            .expect("Can't find suit memory type for instance buffer.");
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(mem_req.size)
+            .memory_type_index(memory_type_index);
+
+        unsafe { vk_device.allocate_memory(&allocate_info, None) }
+            .expect("Can't allocate memory for instance buffer.")
+    }
+
+    pub fn destroy(&mut self, vk_device: &ash::Device) {
+        debug!(self.logger, "Instance buffer destroy() called");
+        unsafe {
+            vk_device.destroy_buffer(self.buffer, None);
+            debug!(self.logger, "\tInstance buffer destroyed");
+            vk_device.free_memory(self.memory, None);
+            debug!(self.logger, "\tInstance buffer memory freed");
+        }
+    }
+}
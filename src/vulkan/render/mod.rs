@@ -1,26 +1,40 @@
+pub mod circle_geometry;
 pub mod depth_image;
 pub mod framebuffers;
 pub mod geometry_buffers;
+pub mod histogram_geometry;
+pub mod instance_buffer;
+pub mod offscreen;
+#[macro_use]
 pub mod pipeline;
 pub mod render_pass;
 pub mod semaphores;
+pub mod sphere_geometry;
+pub mod sphere_pipeline;
 pub mod vertex;
 
 use super::base::VulkanBase;
 use super::present::VulkanPresent;
 use crate::vulkan::render::framebuffers::Framebuffers;
 use crate::vulkan::render::geometry_buffers::GeometryBuffers;
+use crate::vulkan::render::instance_buffer::InstanceBuffer;
 use crate::vulkan::render::pipeline::Pipeline;
 use crate::vulkan::render::render_pass::RenderPass;
+use crate::vulkan::render::sphere_pipeline::{SpherePipeline, ViewProjection, VERTICES_PER_SPHERE};
+use crate::vulkan::render::vertex::{SphereInstance, Vertex};
 use ash::version::DeviceV1_0;
 use ash::vk;
 use depth_image::DepthImage;
+use glam::Mat4;
 use semaphores::Semaphores;
 use slog::Logger;
 
 pub struct VulkanRenderer {
     pipeline: Pipeline,
     geometry_buffers: GeometryBuffers,
+    sphere_pipeline: SpherePipeline,
+    sphere_instances: InstanceBuffer,
+    view_projection: ViewProjection,
     framebuffers: Framebuffers,
     render_pass: RenderPass,
     semaphores: Semaphores,
@@ -30,17 +44,32 @@ pub struct VulkanRenderer {
 
 impl VulkanRenderer {
     pub fn new(base: &VulkanBase, presenter: &VulkanPresent, logger: Logger) -> Self {
-        let depth_image = DepthImage::new(base, presenter, logger.clone());
+        let swapchain = presenter.get_swapchain();
+        let resolution = swapchain.get_resolution();
+        let depth_image = DepthImage::new(base, resolution, logger.clone());
         let semaphores = Semaphores::new(base, logger.clone());
-        let render_pass = RenderPass::new(base, presenter, logger.clone());
+        let render_pass = RenderPass::new(
+            base,
+            swapchain.get_surface_format().format,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+            logger.clone(),
+        );
         let framebuffers =
             Framebuffers::new(base, presenter, &depth_image, &render_pass, logger.clone());
         let mut geometry_buffers = GeometryBuffers::new(base, logger.clone());
         geometry_buffers.write_triangle(base.get_device().get_vk_device());
-        let pipeline = Pipeline::new(base, presenter, logger.clone(), &render_pass);
+        let pipeline = Pipeline::new(base, resolution, logger.clone(), &render_pass);
+        let sphere_pipeline = SpherePipeline::new(base, resolution, logger.clone(), &render_pass);
+        let sphere_instances = InstanceBuffer::with_capacity(base, 1, logger.clone());
         Self {
             pipeline,
             geometry_buffers,
+            sphere_pipeline,
+            sphere_instances,
+            view_projection: ViewProjection {
+                view: Mat4::identity(),
+                projection: Mat4::identity(),
+            },
             depth_image,
             logger,
             semaphores,
@@ -118,7 +147,37 @@ impl VulkanRenderer {
                 vk::IndexType::UINT32,
             );
 
-            vk_device.cmd_draw_indexed(command_buffer, 3, 1, 0, 0, 1);
+            vk_device.cmd_draw_indexed(
+                command_buffer,
+                self.geometry_buffers.get_index_count(),
+                1,
+                0,
+                0,
+                1,
+            );
+
+            let sphere_count = self.sphere_instances.get_count();
+            if sphere_count > 0 {
+                vk_device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.sphere_pipeline.get_vk_pipeline(),
+                );
+                vk_device.cmd_push_constants(
+                    command_buffer,
+                    self.sphere_pipeline.get_layout(),
+                    SpherePipeline::get_push_constant_stages(),
+                    0,
+                    self.view_projection.as_bytes(),
+                );
+                vk_device.cmd_bind_vertex_buffers(
+                    command_buffer,
+                    0,
+                    &[self.sphere_instances.get_buffer()],
+                    &[0],
+                );
+                vk_device.cmd_draw(command_buffer, VERTICES_PER_SPHERE, sphere_count, 0, 0);
+            }
             vk_device.cmd_end_render_pass(command_buffer);
 
             vk_device
@@ -129,6 +188,42 @@ impl VulkanRenderer {
         present_index
     }
 
+    /// Replaces drawn geometry, growing buffers if needed. Must not be called during a frame.
+    pub fn write_geometry(&mut self, base: &VulkanBase, vertices: &[Vertex], indices: &[u32]) {
+        let vk_device = base.get_device().get_vk_device();
+        if !self.geometry_buffers.fits(vertices.len(), indices.len()) {
+            self.geometry_buffers.destroy(vk_device);
+            self.geometry_buffers = GeometryBuffers::with_capacity(
+                base,
+                vertices.len().next_power_of_two(),
+                indices.len().next_power_of_two(),
+                self.logger.clone(),
+            );
+        }
+        self.geometry_buffers.write(vertices, indices, vk_device);
+    }
+
+    /// Replaces drawn sphere impostors, seen through `view_projection`. Must not be called
+    /// during a frame.
+    pub fn write_spheres(
+        &mut self,
+        base: &VulkanBase,
+        instances: &[SphereInstance],
+        view_projection: ViewProjection,
+    ) {
+        let vk_device = base.get_device().get_vk_device();
+        if !self.sphere_instances.fits(instances.len()) {
+            self.sphere_instances.destroy(vk_device);
+            self.sphere_instances = InstanceBuffer::with_capacity(
+                base,
+                instances.len().next_power_of_two(),
+                self.logger.clone(),
+            );
+        }
+        self.sphere_instances.write(instances, vk_device);
+        self.view_projection = view_projection;
+    }
+
     pub fn render(&self, base: &VulkanBase, present: &VulkanPresent) {
         let present_index = self.record_render_command_buffer(base, present);
 
@@ -168,6 +263,8 @@ impl VulkanRenderer {
         let vk_device = base.get_device().get_vk_device();
         self.pipeline.destroy(vk_device);
         self.geometry_buffers.destroy(vk_device);
+        self.sphere_pipeline.destroy(vk_device);
+        self.sphere_instances.destroy(vk_device);
         self.framebuffers.destroy(vk_device);
         self.render_pass.destroy(vk_device);
         self.semaphores.destroy(vk_device);
@@ -0,0 +1,236 @@
+use crate::vulkan::base::VulkanBase;
+use crate::vulkan::render::depth_image::DepthImage;
+use crate::vulkan::render::geometry_buffers::GeometryBuffers;
+use crate::vulkan::render::pipeline::Pipeline;
+use crate::vulkan::render::render_pass::RenderPass;
+use crate::vulkan::render::vertex::Vertex;
+use ash::version::DeviceV1_0;
+use ash::vk;
+use slog::Logger;
+
+/// Renders geometry into an image without a window, e.g. for benchmarks.
+pub struct OffscreenRenderer {
+    pipeline: Pipeline,
+    geometry_buffers: GeometryBuffers,
+    framebuffer: vk::Framebuffer,
+    render_pass: RenderPass,
+    depth_image: DepthImage,
+    color_image: vk::Image,
+    color_view: vk::ImageView,
+    color_memory: vk::DeviceMemory,
+    resolution: vk::Extent2D,
+    logger: Logger,
+}
+
+impl OffscreenRenderer {
+    pub fn new(
+        base: &VulkanBase,
+        resolution: vk::Extent2D,
+        vertex_capacity: usize,
+        index_capacity: usize,
+        logger: Logger,
+    ) -> Self {
+        let vk_device = base.get_device().get_vk_device();
+        let (color_image, color_memory) = Self::create_color_image(base, resolution);
+        let color_view = Self::create_color_view(vk_device, color_image);
+        let depth_image = DepthImage::new(base, resolution, logger.clone());
+        let render_pass = RenderPass::new(
+            base,
+            Self::get_color_format(),
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            logger.clone(),
+        );
+        let attachments = [color_view, depth_image.get_view()];
+        let framebuffer_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass.get_vk_render_pass())
+            .attachments(&attachments)
+            .width(resolution.width)
+            .height(resolution.height)
+            .layers(1);
+        let framebuffer = unsafe { vk_device.create_framebuffer(&framebuffer_info, None) }
+            .expect("Can't create offscreen framebuffer.");
+        let geometry_buffers =
+            GeometryBuffers::with_capacity(base, vertex_capacity, index_capacity, logger.clone());
+        let pipeline = Pipeline::new(base, resolution, logger.clone(), &render_pass);
+        Self {
+            pipeline,
+            geometry_buffers,
+            framebuffer,
+            render_pass,
+            depth_image,
+            color_image,
+            color_view,
+            color_memory,
+            resolution,
+            logger,
+        }
+    }
+
+    pub fn get_color_format() -> vk::Format {
+        vk::Format::R8G8B8A8_UNORM
+    }
+
+    pub fn write_geometry(&mut self, base: &VulkanBase, vertices: &[Vertex], indices: &[u32]) {
+        self.geometry_buffers
+            .write(vertices, indices, base.get_device().get_vk_device());
+    }
+
+    /// Draws the written geometry and waits until the frame is finished.
+    pub fn render(&self, base: &VulkanBase) {
+        let vk_device = base.get_device().get_vk_device();
+        let command_buffer = base.get_command_buffers().get_render();
+
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 0.0],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass.get_vk_render_pass())
+            .framebuffer(self.framebuffer)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: self.resolution,
+            })
+            .clear_values(&clear_values);
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+        unsafe {
+            vk_device
+                .begin_command_buffer(command_buffer, &begin_info)
+                .expect("Can't begin offscreen command buffer");
+            vk_device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+            vk_device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.get_vk_pipeline(),
+            );
+            vk_device.cmd_set_viewport(
+                command_buffer,
+                0,
+                &Pipeline::get_viewports(self.resolution),
+            );
+            vk_device.cmd_set_scissor(command_buffer, 0, &Pipeline::get_scissors(self.resolution));
+            vk_device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[self.geometry_buffers.get_vertex_buffer()],
+                &[0],
+            );
+            vk_device.cmd_bind_index_buffer(
+                command_buffer,
+                self.geometry_buffers.get_index_buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+            vk_device.cmd_draw_indexed(
+                command_buffer,
+                self.geometry_buffers.get_index_count(),
+                1,
+                0,
+                0,
+                0,
+            );
+            vk_device.cmd_end_render_pass(command_buffer);
+            vk_device
+                .end_command_buffer(command_buffer)
+                .expect("Can't end offscreen command buffer");
+        }
+
+        let command_buffers = [command_buffer];
+        let submit_info = vk::SubmitInfo::builder().command_buffers(&command_buffers);
+        let queue = base.get_device().get_vk_queue();
+        unsafe {
+            vk_device
+                .queue_submit(queue, &[submit_info.build()], vk::Fence::null())
+                .expect("Offscreen queue submit failed.");
+            vk_device
+                .queue_wait_idle(queue)
+                .expect("Can't wait queue idle after offscreen render.");
+        }
+    }
+
+    fn create_color_image(
+        base: &VulkanBase,
+        resolution: vk::Extent2D,
+    ) -> (vk::Image, vk::DeviceMemory) {
+        let vk_device = base.get_device().get_vk_device();
+        let create_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(Self::get_color_format())
+            .extent(vk::Extent3D {
+                width: resolution.width,
+                height: resolution.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let image = unsafe { vk_device.create_image(&create_info, None) }
+            .expect("Can't create offscreen color image.");
+
+        let memory_requirements = unsafe { vk_device.get_image_memory_requirements(image) };
+        let memory_type_index = base
+            .get_physical_device()
+            .find_memorytype_index(&memory_requirements, vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .expect("Unable to find suitable memory index for offscreen color image.");
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(memory_requirements.size)
+            .memory_type_index(memory_type_index);
+        let memory = unsafe { vk_device.allocate_memory(&allocate_info, None) }
+            .expect("Can't allocate memory for offscreen color image");
+        unsafe { vk_device.bind_image_memory(image, memory, 0) }
+            .expect("Can't bind offscreen color image memory");
+        (image, memory)
+    }
+
+    fn create_color_view(vk_device: &ash::Device, image: vk::Image) -> vk::ImageView {
+        let create_info = vk::ImageViewCreateInfo::builder()
+            .subresource_range(
+                vk::ImageSubresourceRange::builder()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .level_count(1)
+                    .layer_count(1)
+                    .build(),
+            )
+            .image(image)
+            .format(Self::get_color_format())
+            .view_type(vk::ImageViewType::TYPE_2D);
+        unsafe { vk_device.create_image_view(&create_info, None) }
+            .expect("Can't create offscreen color image view.")
+    }
+
+    pub fn destroy(&mut self, base: &VulkanBase) {
+        debug!(self.logger, "Offscreen renderer destroy() called");
+        let vk_device = base.get_device().get_vk_device();
+        self.pipeline.destroy(vk_device);
+        self.geometry_buffers.destroy(vk_device);
+        unsafe {
+            vk_device.destroy_framebuffer(self.framebuffer, None);
+        }
+        self.render_pass.destroy(vk_device);
+        self.depth_image.destroy(vk_device);
+        unsafe {
+            vk_device.destroy_image_view(self.color_view, None);
+            vk_device.destroy_image(self.color_image, None);
+            vk_device.free_memory(self.color_memory, None);
+        }
+        debug!(self.logger, "\tOffscreen color image destroyed");
+    }
+}
@@ -1,5 +1,4 @@
 use crate::vulkan::base::VulkanBase;
-use crate::vulkan::present::VulkanPresent;
 use crate::vulkan::render::render_pass::RenderPass;
 use crate::vulkan::render::vertex::Vertex;
 use ash::version::DeviceV1_0;
@@ -27,7 +26,7 @@ pub struct Pipeline {
 impl Pipeline {
     pub fn new(
         base: &VulkanBase,
-        presenter: &VulkanPresent,
+        surface_resolution: vk::Extent2D,
         logger: Logger,
         render_pass: &RenderPass,
     ) -> Self {
@@ -87,8 +86,6 @@ impl Pipeline {
             ..Default::default()
         };
 
-        let surface_resolution = presenter.get_swapchain().get_resolution();
-
         let viewports = Self::get_viewports(surface_resolution);
         let scissors = Self::get_scissors(surface_resolution);
 
@@ -211,7 +208,7 @@ impl Pipeline {
         (vert_shader_module, frag_shader_module)
     }
 
-    fn create_shader_module(
+    pub fn create_shader_module(
         cursor: &mut Cursor<&[u8]>,
         vk_device: &ash::Device,
     ) -> vk::ShaderModule {
@@ -1,5 +1,4 @@
 use crate::vulkan::base::VulkanBase;
-use crate::vulkan::present::VulkanPresent;
 use crate::vulkan::render::depth_image::DepthImage;
 use ash::version::DeviceV1_0;
 use ash::vk;
@@ -11,9 +10,13 @@ pub struct RenderPass {
 }
 
 impl RenderPass {
-    pub fn new(base: &VulkanBase, presenter: &VulkanPresent, logger: Logger) -> Self {
-        let color_format = presenter.get_swapchain().get_surface_format().format;
-        let attachments = Self::create_attachment_descrs(color_format);
+    pub fn new(
+        base: &VulkanBase,
+        color_format: vk::Format,
+        color_final_layout: vk::ImageLayout,
+        logger: Logger,
+    ) -> Self {
+        let attachments = Self::create_attachment_descrs(color_format, color_final_layout);
 
         let color_attachment_refs = [vk::AttachmentReference {
             attachment: 0,
@@ -24,12 +27,18 @@ impl RenderPass {
             layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
         };
 
+        // Depth image is shared by all frames, so depth tests wait for the previous frame too.
         let dependencies = [vk::SubpassDependency {
             src_subpass: vk::SUBPASS_EXTERNAL,
-            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            src_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
             dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
-                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
             ..Default::default()
         }];
 
@@ -55,19 +64,26 @@ impl RenderPass {
         }
     }
 
-    fn create_attachment_descrs(color_format: vk::Format) -> [vk::AttachmentDescription; 2] {
-        let color_attachment_descr = Self::create_color_attachment_descr(color_format);
+    fn create_attachment_descrs(
+        color_format: vk::Format,
+        color_final_layout: vk::ImageLayout,
+    ) -> [vk::AttachmentDescription; 2] {
+        let color_attachment_descr =
+            Self::create_color_attachment_descr(color_format, color_final_layout);
         let depth_attachment_descr = Self::create_depth_attachment_descr();
         [color_attachment_descr, depth_attachment_descr]
     }
 
-    fn create_color_attachment_descr(format: vk::Format) -> vk::AttachmentDescription {
+    fn create_color_attachment_descr(
+        format: vk::Format,
+        final_layout: vk::ImageLayout,
+    ) -> vk::AttachmentDescription {
         vk::AttachmentDescription {
             format,
             samples: vk::SampleCountFlags::TYPE_1,
             load_op: vk::AttachmentLoadOp::CLEAR,
             store_op: vk::AttachmentStoreOp::STORE,
-            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            final_layout,
             ..Default::default()
         }
     }
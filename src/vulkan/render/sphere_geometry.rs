@@ -0,0 +1,35 @@
+use crate::circles_app::scalar;
+use crate::circles_app::sphere::Sphere;
+use crate::vulkan::render::vertex::SphereInstance;
+
+/// Impostor instances in world coordinates, the camera is applied in the vertex shader.
+pub fn sphere_instances<'a>(spheres: impl Iterator<Item = &'a Sphere>) -> Vec<SphereInstance> {
+    spheres
+        .map(|sphere| SphereInstance {
+            center_radius: scalar::to_render3(sphere.center())
+                .extend(scalar::narrow(sphere.radius())),
+            color: sphere.color(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sphere_instances;
+    use crate::circles_app::scalar::Vector3;
+    use crate::circles_app::sphere::Sphere;
+    use glam::Vec4;
+
+    #[test]
+    fn instances_pack_radius_into_w() {
+        let mut sphere = Sphere::new(Vector3::new(1.0, 2.0, 3.0), 0.5, Vector3::zero());
+        sphere.set_color(Vec4::new(1f32, 0f32, 0f32, 1f32));
+        let instances = sphere_instances([sphere].iter());
+        assert_eq!(instances.len(), 1);
+        assert_eq!(
+            instances[0].center_radius,
+            Vec4::new(1f32, 2f32, 3f32, 0.5f32)
+        );
+        assert_eq!(instances[0].color, sphere.color());
+    }
+}
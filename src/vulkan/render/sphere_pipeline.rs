@@ -0,0 +1,219 @@
+use crate::vulkan::base::VulkanBase;
+use crate::vulkan::render::pipeline::Pipeline;
+use crate::vulkan::render::render_pass::RenderPass;
+use crate::vulkan::render::vertex::SphereInstance;
+use ash::version::DeviceV1_0;
+use ash::vk;
+use glam::Mat4;
+use slog::Logger;
+use std::ffi::CString;
+use std::io::Cursor;
+
+/// Vertices of the billboard drawn for each instance, generated in the vertex shader.
+pub const VERTICES_PER_SPHERE: u32 = 6;
+
+/// Push constants shared by both sphere shaders.
+#[derive(Debug, Copy, Clone)]
+pub struct ViewProjection {
+    pub view: Mat4,
+    pub projection: Mat4,
+}
+
+impl ViewProjection {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                std::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
+
+/// Draws instanced sphere impostors: billboards ray cast in the fragment shader, which writes
+/// depth of the hit point, so intersecting spheres are resolved by the depth test.
+pub struct SpherePipeline {
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    logger: Logger,
+}
+
+impl SpherePipeline {
+    pub fn new(
+        base: &VulkanBase,
+        surface_resolution: vk::Extent2D,
+        logger: Logger,
+        render_pass: &RenderPass,
+    ) -> Self {
+        let vk_device = base.get_device().get_vk_device();
+
+        let (vert_shader, frag_shader) = Self::load_shaders(vk_device);
+
+        let layout = Self::create_layout(vk_device);
+
+        let shader_entry_name = CString::new("main").unwrap();
+        let shader_stage_create_infos = [
+            vk::PipelineShaderStageCreateInfo {
+                module: vert_shader,
+                p_name: shader_entry_name.as_ptr(),
+                stage: vk::ShaderStageFlags::VERTEX,
+                ..Default::default()
+            },
+            vk::PipelineShaderStageCreateInfo {
+                module: frag_shader,
+                p_name: shader_entry_name.as_ptr(),
+                stage: vk::ShaderStageFlags::FRAGMENT,
+                ..Default::default()
+            },
+        ];
+
+        let vertex_input_binding_descriptions = [vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: std::mem::size_of::<SphereInstance>() as u32,
+            input_rate: vk::VertexInputRate::INSTANCE,
+        }];
+        let vertex_input_attribute_descriptions = [
+            vk::VertexInputAttributeDescription {
+                location: 0,
+                binding: 0,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: offset_of!(SphereInstance, center_radius) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                location: 1,
+                binding: 0,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: offset_of!(SphereInstance, color) as u32,
+            },
+        ];
+
+        let vertex_input_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&vertex_input_binding_descriptions)
+            .vertex_attribute_descriptions(&vertex_input_attribute_descriptions);
+
+        let vertex_input_assembly_state_info = vk::PipelineInputAssemblyStateCreateInfo {
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            ..Default::default()
+        };
+
+        let viewports = Pipeline::get_viewports(surface_resolution);
+        let scissors = Pipeline::get_scissors(surface_resolution);
+
+        let viewport_state_info = vk::PipelineViewportStateCreateInfo::builder()
+            .scissors(&scissors)
+            .viewports(&viewports);
+
+        let rasterization_info = vk::PipelineRasterizationStateCreateInfo {
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            line_width: 1.0,
+            polygon_mode: vk::PolygonMode::FILL,
+            ..Default::default()
+        };
+        let multisample_state_info = vk::PipelineMultisampleStateCreateInfo {
+            rasterization_samples: vk::SampleCountFlags::TYPE_1,
+            ..Default::default()
+        };
+        let noop_stencil_state = vk::StencilOpState {
+            fail_op: vk::StencilOp::KEEP,
+            pass_op: vk::StencilOp::KEEP,
+            depth_fail_op: vk::StencilOp::KEEP,
+            compare_op: vk::CompareOp::ALWAYS,
+            ..Default::default()
+        };
+        let depth_state_info = vk::PipelineDepthStencilStateCreateInfo {
+            depth_test_enable: 1,
+            depth_write_enable: 1,
+            depth_compare_op: vk::CompareOp::LESS,
+            front: noop_stencil_state,
+            back: noop_stencil_state,
+            max_depth_bounds: 1.0,
+            ..Default::default()
+        };
+        let color_blend_attachment_states = [vk::PipelineColorBlendAttachmentState {
+            blend_enable: 0,
+            color_write_mask: vk::ColorComponentFlags::all(),
+            ..Default::default()
+        }];
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .attachments(&color_blend_attachment_states);
+
+        let dynamic_state = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_state_info =
+            vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_state);
+
+        let pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&shader_stage_create_infos)
+            .vertex_input_state(&vertex_input_state_info)
+            .input_assembly_state(&vertex_input_assembly_state_info)
+            .viewport_state(&viewport_state_info)
+            .rasterization_state(&rasterization_info)
+            .multisample_state(&multisample_state_info)
+            .depth_stencil_state(&depth_state_info)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state_info)
+            .layout(layout)
+            .render_pass(render_pass.get_vk_render_pass());
+
+        let pipeline = unsafe {
+            vk_device.create_graphics_pipelines(
+                vk::PipelineCache::null(),
+                &[pipeline_info.build()],
+                None,
+            )
+        }
+        .expect("Can't create sphere pipeline.")
+        .remove(0);
+
+        unsafe {
+            vk_device.destroy_shader_module(vert_shader, None);
+            vk_device.destroy_shader_module(frag_shader, None);
+        }
+        Self {
+            pipeline,
+            layout,
+            logger,
+        }
+    }
+
+    pub fn get_vk_pipeline(&self) -> vk::Pipeline {
+        self.pipeline
+    }
+
+    pub fn get_layout(&self) -> vk::PipelineLayout {
+        self.layout
+    }
+
+    pub fn get_push_constant_stages() -> vk::ShaderStageFlags {
+        vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT
+    }
+
+    fn create_layout(vk_device: &ash::Device) -> vk::PipelineLayout {
+        let push_constant_ranges = [vk::PushConstantRange {
+            stage_flags: Self::get_push_constant_stages(),
+            offset: 0,
+            size: std::mem::size_of::<ViewProjection>() as u32,
+        }];
+        let create_info =
+            vk::PipelineLayoutCreateInfo::builder().push_constant_ranges(&push_constant_ranges);
+        unsafe { vk_device.create_pipeline_layout(&create_info, None) }
+            .expect("Can't create sphere pipeline layout")
+    }
+
+    fn load_shaders(vk_device: &ash::Device) -> (vk::ShaderModule, vk::ShaderModule) {
+        let mut vert_spv_file = Cursor::new(&include_bytes!("../../shaders/sphere/vert.spv")[..]);
+        let mut frag_spv_file = Cursor::new(&include_bytes!("../../shaders/sphere/frag.spv")[..]);
+        let vert_shader_module = Pipeline::create_shader_module(&mut vert_spv_file, vk_device);
+        let frag_shader_module = Pipeline::create_shader_module(&mut frag_spv_file, vk_device);
+        (vert_shader_module, frag_shader_module)
+    }
+
+    pub fn destroy(&mut self, vk_device: &ash::Device) {
+        debug!(self.logger, "Sphere pipeline destroy() called.");
+        unsafe {
+            vk_device.destroy_pipeline(self.pipeline, None);
+            debug!(self.logger, "\tSphere pipeline destroyed");
+            vk_device.destroy_pipeline_layout(self.layout, None);
+            debug!(self.logger, "\tSphere pipeline layout destroyed");
+        }
+    }
+}
@@ -3,3 +3,11 @@ pub struct Vertex {
     pub position: glam::Vec4,
     pub color: glam::Vec4,
 }
+
+/// Per instance input of the sphere impostor pipeline.
+#[derive(Debug, Copy, Clone)]
+pub struct SphereInstance {
+    /// Center in world coordinates, radius in `w`.
+    pub center_radius: glam::Vec4,
+    pub color: glam::Vec4,
+}
@@ -0,0 +1,184 @@
+use circles::circles_app::circle::Circle;
+use circles::circles_app::generators;
+use circles::circles_app::material::Material;
+use circles::circles_app::scalar::consts::PI;
+use circles::circles_app::scalar::{seconds, Scalar, Vector};
+use circles::circles_app::simulation::Simulation;
+use circles::circles_app::stats::SpeedHistogram;
+use quickcheck::{quickcheck, TestResult};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+use slog::{o, Discard, Logger};
+use std::time::Duration;
+
+/// Relative error of momentum and energy after a single collision, limited by floating point rounding.
+const COLLISION_TOLERANCE: Scalar = 1e-4;
+/// Relative deviation of P·A from N·T. Finite size and pressure sampling noise stay below it.
+const IDEAL_GAS_TOLERANCE: Scalar = 0.1;
+/// Chi-squared critical value at 0.1% significance for up to 20 degrees of freedom.
+const MAXWELL_CHI_SQUARED: Scalar = 45.3;
+/// Relative error of position and velocity of a falling circle after 5 seconds.
+const FREE_FALL_TOLERANCE: Scalar = 1e-4;
+
+fn logger() -> Logger {
+    Logger::root(Discard, o!())
+}
+
+fn relative_error(actual: Scalar, expected: Scalar, scale: Scalar) -> Scalar {
+    (actual - expected).abs() / scale.max(Scalar::EPSILON)
+}
+
+/// Two touching circles with velocities and materials from the fuzzer input.
+fn touching_pair(
+    (r1, r2): (u8, u8),
+    angle: u8,
+    (v1x, v1y, v2x, v2y): (i8, i8, i8, i8),
+    (d1, d2): (u8, u8),
+    restitution: Scalar,
+) -> (Circle, Circle) {
+    let r1 = 1.0 + Scalar::from(r1) / 16.0;
+    let r2 = 1.0 + Scalar::from(r2) / 16.0;
+    let angle = Scalar::from(angle) / 256.0 * 2.0 * PI;
+    let c2 = Vector::new(angle.cos(), angle.sin()) * (r1 + r2);
+    let mut a = Circle::new(Vector::zero(), r1, Vector::new(v1x.into(), v1y.into()));
+    let mut b = Circle::new(c2, r2, Vector::new(v2x.into(), v2y.into()));
+    a.set_material(Material::new(0.1 + Scalar::from(d1) / 32.0, restitution));
+    b.set_material(Material::new(0.1 + Scalar::from(d2) / 32.0, restitution));
+    (a, b)
+}
+
+fn approaching(a: &Circle, b: &Circle) -> bool {
+    (b.speed() - a.speed()).dot(b.center() - a.center()) < 0.0
+}
+
+fn momentum(a: &Circle, b: &Circle) -> Vector {
+    a.speed() * a.mass() + b.speed() * b.mass()
+}
+
+quickcheck! {
+    fn elastic_collision_conserves_momentum_and_energy(
+        radii: (u8, u8),
+        angle: u8,
+        speeds: (i8, i8, i8, i8),
+        densities: (u8, u8)
+    ) -> TestResult {
+        let (mut a, mut b) = touching_pair(radii, angle, speeds, densities, 1.0);
+        if !approaching(&a, &b) {
+            return TestResult::discard();
+        }
+        let p = momentum(&a, &b);
+        let e = a.kinetic_energy() + b.kinetic_energy();
+        let p_scale = a.speed().length() * a.mass() + b.speed().length() * b.mass();
+        a.collide(&mut b);
+
+        let dp = (momentum(&a, &b) - p).length() / p_scale;
+        let de = relative_error(a.kinetic_energy() + b.kinetic_energy(), e, e);
+        TestResult::from_bool(dp < COLLISION_TOLERANCE && de < COLLISION_TOLERANCE)
+    }
+
+    fn inelastic_collision_conserves_momentum_and_loses_energy(
+        radii: (u8, u8),
+        angle: u8,
+        speeds: (i8, i8, i8, i8),
+        densities: (u8, u8),
+        restitution: u8
+    ) -> TestResult {
+        let restitution = Scalar::from(restitution) / 255.0;
+        let (mut a, mut b) = touching_pair(radii, angle, speeds, densities, restitution);
+        if !approaching(&a, &b) {
+            return TestResult::discard();
+        }
+        let p = momentum(&a, &b);
+        let e = a.kinetic_energy() + b.kinetic_energy();
+        let p_scale = a.speed().length() * a.mass() + b.speed().length() * b.mass();
+        a.collide(&mut b);
+
+        let dp = (momentum(&a, &b) - p).length() / p_scale;
+        let e_after = a.kinetic_energy() + b.kinetic_energy();
+        TestResult::from_bool(dp < COLLISION_TOLERANCE && e_after <= e * (1.0 + COLLISION_TOLERANCE))
+    }
+}
+
+#[test]
+fn ideal_gas_law() {
+    // Dilute gas: packing fraction ~0.2%, so excluded area correction is well within tolerance.
+    let size = 200.0;
+    let mut sim = Simulation::new((size, size).into(), logger());
+    for circle in generators::poisson_disk((size, size).into(), 300, (0.2, 0.4), 30.0, 7) {
+        sim.add_circle(circle);
+    }
+    sim.set_pressure_window(Duration::from_secs(30));
+    for _ in 0..3000 {
+        sim.step(Duration::from_millis(10));
+    }
+
+    let observables = sim.observables();
+    let pv = observables.pressure * size * size;
+    let nkt = observables.count as Scalar * observables.temperature;
+    assert!(
+        relative_error(pv, nkt, nkt) < IDEAL_GAS_TOLERANCE,
+        "PV = {}, NkT = {}",
+        pv,
+        nkt
+    );
+}
+
+#[test]
+fn relaxation_to_maxwell_boltzmann() {
+    let size = 300.0;
+    let speed = 40.0;
+    let mut rng = Pcg64Mcg::seed_from_u64(44);
+    let mut sim = Simulation::new((size, size).into(), logger());
+    // Equal circles with equal speeds in random directions.
+    for circle in generators::square_lattice((size, size).into(), 12.0, 3.0, 0.0, 0) {
+        let angle = rng.gen_range(0.0, 2.0 * PI);
+        let mut circle = circle;
+        circle.set_speed(Vector::new(angle.cos(), angle.sin()) * speed);
+        sim.add_circle(circle);
+    }
+    let histogram = |sim: &Simulation| {
+        let circles: Vec<Circle> = sim.circles().map(|(_, circle)| *circle).collect();
+        SpeedHistogram::new(&circles, 20, 3.0 * speed, sim.temperature()).chi_squared()
+    };
+    let initial = histogram(&sim);
+    for _ in 0..1500 {
+        sim.step(Duration::from_millis(10));
+    }
+    let relaxed = histogram(&sim);
+    assert!(initial > 10.0 * MAXWELL_CHI_SQUARED, "initial {}", initial);
+    assert!(relaxed < MAXWELL_CHI_SQUARED, "relaxed {}", relaxed);
+}
+
+#[test]
+fn free_fall() {
+    let gravity = Vector::new(0.0, 9.8);
+    let start = Vector::new(500.0, 100.0);
+    let initial_speed = Vector::new(3.0, -20.0);
+    let mut sim = Simulation::new((1000.0, 1000.0).into(), logger());
+    sim.set_gravity(gravity);
+    let handle = sim.add_circle(Circle::new(start, 1.0, initial_speed));
+
+    let dt = Duration::from_millis(10);
+    for _ in 0..500 {
+        sim.step(dt);
+    }
+
+    let t = seconds(sim.time());
+    let expected_center = start + initial_speed * t + gravity * (t * t / 2.0);
+    let expected_speed = initial_speed + gravity * t;
+    let circle = sim.circle(handle).unwrap();
+    let position_error = (circle.center() - expected_center).length();
+    let speed_error = (circle.speed() - expected_speed).length();
+    assert!(
+        position_error < FREE_FALL_TOLERANCE * expected_center.length(),
+        "{:?} vs {:?}",
+        circle.center(),
+        expected_center
+    );
+    assert!(
+        speed_error < FREE_FALL_TOLERANCE * expected_speed.length(),
+        "{:?} vs {:?}",
+        circle.speed(),
+        expected_speed
+    );
+}